@@ -0,0 +1,253 @@
+use super::{CacheEntry, CacheStats, EntryMetadata, PrunePolicy};
+use crate::error::{Error, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long to wait for the cache server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a whole request may take; a slow cache should not hold up a
+/// commit for longer than asking the provider would.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The body of `PUT /entries/{key}`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct StoreRequest {
+    pub value: String,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+}
+
+/// The body of `POST /prune`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PruneRequest {
+    /// In seconds.
+    pub max_age: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+impl From<&PrunePolicy> for PruneRequest {
+    fn from(policy: &PrunePolicy) -> Self {
+        Self {
+            max_age: policy.max_age.map(|age| age.as_secs()),
+            max_bytes: policy.max_bytes,
+        }
+    }
+}
+
+impl From<PruneRequest> for PrunePolicy {
+    fn from(request: PruneRequest) -> Self {
+        Self {
+            max_age: request.max_age.map(Duration::from_secs),
+            max_bytes: request.max_bytes,
+        }
+    }
+}
+
+/// The response of `DELETE /entries` and `POST /prune`.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Removed {
+    pub removed: u64,
+}
+
+/// A cache shared over HTTP, such as the one `gitsc cache serve` exposes.
+///
+/// The server decides how long values stay valid; the endpoints are:
+///
+/// - `GET /entries/{key}`: the value as text, or 404. Counts as a lookup.
+/// - `PUT /entries/{key}`: stores `{"value": ..., "provider": ..., ...}`.
+/// - `DELETE /entries/{key}`: removes the entry, or 404.
+/// - `GET /entries/{key}/info`: the entry and its metadata as JSON, or 404.
+/// - `GET /entries`, `DELETE /entries`, `GET /stats` and `POST /prune`.
+pub struct HttpCache {
+    client: Client,
+    url: Url,
+    token: Option<String>,
+}
+
+impl HttpCache {
+    pub fn new(url: &str) -> Result<Self> {
+        let url = Url::parse(url)
+            .map_err(|e| Error::Config(format!("Invalid cache url '{}': {}", url, e)))?;
+        if url.cannot_be_a_base() {
+            return Err(Error::Config(format!("Invalid cache url '{}'", url)));
+        }
+        let client = Client::builder()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            client,
+            url,
+            token: None,
+        })
+    }
+
+    /// Sends `token` as a bearer token with every request.
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    /// Builds the URL of an endpoint below the base URL, escaping the key.
+    fn endpoint(&self, segments: &[&str]) -> Url {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .expect("checked in new")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let request = match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        Ok(request.send().await?)
+    }
+}
+
+/// Fails unless the request succeeded.
+fn check(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        Ok(response)
+    } else {
+        Err(Error::Cache(format!("{} returned {}", response.url(), status)))
+    }
+}
+
+/// Like `check`, but treats 404 as a missing entry.
+fn found(response: Response) -> Result<Option<Response>> {
+    if response.status() == StatusCode::NOT_FOUND {
+        Ok(None)
+    } else {
+        check(response).map(Some)
+    }
+}
+
+#[async_trait]
+impl super::CacheRepository for HttpCache {
+    /// Treats a server that does not answer in time as a miss.
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let result = async {
+            let response = self
+                .send(self.client.get(self.endpoint(&["entries", key])))
+                .await?;
+            match found(response)? {
+                Some(response) => Ok(Some(response.text().await?)),
+                None => Ok(None),
+            }
+        }
+        .await;
+        match result {
+            Err(Error::Reqwest(e)) if e.is_timeout() => {
+                warn!("The cache at {} timed out: {}", self.url, e);
+                Ok(None)
+            },
+            result => result,
+        }
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.set_with_metadata(key, value, &EntryMetadata::default())
+            .await
+    }
+
+    async fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        metadata: &EntryMetadata,
+    ) -> Result<()> {
+        let body = StoreRequest {
+            value: value.to_string(),
+            metadata: metadata.clone(),
+        };
+        let response = self
+            .send(
+                self.client
+                    .put(self.endpoint(&["entries", key]))
+                    .json(&body),
+            )
+            .await?;
+        check(response)?;
+        Ok(())
+    }
+
+    async fn entry(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let response = self
+            .send(self.client.get(self.endpoint(&["entries", key, "info"])))
+            .await?;
+        match found(response)? {
+            Some(response) => Ok(Some(response.json().await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn list(&self) -> Result<Vec<CacheEntry>> {
+        let response = self
+            .send(self.client.get(self.endpoint(&["entries"])))
+            .await?;
+        Ok(check(response)?.json().await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        let response = self
+            .send(self.client.delete(self.endpoint(&["entries", key])))
+            .await?;
+        Ok(found(response)?.is_some())
+    }
+
+    async fn clear(&self) -> Result<u64> {
+        let response = self
+            .send(self.client.delete(self.endpoint(&["entries"])))
+            .await?;
+        let removed: Removed = check(response)?.json().await?;
+        Ok(removed.removed)
+    }
+
+    async fn prune(&self, policy: &PrunePolicy) -> Result<u64> {
+        let response = self
+            .send(
+                self.client
+                    .post(self.endpoint(&["prune"]))
+                    .json(&PruneRequest::from(policy)),
+            )
+            .await?;
+        let removed: Removed = check(response)?.json().await?;
+        Ok(removed.removed)
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let response = self
+            .send(self.client.get(self.endpoint(&["stats"])))
+            .await?;
+        Ok(check(response)?.json().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::CacheRepository;
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn treats_a_server_that_does_not_answer_as_a_miss() {
+        // Connections are queued by the kernel but never answered.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let cache = HttpCache::new(&url).unwrap();
+
+        let started = std::time::Instant::now();
+        assert_eq!(cache.get("key").await.unwrap(), None);
+        assert!(started.elapsed() < REQUEST_TIMEOUT + Duration::from_secs(2));
+        // Only lookups fall back to a miss.
+        assert!(cache.set("key", "value").await.is_err());
+    }
+}
@@ -0,0 +1,99 @@
+use super::diff_hasher::generate_diff_hash;
+use crate::ai::prompt::Prompt;
+use serde::Serialize;
+
+/// The version of the cache key layout. Bump it whenever the fields below or
+/// the format of cached values change: keys of other versions are then never
+/// looked up again, and `prune` removes them even when values never expire.
+pub const CACHE_KEY_VERSION: u32 = 1;
+
+/// Whether a stored key was derived with the current `CACHE_KEY_VERSION`.
+/// Keys read `v<version>-<hash>`, optionally behind a namespace such as
+/// `examples-`.
+pub fn is_current(key: &str) -> bool {
+    let version = format!("v{}", CACHE_KEY_VERSION);
+    match key.splitn(3, '-').collect::<Vec<_>>().as_slice() {
+        [tag, _] | [_, tag, _] => *tag == version,
+        _ => false,
+    }
+}
+
+/// Everything that influences a generated commit message.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CacheKey {
+    pub version: u32,
+    /// The hash of the normalized diff.
+    pub diff: String,
+    pub provider: String,
+    pub model: String,
+    /// The hash of the prompt without the diff: instructions, scope hints,
+    /// examples and commit list.
+    pub prompt: String,
+    pub language: Option<String>,
+    pub with_body: bool,
+}
+
+impl CacheKey {
+    /// The key of the message `model` of `provider` writes for `prompt`.
+    pub fn new(provider: &str, model: &str, prompt: &Prompt) -> Self {
+        let template = Prompt {
+            diff: String::new(),
+            ..prompt.clone()
+        };
+        Self {
+            version: CACHE_KEY_VERSION,
+            diff: generate_diff_hash(&prompt.diff),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            prompt: generate_diff_hash(&template.render()),
+            language: prompt.language.clone(),
+            with_body: prompt.with_body,
+        }
+    }
+
+    /// Returns the key under which the message is stored, e.g. `v1-3f2a...`.
+    pub fn digest(&self) -> String {
+        // Serializing a struct of strings and booleans cannot fail.
+        let fields = serde_json::to_string(self).unwrap_or_default();
+        format!("v{}-{}", self.version, generate_diff_hash(&fields))
+    }
+}
+
+/// The key of a free-form response, such as a pull request, that `model` of
+/// `provider` writes for `prompt`. It is namespaced by `kind`, e.g.
+/// `pr-v1-3f2a...`.
+pub fn text_key(kind: &str, provider: &str, model: &str, prompt: &str) -> String {
+    let fields = serde_json::json!([provider, model, prompt]).to_string();
+    format!("{}-v{}-{}", kind, CACHE_KEY_VERSION, generate_diff_hash(&fields))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_current_keys() {
+        assert!(is_current(&format!("v{}-3f2a", CACHE_KEY_VERSION)));
+        assert!(!is_current(&format!("v{}-3f2a", CACHE_KEY_VERSION + 1)));
+        assert!(is_current(&format!("examples-v{}-3f2a", CACHE_KEY_VERSION)));
+        assert!(!is_current("examples-v0-3f2a"));
+        assert!(!is_current("3f2a9c"));
+        assert!(!is_current("v-3f2a"));
+    }
+
+    #[test]
+    fn keys_text_by_kind_provider_model_and_prompt() {
+        let key = text_key("pr", "ollama", "llama3", "prompt");
+        assert!(key.starts_with(&format!("pr-v{}-", CACHE_KEY_VERSION)));
+        assert!(is_current(&key));
+        assert_eq!(key, text_key("pr", "ollama", "llama3", "prompt"));
+        for other in [
+            text_key("changelog", "ollama", "llama3", "prompt"),
+            text_key("pr", "gemini", "llama3", "prompt"),
+            text_key("pr", "ollama", "llama3.1", "prompt"),
+            text_key("pr", "ollama", "llama3", "prompt 2"),
+        ] {
+            assert_ne!(key, other);
+        }
+    }
+}
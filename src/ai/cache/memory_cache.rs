@@ -0,0 +1,190 @@
+use super::{
+    CacheEntry, CacheStats, EntryMetadata, PrunePolicy, is_current, keys_over_budget, now,
+};
+use crate::error::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// A cache that lives as long as the process. Useful when gitsc is embedded
+/// in a long-running program, and in tests.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    /// `None` keeps values forever.
+    ttl: Option<Duration>,
+}
+
+impl MemoryCache {
+    pub fn new(ttl: Option<Duration>) -> Self {
+        Self {
+            ttl,
+            ..Self::default()
+        }
+    }
+
+    fn is_expired(&self, entry: &CacheEntry, current_time: u64) -> bool {
+        self.ttl
+            .is_some_and(|ttl| entry.timestamp + ttl.as_secs() <= current_time)
+    }
+
+    fn snapshot(&self) -> Vec<CacheEntry> {
+        let current_time = now();
+        let mut entries: Vec<CacheEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .map(|entry| CacheEntry {
+                expired: self.is_expired(entry, current_time),
+                ..entry.clone()
+            })
+            .collect();
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.key.cmp(&b.key)));
+        entries
+    }
+}
+
+#[async_trait]
+impl super::CacheRepository for MemoryCache {
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let current_time = now();
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get_mut(key) {
+            Some(entry) if !self.is_expired(entry, current_time) => {
+                entry.hit_count += 1;
+                Some(entry.value.clone())
+            },
+            Some(_) => {
+                entries.remove(key);
+                None
+            },
+            None => None,
+        };
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.set_with_metadata(key, value, &EntryMetadata::default())
+            .await
+    }
+
+    async fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        metadata: &EntryMetadata,
+    ) -> Result<()> {
+        let timestamp = now();
+        let mut entries = self.entries.lock().unwrap();
+        let previous = entries.remove(key);
+        entries.insert(
+            key.to_string(),
+            CacheEntry {
+                key: key.to_string(),
+                value: value.to_string(),
+                timestamp,
+                expired: false,
+                metadata: metadata.clone(),
+                created_at: previous
+                    .as_ref()
+                    .and_then(|p| p.created_at)
+                    .or(Some(timestamp)),
+                hit_count: previous.map_or(0, |p| p.hit_count),
+            },
+        );
+        Ok(())
+    }
+
+    async fn entry(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries.get(key).map(|entry| CacheEntry {
+            expired: self.is_expired(entry, now()),
+            ..entry.clone()
+        }))
+    }
+
+    async fn list(&self) -> Result<Vec<CacheEntry>> {
+        Ok(self.snapshot())
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        Ok(self.entries.lock().unwrap().remove(key).is_some())
+    }
+
+    async fn clear(&self) -> Result<u64> {
+        let mut entries = self.entries.lock().unwrap();
+        let removed = entries.len() as u64;
+        entries.clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        Ok(removed)
+    }
+
+    async fn prune(&self, policy: &PrunePolicy) -> Result<u64> {
+        let current_time = now();
+        let oldest = policy
+            .max_age
+            .map(|age| current_time.saturating_sub(age.as_secs()));
+        let mut doomed: Vec<String> = self
+            .snapshot()
+            .into_iter()
+            .filter(|e| {
+                e.expired
+                    || !is_current(&e.key)
+                    || oldest.is_some_and(|oldest| e.timestamp < oldest)
+            })
+            .map(|e| e.key)
+            .collect();
+
+        let mut entries = self.entries.lock().unwrap();
+        for key in &doomed {
+            entries.remove(key);
+        }
+        drop(entries);
+
+        if let Some(max_bytes) = policy.max_bytes {
+            let over = keys_over_budget(&self.snapshot(), max_bytes);
+            let mut entries = self.entries.lock().unwrap();
+            for key in &over {
+                entries.remove(key);
+            }
+            doomed.extend(over);
+        }
+        Ok(doomed.len() as u64)
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats::from_entries(&self.snapshot());
+        stats.hits = self.hits.load(Ordering::Relaxed);
+        stats.misses = self.misses.load(Ordering::Relaxed);
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::{CACHE_KEY_VERSION, CacheRepository};
+
+    #[tokio::test]
+    async fn prunes_keys_of_other_versions() {
+        let cache = MemoryCache::new(None);
+        let current = format!("v{}-abc", CACHE_KEY_VERSION);
+        cache.set("v0-abc", "feat: x").await.unwrap();
+        cache.set(&current, "feat: x").await.unwrap();
+
+        assert_eq!(cache.prune(&PrunePolicy::default()).await.unwrap(), 1);
+        assert_eq!(cache.get(&current).await.unwrap().as_deref(), Some("feat: x"));
+    }
+}
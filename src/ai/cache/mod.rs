@@ -1,12 +1,324 @@
-use crate::error::Result;
+use crate::config::{CacheBackendKind, Config};
+use crate::error::{Error, Result};
 use async_trait::async_trait;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub mod diff_hasher;
-
+pub mod http_cache;
+pub mod key;
+pub mod memory_cache;
+#[cfg(feature = "redis")]
+pub mod redis_cache;
+pub mod server;
 pub mod sqlite_cache;
 
+pub use http_cache::HttpCache;
+pub use key::{CACHE_KEY_VERSION, CacheKey, is_current, text_key};
+pub use memory_cache::MemoryCache;
+#[cfg(feature = "redis")]
+pub use redis_cache::RedisCache;
+pub use sqlite_cache::SqliteCache;
+
+/// The default time to live of cached values.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(3600); // 1 hour
+
+/// A cached value together with its bookkeeping.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    pub key: String,
+    pub value: String,
+    /// When the value was last stored, in seconds since the Unix epoch. The
+    /// time to live counts from here.
+    pub timestamp: u64,
+    pub expired: bool,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+    /// When the key was first stored, in seconds since the Unix epoch.
+    pub created_at: Option<u64>,
+    /// How many lookups this entry answered.
+    pub hit_count: u64,
+}
+
+impl CacheEntry {
+    /// The size of the key and value, in bytes.
+    pub fn size(&self) -> u64 {
+        (self.key.len() + self.value.len()) as u64
+    }
+}
+
+/// Describes how a cached value was produced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntryMetadata {
+    pub provider: Option<String>,
+    pub model: Option<String>,
+    /// The scopes inferred from the changed paths that the provider was
+    /// offered, if any.
+    pub candidates: Vec<String>,
+}
+
+/// Usage counters and totals of a cache.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub entries: u64,
+    pub expired: u64,
+    /// The total size of the keys and values, in bytes.
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    /// The timestamps of the oldest and newest entries.
+    pub oldest: Option<u64>,
+    pub newest: Option<u64>,
+}
+
+impl CacheStats {
+    /// Computes the totals of a list of entries. The counters are left at zero.
+    pub fn from_entries(entries: &[CacheEntry]) -> Self {
+        Self {
+            entries: entries.len() as u64,
+            expired: entries.iter().filter(|e| e.expired).count() as u64,
+            bytes: entries.iter().map(CacheEntry::size).sum(),
+            oldest: entries.iter().map(|e| e.timestamp).min(),
+            newest: entries.iter().map(|e| e.timestamp).max(),
+            ..Self::default()
+        }
+    }
+
+    /// The share of lookups that were answered from the cache, if any lookups
+    /// were made.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+        (lookups > 0).then(|| self.hits as f64 / lookups as f64)
+    }
+}
+
+/// Which entries `prune` removes, besides the expired ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrunePolicy {
+    /// Remove entries stored longer ago than this.
+    pub max_age: Option<Duration>,
+    /// Remove the oldest entries until the keys and values take at most this
+    /// many bytes.
+    pub max_bytes: Option<u64>,
+}
+
+impl PrunePolicy {
+    /// Reads the eviction limits from the configuration.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let max_age = config
+            .cache_max_age
+            .as_deref()
+            .map(humantime::parse_duration)
+            .transpose()
+            .map_err(|e| Error::Config(format!("Invalid cache_max_age: {}", e)))?;
+        let max_bytes = config
+            .cache_max_size
+            .as_deref()
+            .map(parse_size)
+            .transpose()
+            .map_err(|e| Error::Config(format!("Invalid cache_max_size: {}", e)))?;
+        Ok(Self { max_age, max_bytes })
+    }
+}
+
+/// Reads the time to live of cached values from the configuration. `never`
+/// keeps them until they are pruned.
+pub fn configured_ttl(config: &Config) -> Result<Option<Duration>> {
+    match config.cache_ttl.as_deref().map(str::trim) {
+        None => Ok(Some(DEFAULT_TTL)),
+        Some("never") => Ok(None),
+        Some(ttl) => humantime::parse_duration(ttl)
+            .map(Some)
+            .map_err(|e| Error::Config(format!("Invalid cache_ttl '{}': {}", ttl, e))),
+    }
+}
+
+/// Returns the backend selected in the configuration. Without an explicit
+/// `cache.backend`, Redis is used when `redis_url` is set and gitsc was built
+/// with the `redis` feature, and SQLite otherwise.
+pub fn configured_backend(config: &Config) -> CacheBackendKind {
+    match config.cache.as_ref().and_then(|c| c.backend) {
+        Some(backend) => backend,
+        None if cfg!(feature = "redis") && config.redis_url.is_some() => CacheBackendKind::Redis,
+        None => CacheBackendKind::Sqlite,
+    }
+}
+
+/// Opens the cache backend selected in the configuration, whether or not
+/// caching is enabled.
+pub async fn create_cache(config: &Config) -> Result<Box<dyn CacheRepository + Send + Sync>> {
+    let ttl = configured_ttl(config)?;
+    match configured_backend(config) {
+        CacheBackendKind::Sqlite => {
+            let path = config
+                .cache_path
+                .as_ref()
+                .ok_or_else(|| Error::Config("The sqlite cache needs a cache_path".to_string()))?;
+            let path = expand_home(path)?;
+            if let Some(parent_dir) = path.parent() {
+                fs::create_dir_all(parent_dir)?;
+            }
+            let cache = SqliteCache::new(&path).await?.with_ttl(ttl);
+            debug!("SQLite cache initialized successfully at {:?}", path);
+            Ok(Box::new(cache))
+        },
+        CacheBackendKind::Memory => Ok(Box::new(MemoryCache::new(ttl))),
+        CacheBackendKind::Http => {
+            let cache_config = config.cache.as_ref();
+            let url = cache_config
+                .and_then(|c| c.url.as_deref())
+                .ok_or_else(|| Error::Config("The http cache needs a cache.url".to_string()))?;
+            let cache = HttpCache::new(url)?.with_token(cache_config.and_then(|c| c.token.clone()));
+            debug!("HTTP cache initialized at {}", url);
+            Ok(Box::new(cache))
+        },
+        #[cfg(feature = "redis")]
+        CacheBackendKind::Redis => {
+            let url = config
+                .redis_url
+                .as_deref()
+                .ok_or_else(|| Error::Config("The redis cache needs a redis_url".to_string()))?;
+            let cache = RedisCache::new(url).await?.with_ttl(ttl);
+            debug!("Redis cache initialized successfully at {}", url);
+            Ok(Box::new(cache))
+        },
+        #[cfg(not(feature = "redis"))]
+        CacheBackendKind::Redis => {
+            Err(Error::Config("gitsc was built without the `redis` feature".to_string()))
+        },
+    }
+}
+
+/// Expands a leading `~/` to the home directory.
+pub(crate) fn expand_home(path: &std::path::Path) -> Result<std::path::PathBuf> {
+    match path.to_str().and_then(|s| s.strip_prefix("~/")) {
+        Some(relative) => {
+            let home_dir = dirs::home_dir().ok_or_else(|| {
+                Error::Config("Could not find home directory for cache path expansion".to_string())
+            })?;
+            Ok(home_dir.join(relative))
+        },
+        None => Ok(path.to_path_buf()),
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Returns the keys that do not fit in `max_bytes` when entries are kept
+/// newest first. `entries` must be sorted newest first.
+pub(crate) fn keys_over_budget(entries: &[CacheEntry], max_bytes: u64) -> Vec<String> {
+    let mut total = 0;
+    entries
+        .iter()
+        .filter(|entry| {
+            total += entry.size();
+            total > max_bytes
+        })
+        .map(|entry| entry.key.clone())
+        .collect()
+}
+
+/// Parses a size such as `512`, `64KB` or `10MB`. Units are powers of 1024.
+pub fn parse_size(text: &str) -> std::result::Result<u64, String> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", text))?;
+    let multiplier: u64 = match unit.trim().to_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        other => return Err(format!("unknown size unit '{}'", other)),
+    };
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", text))
+}
+
+/// Formats a size in bytes with a binary unit, e.g. `4.2 KiB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
 #[async_trait]
 pub trait CacheRepository: Send + Sync {
+    /// Returns the value stored under `key`, unless it expired. Lookups count
+    /// towards the hit and miss statistics.
     async fn get(&self, key: &str) -> Result<Option<String>>;
     async fn set(&self, key: &str, value: &str) -> Result<()>;
+    /// Stores a value together with how it was produced. Backends that cannot
+    /// keep the metadata store only the value.
+    async fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        _metadata: &EntryMetadata,
+    ) -> Result<()> {
+        self.set(key, value).await
+    }
+    /// Returns an entry without counting the lookup, even if it expired.
+    async fn entry(&self, key: &str) -> Result<Option<CacheEntry>>;
+    /// Returns every entry, newest first.
+    async fn list(&self) -> Result<Vec<CacheEntry>>;
+    /// Removes an entry. Returns whether it existed.
+    async fn delete(&self, key: &str) -> Result<bool>;
+    /// Removes every entry and resets the statistics. Returns the number of
+    /// entries removed.
+    async fn clear(&self) -> Result<u64>;
+    /// Removes expired entries, entries stored under another key version and
+    /// those selected by the policy. Returns the number of entries removed.
+    async fn prune(&self, policy: &PrunePolicy) -> Result<u64>;
+    async fn stats(&self) -> Result<CacheStats>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size(" 64KB "), Ok(64 * 1024));
+        assert_eq!(parse_size("10 mib"), Ok(10 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5MB"), Err("unknown size unit '.5MB'".to_string()));
+        assert_eq!(parse_size("MB"), Err("invalid size 'MB'".to_string()));
+        assert_eq!(parse_size("10TB"), Err("unknown size unit 'TB'".to_string()));
+        assert_eq!(
+            parse_size("99999999999999GB"),
+            Err("size '99999999999999GB' is too large".to_string())
+        );
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(1023), "1023 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(10 << 20), "10.0 MiB");
+        assert_eq!(format_size(5 << 40), "5120.0 GiB");
+    }
 }
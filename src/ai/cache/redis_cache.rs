@@ -0,0 +1,256 @@
+use super::{
+    CacheEntry, CacheStats, DEFAULT_TTL, EntryMetadata, PrunePolicy, is_current, keys_over_budget,
+    now,
+};
+use crate::error::Result;
+use async_trait::async_trait;
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The prefix of every key gitsc writes, so a shared Redis stays tidy.
+const KEY_PREFIX: &str = "gitsc:";
+
+/// Reads a value and counts the lookup in one atomic step. Checking and
+/// counting separately could recreate an entry that expired in between as a
+/// hash holding only `hit_count`, without a TTL.
+const GET_SCRIPT: &str = r"
+local value = redis.call('HGET', KEYS[1], 'value')
+if value then
+    redis.call('HINCRBY', KEYS[1], 'hit_count', 1)
+    redis.call('INCR', KEYS[2])
+else
+    redis.call('INCR', KEYS[3])
+end
+return value
+";
+
+/// A cache stored in Redis. Each entry is a hash that expires through Redis'
+/// own TTLs, so expired entries never have to be pruned.
+pub struct RedisCache {
+    conn: MultiplexedConnection,
+    /// `None` keeps values forever.
+    ttl: Option<Duration>,
+}
+
+impl RedisCache {
+    pub async fn new(url: &str) -> Result<Self> {
+        let client = redis::Client::open(url)?;
+        let conn = client.get_multiplexed_tokio_connection().await?;
+        Ok(Self {
+            conn,
+            ttl: Some(DEFAULT_TTL),
+        })
+    }
+
+    /// Sets how long values stay valid. `None` keeps them until they are
+    /// pruned or purged.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    fn entry_key(key: &str) -> String {
+        format!("{}cache:{}", KEY_PREFIX, key)
+    }
+
+    fn stats_key(counter: &str) -> String {
+        format!("{}stats:{}", KEY_PREFIX, counter)
+    }
+
+    async fn entry_keys(&self) -> Result<Vec<String>> {
+        let mut conn = self.conn.clone();
+        let mut iter = conn.scan_match::<_, String>(Self::entry_key("*")).await?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    async fn delete_keys(&self, keys: &[String]) -> Result<u64> {
+        if keys.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = self.conn.clone();
+        let deleted: u64 = conn.del(keys).await?;
+        Ok(deleted)
+    }
+}
+
+/// Builds an entry from the fields of its hash.
+fn to_entry(key: &str, mut fields: HashMap<String, String>) -> Option<CacheEntry> {
+    let mut take = |name: &str| fields.remove(name).filter(|v| !v.is_empty());
+    let value = take("value")?;
+    let timestamp = take("timestamp")?.parse().ok()?;
+    Some(CacheEntry {
+        key: key.to_string(),
+        value,
+        timestamp,
+        // Redis removes expired entries by itself.
+        expired: false,
+        metadata: EntryMetadata {
+            provider: take("provider"),
+            model: take("model"),
+            candidates: take("candidates")
+                .and_then(|c| serde_json::from_str(&c).ok())
+                .unwrap_or_default(),
+        },
+        created_at: take("created_at").and_then(|c| c.parse().ok()),
+        hit_count: take("hit_count").and_then(|c| c.parse().ok()).unwrap_or(0),
+    })
+}
+
+#[async_trait]
+impl super::CacheRepository for RedisCache {
+    async fn get(&self, key: &str) -> Result<Option<String>> {
+        let mut conn = self.conn.clone();
+        let value: Option<String> = redis::cmd("EVAL")
+            .arg(GET_SCRIPT)
+            .arg(3)
+            .arg(Self::entry_key(key))
+            .arg(Self::stats_key("hits"))
+            .arg(Self::stats_key("misses"))
+            .query_async(&mut conn)
+            .await?;
+        Ok(value)
+    }
+
+    async fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.set_with_metadata(key, value, &EntryMetadata::default())
+            .await
+    }
+
+    async fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        metadata: &EntryMetadata,
+    ) -> Result<()> {
+        let mut conn = self.conn.clone();
+        let entry_key = Self::entry_key(key);
+        let timestamp = now();
+        let created_at: Option<u64> = conn.hget(&entry_key, "created_at").await?;
+        let candidates = if metadata.candidates.is_empty() {
+            String::new()
+        } else {
+            serde_json::to_string(&metadata.candidates).map_err(std::io::Error::from)?
+        };
+
+        let fields = [
+            ("value", value.to_string()),
+            ("timestamp", timestamp.to_string()),
+            ("created_at", created_at.unwrap_or(timestamp).to_string()),
+            ("provider", metadata.provider.clone().unwrap_or_default()),
+            ("model", metadata.model.clone().unwrap_or_default()),
+            ("candidates", candidates),
+        ];
+        let mut pipe = redis::pipe();
+        pipe.atomic().hset_multiple(&entry_key, &fields).ignore();
+        match self.ttl {
+            Some(ttl) => pipe.expire(&entry_key, ttl.as_secs() as i64).ignore(),
+            None => pipe.persist(&entry_key).ignore(),
+        };
+        let _: () = pipe.query_async(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn entry(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let mut conn = self.conn.clone();
+        let fields: HashMap<String, String> = conn.hgetall(Self::entry_key(key)).await?;
+        Ok(to_entry(key, fields))
+    }
+
+    async fn list(&self) -> Result<Vec<CacheEntry>> {
+        let mut conn = self.conn.clone();
+        let prefix = Self::entry_key("");
+        let mut entries = Vec::new();
+        for entry_key in self.entry_keys().await? {
+            let fields: HashMap<String, String> = conn.hgetall(&entry_key).await?;
+            let key = entry_key.strip_prefix(&prefix).unwrap_or(&entry_key);
+            entries.extend(to_entry(key, fields));
+        }
+        entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.key.cmp(&b.key)));
+        Ok(entries)
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        Ok(self.delete_keys(&[Self::entry_key(key)]).await? > 0)
+    }
+
+    async fn clear(&self) -> Result<u64> {
+        let deleted = self.delete_keys(&self.entry_keys().await?).await?;
+        self.delete_keys(&[Self::stats_key("hits"), Self::stats_key("misses")])
+            .await?;
+        Ok(deleted)
+    }
+
+    async fn prune(&self, policy: &PrunePolicy) -> Result<u64> {
+        let (mut entries, stale): (Vec<_>, Vec<_>) = self
+            .list()
+            .await?
+            .into_iter()
+            .partition(|e| is_current(&e.key));
+        let mut doomed: Vec<String> = stale.into_iter().map(|e| e.key).collect();
+
+        if let Some(max_age) = policy.max_age {
+            let oldest = now().saturating_sub(max_age.as_secs());
+            let (old, young): (Vec<_>, Vec<_>) =
+                entries.into_iter().partition(|e| e.timestamp < oldest);
+            doomed.extend(old.into_iter().map(|e| e.key));
+            entries = young;
+        }
+        if let Some(max_bytes) = policy.max_bytes {
+            doomed.extend(keys_over_budget(&entries, max_bytes));
+        }
+
+        let keys: Vec<String> = doomed.iter().map(|k| Self::entry_key(k)).collect();
+        self.delete_keys(&keys).await
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let mut stats = CacheStats::from_entries(&self.list().await?);
+        let mut conn = self.conn.clone();
+        let (hits, misses): (Option<u64>, Option<u64>) = conn
+            .mget(&[Self::stats_key("hits"), Self::stats_key("misses")])
+            .await?;
+        stats.hits = hits.unwrap_or(0);
+        stats.misses = misses.unwrap_or(0);
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::CacheRepository;
+    use std::env;
+
+    /// Needs a Redis server: `GITSC_TEST_REDIS_URL` or `redis://127.0.0.1/`.
+    #[tokio::test]
+    #[ignore = "needs a local redis-server"]
+    async fn counts_hits_without_recreating_missing_entries() {
+        let url = env::var("GITSC_TEST_REDIS_URL").unwrap_or("redis://127.0.0.1/".to_string());
+        let cache = RedisCache::new(&url)
+            .await
+            .unwrap()
+            .with_ttl(Some(Duration::from_secs(60)));
+        let key = format!("test-{}", std::process::id());
+        let mut conn = cache.conn.clone();
+
+        assert_eq!(cache.get(&key).await.unwrap(), None);
+        let exists: bool = conn.exists(RedisCache::entry_key(&key)).await.unwrap();
+        assert!(!exists, "a miss must not create the entry");
+
+        cache.set(&key, "feat: x").await.unwrap();
+        assert_eq!(cache.get(&key).await.unwrap().as_deref(), Some("feat: x"));
+        assert_eq!(cache.get(&key).await.unwrap().as_deref(), Some("feat: x"));
+        let entry = cache.entry(&key).await.unwrap().unwrap();
+        assert_eq!(entry.hit_count, 2);
+        let ttl: i64 = conn.ttl(RedisCache::entry_key(&key)).await.unwrap();
+        assert!(ttl > 0, "hits must keep the TTL");
+
+        assert!(cache.delete(&key).await.unwrap());
+    }
+}
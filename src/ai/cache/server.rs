@@ -0,0 +1,169 @@
+use super::http_cache::{PruneRequest, Removed, StoreRequest};
+use super::{CacheRepository, PrunePolicy};
+use crate::error::{Error, Result};
+use axum::extract::{Path, Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use log::{debug, error, info};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the server prunes the cache it serves. Clients never prune a
+/// shared cache when they open it.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3_600);
+
+struct Shared {
+    cache: Box<dyn CacheRepository + Send + Sync>,
+    token: Option<String>,
+}
+
+type AppState = Arc<Shared>;
+
+/// Turns cache errors into `500 Internal Server Error`.
+struct ServerError(Error);
+
+impl From<Error> for ServerError {
+    fn from(err: Error) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ServerError {
+    fn into_response(self) -> Response {
+        error!("{}", self.0);
+        (StatusCode::INTERNAL_SERVER_ERROR, self.0.to_string()).into_response()
+    }
+}
+
+type Reply = std::result::Result<Response, ServerError>;
+
+/// Serves `cache` over HTTP on `addr` until the process is stopped, using the
+/// protocol `HttpCache` speaks, and prunes it with `policy` every hour. When
+/// `token` is set, requests must send it as a bearer token; without one, only
+/// loopback addresses are allowed.
+pub async fn serve(
+    cache: Box<dyn CacheRepository + Send + Sync>,
+    addr: SocketAddr,
+    token: Option<String>,
+    policy: PrunePolicy,
+) -> Result<()> {
+    if token.is_none() && !addr.ip().is_loopback() {
+        return Err(Error::Config(format!(
+            "Refusing to serve the cache on {} without a token. Set --token or cache.token, or listen on a loopback address.",
+            addr
+        )));
+    }
+
+    let state = Arc::new(Shared { cache, token });
+    let app = Router::new()
+        .route("/entries", get(list).delete(clear))
+        .route("/entries/{key}", get(lookup).put(store).delete(remove))
+        .route("/entries/{key}/info", get(entry))
+        .route("/stats", get(stats))
+        .route("/prune", post(prune))
+        .layer(middleware::from_fn_with_state(state.clone(), authorize))
+        .with_state(state.clone());
+
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Serving the cache on http://{}", listener.local_addr()?);
+    tokio::spawn(prune_periodically(state, policy));
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn prune_periodically(state: AppState, policy: PrunePolicy) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match state.cache.prune(&policy).await {
+            Ok(0) => {},
+            Ok(removed) => debug!("Pruned {} cache entries.", removed),
+            Err(e) => error!("Failed to prune the cache: {}", e),
+        }
+    }
+}
+
+async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if let Some(token) = &state.token {
+        let sent = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if sent != Some(token.as_str()) {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+async fn lookup(State(state): State<AppState>, Path(key): Path<String>) -> Reply {
+    Ok(match state.cache.get(&key).await? {
+        Some(value) => value.into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+async fn store(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Json(request): Json<StoreRequest>,
+) -> Reply {
+    state
+        .cache
+        .set_with_metadata(&key, &request.value, &request.metadata)
+        .await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn remove(State(state): State<AppState>, Path(key): Path<String>) -> Reply {
+    Ok(if state.cache.delete(&key).await? {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+    .into_response())
+}
+
+async fn entry(State(state): State<AppState>, Path(key): Path<String>) -> Reply {
+    Ok(match state.cache.entry(&key).await? {
+        Some(entry) => Json(entry).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    })
+}
+
+async fn list(State(state): State<AppState>) -> Reply {
+    Ok(Json(state.cache.list().await?).into_response())
+}
+
+async fn clear(State(state): State<AppState>) -> Reply {
+    let removed = state.cache.clear().await?;
+    Ok(Json(Removed { removed }).into_response())
+}
+
+async fn stats(State(state): State<AppState>) -> Reply {
+    Ok(Json(state.cache.stats().await?).into_response())
+}
+
+async fn prune(State(state): State<AppState>, Json(request): Json<PruneRequest>) -> Reply {
+    let removed = state.cache.prune(&request.into()).await?;
+    Ok(Json(Removed { removed }).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::memory_cache::MemoryCache;
+
+    #[tokio::test]
+    async fn refuses_public_addresses_without_a_token() {
+        let cache = Box::new(MemoryCache::new(None));
+        let addr = "0.0.0.0:0".parse().unwrap();
+        let result = serve(cache, addr, None, PrunePolicy::default()).await;
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
+}
@@ -1,33 +1,131 @@
-use crate::error::Result;
+use super::{CacheEntry, CacheStats, DEFAULT_TTL, EntryMetadata, PrunePolicy, is_current, now};
+use crate::error::{Error, Result};
 use async_trait::async_trait;
+use rusqlite::{OptionalExtension, params};
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 use tokio_rusqlite::Connection;
 
-const CACHE_TTL_SECONDS: u64 = 3600; // 1 hour
+/// Schema migrations, in order. `PRAGMA user_version` holds the number of
+/// migrations applied, so databases of any earlier version are upgraded in
+/// place. Never edit a released migration; append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    // 1: the original table, which older databases already have.
+    "CREATE TABLE IF NOT EXISTS cache (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS cache_stats (
+        name TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );",
+    // 2: how each entry was produced and how often it was used.
+    "ALTER TABLE cache ADD COLUMN provider TEXT;
+    ALTER TABLE cache ADD COLUMN model TEXT;
+    ALTER TABLE cache ADD COLUMN created_at INTEGER;
+    ALTER TABLE cache ADD COLUMN hit_count INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE cache ADD COLUMN candidates TEXT;
+    UPDATE cache SET created_at = timestamp;",
+];
+
+const ENTRY_COLUMNS: &str =
+    "key, value, timestamp, provider, model, candidates, created_at, hit_count";
 
 pub struct SqliteCache {
     conn: Connection,
+    /// `None` keeps values forever.
+    ttl: Option<Duration>,
 }
 
 impl SqliteCache {
     pub async fn new(cache_path: &PathBuf) -> Result<Self> {
         let conn = Connection::open(cache_path).await?;
-        conn.call(|conn| {
-            conn.execute(
-                "CREATE TABLE IF NOT EXISTS cache (\n                    key TEXT PRIMARY KEY,\n                    value TEXT NOT NULL,\n                    timestamp INTEGER NOT NULL\n                )",
-                [],
-            )?;
-            Ok(())
-        }).await?;
-        Ok(Self { conn })
+        let version = conn.call(|conn| Ok(migrate(conn, MIGRATIONS)?)).await?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::Config(format!(
+                "The cache at {} was created by a newer version of gitsc (schema {}, expected {}).",
+                cache_path.display(),
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        Ok(Self {
+            conn,
+            ttl: Some(DEFAULT_TTL),
+        })
+    }
+
+    /// Sets how long values stay valid. `None` keeps them until they are
+    /// pruned or purged.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Entries stored at or before this timestamp have expired.
+    fn expiry_cutoff(&self) -> Option<u64> {
+        self.ttl.map(|ttl| now().saturating_sub(ttl.as_secs()))
     }
 }
 
+/// Applies the pending migrations and returns the resulting schema version.
+pub(crate) fn migrate(
+    conn: &mut rusqlite::Connection,
+    migrations: &[&str],
+) -> rusqlite::Result<usize> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in migrations.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+fn count(conn: &rusqlite::Connection, counter: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO cache_stats (name, value) VALUES (?1, 1)
+         ON CONFLICT(name) DO UPDATE SET value = value + 1",
+        [counter],
+    )?;
+    Ok(())
+}
+
+fn counter(conn: &rusqlite::Connection, counter: &str) -> rusqlite::Result<u64> {
+    Ok(conn
+        .query_row("SELECT value FROM cache_stats WHERE name = ?", [counter], |row| row.get(0))
+        .optional()?
+        .unwrap_or(0))
+}
+
+/// Reads a row selected with `ENTRY_COLUMNS`.
+fn to_entry(row: &rusqlite::Row<'_>, cutoff: Option<u64>) -> rusqlite::Result<CacheEntry> {
+    let timestamp: u64 = row.get(2)?;
+    let candidates: Option<String> = row.get(5)?;
+    Ok(CacheEntry {
+        key: row.get(0)?,
+        value: row.get(1)?,
+        timestamp,
+        expired: cutoff.is_some_and(|cutoff| timestamp <= cutoff),
+        metadata: EntryMetadata {
+            provider: row.get(3)?,
+            model: row.get(4)?,
+            candidates: candidates
+                .and_then(|c| serde_json::from_str(&c).ok())
+                .unwrap_or_default(),
+        },
+        created_at: row.get(6)?,
+        hit_count: row.get(7)?,
+    })
+}
+
 #[async_trait]
 impl super::CacheRepository for SqliteCache {
     async fn get(&self, key: &str) -> Result<Option<String>> {
         let key = key.to_string();
+        let cutoff = self.expiry_cutoff();
         let result = self
             .conn
             .call(move |conn| {
@@ -37,19 +135,22 @@ impl super::CacheRepository for SqliteCache {
                 if let Some(row) = rows.next()? {
                     let value: String = row.get(0)?;
                     let timestamp: u64 = row.get(1)?;
-                    let current_time = SystemTime::now()
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
 
-                    if current_time - timestamp < CACHE_TTL_SECONDS {
+                    if cutoff.is_none_or(|cutoff| timestamp > cutoff) {
+                        conn.execute(
+                            "UPDATE cache SET hit_count = hit_count + 1 WHERE key = ?",
+                            [&key],
+                        )?;
+                        count(conn, "hits")?;
                         Ok(Some(value))
                     } else {
                         // Cache expired, delete it
                         conn.execute("DELETE FROM cache WHERE key = ?", [&key])?;
+                        count(conn, "misses")?;
                         Ok(None)
                     }
                 } else {
+                    count(conn, "misses")?;
                     Ok(None)
                 }
             })
@@ -58,22 +159,303 @@ impl super::CacheRepository for SqliteCache {
     }
 
     async fn set(&self, key: &str, value: &str) -> Result<()> {
+        self.set_with_metadata(key, value, &EntryMetadata::default())
+            .await
+    }
+
+    async fn set_with_metadata(
+        &self,
+        key: &str,
+        value: &str,
+        metadata: &EntryMetadata,
+    ) -> Result<()> {
         let key = key.to_string();
         let value = value.to_string();
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+        let metadata = metadata.clone();
+        let candidates = if metadata.candidates.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&metadata.candidates).map_err(std::io::Error::from)?)
+        };
+        let timestamp = now();
 
         self.conn
             .call(move |conn| {
+                // Keep the creation time and hit count of an existing key.
                 conn.execute(
-                    "INSERT OR REPLACE INTO cache (key, value, timestamp) VALUES (?, ?, ?)",
-                    [&key, &value, &timestamp.to_string()],
+                    "INSERT INTO cache (key, value, timestamp, provider, model, candidates, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?3)
+                     ON CONFLICT(key) DO UPDATE SET
+                         value = excluded.value,
+                         timestamp = excluded.timestamp,
+                         provider = excluded.provider,
+                         model = excluded.model,
+                         candidates = excluded.candidates",
+                    params![key, value, timestamp, metadata.provider, metadata.model, candidates],
                 )?;
                 Ok(())
             })
             .await?;
         Ok(())
     }
+
+    async fn entry(&self, key: &str) -> Result<Option<CacheEntry>> {
+        let key = key.to_string();
+        let cutoff = self.expiry_cutoff();
+        let entry = self
+            .conn
+            .call(move |conn| {
+                Ok(conn
+                    .query_row(
+                        &format!("SELECT {} FROM cache WHERE key = ?", ENTRY_COLUMNS),
+                        [&key],
+                        |row| to_entry(row, cutoff),
+                    )
+                    .optional()?)
+            })
+            .await?;
+        Ok(entry)
+    }
+
+    async fn list(&self) -> Result<Vec<CacheEntry>> {
+        let cutoff = self.expiry_cutoff();
+        let entries = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {} FROM cache ORDER BY timestamp DESC, key",
+                    ENTRY_COLUMNS
+                ))?;
+                let entries = stmt
+                    .query_map([], |row| to_entry(row, cutoff))?
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(entries)
+            })
+            .await?;
+        Ok(entries)
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool> {
+        let key = key.to_string();
+        let deleted = self
+            .conn
+            .call(move |conn| Ok(conn.execute("DELETE FROM cache WHERE key = ?", [&key])?))
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn clear(&self) -> Result<u64> {
+        let deleted = self
+            .conn
+            .call(|conn| {
+                let tx = conn.transaction()?;
+                let deleted = tx.execute("DELETE FROM cache", [])?;
+                tx.execute("DELETE FROM cache_stats", [])?;
+                tx.commit()?;
+                Ok(deleted)
+            })
+            .await?;
+        Ok(deleted as u64)
+    }
+
+    async fn prune(&self, policy: &PrunePolicy) -> Result<u64> {
+        let policy = *policy;
+        let cutoff = self.expiry_cutoff();
+        let current_time = now();
+        let deleted = self
+            .conn
+            .call(move |conn| {
+                let tx = conn.transaction()?;
+                // Comparing with NULL matches nothing when values never expire.
+                let mut deleted =
+                    tx.execute("DELETE FROM cache WHERE timestamp <= ?", params![cutoff])?;
+                let stale: Vec<String> = {
+                    let mut stmt = tx.prepare("SELECT key FROM cache")?;
+                    stmt.query_map([], |row| row.get::<_, String>(0))?
+                        .filter(|key| !key.as_ref().is_ok_and(|key| is_current(key)))
+                        .collect::<rusqlite::Result<_>>()?
+                };
+                for key in stale {
+                    deleted += tx.execute("DELETE FROM cache WHERE key = ?", [&key])?;
+                }
+
+                if let Some(max_age) = policy.max_age {
+                    let oldest = current_time.saturating_sub(max_age.as_secs());
+                    deleted += tx.execute("DELETE FROM cache WHERE timestamp < ?", [oldest])?;
+                }
+
+                if let Some(max_bytes) = policy.max_bytes {
+                    // Keep the newest entries that fit in the budget.
+                    let sizes = {
+                        let mut stmt = tx.prepare(
+                            "SELECT key, length(CAST(key AS BLOB)) + length(CAST(value AS BLOB))
+                             FROM cache ORDER BY timestamp DESC, key",
+                        )?;
+                        stmt.query_map([], |row| {
+                            Ok((row.get::<_, String>(0)?, row.get::<_, u64>(1)?))
+                        })?
+                        .collect::<rusqlite::Result<Vec<_>>>()?
+                    };
+                    let mut total = 0;
+                    for (key, size) in sizes {
+                        total += size;
+                        if total > max_bytes {
+                            deleted += tx.execute("DELETE FROM cache WHERE key = ?", [&key])?;
+                        }
+                    }
+                }
+
+                tx.commit()?;
+                Ok(deleted)
+            })
+            .await?;
+        Ok(deleted as u64)
+    }
+
+    async fn stats(&self) -> Result<CacheStats> {
+        let cutoff = self.expiry_cutoff();
+        let stats = self
+            .conn
+            .call(move |conn| {
+                let mut stats = conn.query_row(
+                    "SELECT COUNT(*),
+                            COALESCE(SUM(timestamp <= ?1), 0),
+                            COALESCE(SUM(length(CAST(key AS BLOB)) + length(CAST(value AS BLOB))), 0),
+                            MIN(timestamp),
+                            MAX(timestamp)
+                     FROM cache",
+                    params![cutoff],
+                    |row| {
+                        Ok(CacheStats {
+                            entries: row.get(0)?,
+                            expired: row.get(1)?,
+                            bytes: row.get(2)?,
+                            oldest: row.get(3)?,
+                            newest: row.get(4)?,
+                            ..CacheStats::default()
+                        })
+                    },
+                )?;
+                stats.hits = counter(conn, "hits")?;
+                stats.misses = counter(conn, "misses")?;
+                Ok(stats)
+            })
+            .await?;
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::{CACHE_KEY_VERSION, CacheRepository};
+    use std::env;
+
+    #[tokio::test]
+    async fn prunes_keys_of_other_versions_when_values_never_expire() {
+        let path = env::temp_dir().join(format!("gitsc-cache-{}.db", std::process::id()));
+        let cache = SqliteCache::new(&path).await.unwrap().with_ttl(None);
+        let current = format!("v{}-abc", CACHE_KEY_VERSION);
+        for key in ["v0-abc", "3f2a9c", current.as_str()] {
+            cache.set(key, "feat: x").await.unwrap();
+        }
+
+        let removed = cache.prune(&PrunePolicy::default()).await.unwrap();
+        let keys: Vec<String> = cache
+            .list()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.key)
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(removed, 2);
+        assert_eq!(keys, [current]);
+    }
+
+    #[tokio::test]
+    async fn upgrades_databases_of_the_original_layout() {
+        let path = env::temp_dir().join(format!("gitsc-cache-v0-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let stored = now();
+        {
+            // The layout written before migrations existed, at user_version 0.
+            let conn = rusqlite::Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE cache (
+                    key TEXT PRIMARY KEY,
+                    value TEXT NOT NULL,
+                    timestamp INTEGER NOT NULL
+                );
+                CREATE TABLE cache_stats (
+                    name TEXT PRIMARY KEY,
+                    value INTEGER NOT NULL
+                );
+                INSERT INTO cache_stats (name, value) VALUES ('hits', 7);",
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO cache (key, value, timestamp) VALUES ('v1-abc', 'feat: x', ?)",
+                [stored],
+            )
+            .unwrap();
+        }
+
+        let cache = SqliteCache::new(&path).await.unwrap();
+        let entry = cache.entry("v1-abc").await.unwrap();
+        let stats = cache.stats().await.unwrap();
+        drop(cache);
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let version: usize = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        let columns: Vec<String> = conn
+            .prepare("SELECT name FROM pragma_table_info('cache')")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(
+            columns,
+            [
+                "key",
+                "value",
+                "timestamp",
+                "provider",
+                "model",
+                "created_at",
+                "hit_count",
+                "candidates"
+            ]
+        );
+        let entry = entry.unwrap();
+        assert_eq!(entry.value, "feat: x");
+        assert_eq!(entry.timestamp, stored);
+        assert_eq!(entry.created_at, Some(stored));
+        assert_eq!(entry.hit_count, 0);
+        assert_eq!(entry.metadata.provider, None);
+        assert_eq!(stats.entries, 1);
+        assert_eq!(stats.hits, 7);
+    }
+
+    #[tokio::test]
+    async fn refuses_databases_of_newer_versions() {
+        let path = env::temp_dir().join(format!("gitsc-cache-v99-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+
+        let result = SqliteCache::new(&path).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(result, Err(Error::Config(_))));
+    }
 }
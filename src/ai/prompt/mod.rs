@@ -1 +1,201 @@
+use crate::git::{CommitInfo, Diff};
 
+pub mod pull_request;
+pub mod release_notes;
+
+pub use pull_request::PullRequestPrompt;
+pub use release_notes::ReleaseNotesPrompt;
+
+/// Builds the prompt sent to AI providers for commit message generation.
+#[derive(Debug, Clone, Default)]
+pub struct Prompt {
+    /// The (possibly truncated) diff to describe.
+    pub diff: String,
+    /// Commits whose combined changes make up the diff, oldest first.
+    pub commits: Vec<CommitInfo>,
+    /// Ask for a body summarizing the changes, not just a subject line.
+    pub with_body: bool,
+    /// The scope inferred from the changed paths, if they agree on one.
+    pub scope: Option<String>,
+    /// The scopes the changed paths belong to, when they do not agree.
+    pub known_scopes: Vec<String>,
+    /// Messages from the repository history whose style should be matched.
+    pub examples: Vec<String>,
+    /// The language of the subject and body, e.g. `es`.
+    pub language: Option<String>,
+    /// The whole diff, for providers that read its structure rather than the
+    /// rendered text. Not part of the rendered prompt.
+    pub changes: Option<Diff>,
+}
+
+impl Prompt {
+    pub fn new(diff: impl Into<String>) -> Self {
+        Self {
+            diff: diff.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_commits(mut self, commits: Vec<CommitInfo>) -> Self {
+        self.commits = commits;
+        self
+    }
+
+    pub fn with_body(mut self, with_body: bool) -> Self {
+        self.with_body = with_body;
+        self
+    }
+
+    /// Adds the scope inferred from the repository layout as a hint.
+    pub fn with_scope(mut self, scope: Option<String>, known_scopes: Vec<String>) -> Self {
+        self.scope = scope;
+        self.known_scopes = known_scopes;
+        self
+    }
+
+    pub fn with_examples(mut self, examples: Vec<String>) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.language = language;
+        self
+    }
+
+    pub fn with_changes(mut self, changes: Diff) -> Self {
+        self.changes = Some(changes);
+        self
+    }
+
+    /// Renders the full prompt text.
+    pub fn render(&self) -> String {
+        let mut prompt = if self.with_body {
+            String::from(
+                "Analyze the following git diff and generate a commit message in the Conventional Commits format. Start with a single header line in the format 'type(scope): subject', then a blank line, then a body made of a short bullet list summarizing the individual changes. Do not add footers.",
+            )
+        } else {
+            String::from(
+                "Analyze the following git diff and generate a **concise single-line commit message** in the format 'type(scope): subject'.",
+            )
+        };
+
+        if let Some(language) = &self.language {
+            prompt.push_str(&format!(
+                " Write the subject{} in {}, but keep the type (feat, fix, docs, ...) in English and the scope unchanged.",
+                if self.with_body { " and body" } else { "" },
+                language_name(language)
+            ));
+        }
+
+        match &self.scope {
+            Some(scope) => prompt.push_str(&format!(
+                "\n\nThe changed files belong to the '{}' module. Use '{}' as the scope.",
+                scope, scope
+            )),
+            None if !self.known_scopes.is_empty() => prompt.push_str(&format!(
+                "\n\nThe changed files belong to these modules: {}. If you use a scope, pick one of them.",
+                self.known_scopes.join(", ")
+            )),
+            None => {},
+        }
+
+        if !self.examples.is_empty() {
+            prompt.push_str(
+                "\n\nHere are recent commit messages from this repository. Match their tone, casing, scope names and language:",
+            );
+            for example in &self.examples {
+                if self.with_body {
+                    prompt.push_str(&format!("\n---\n{}", example));
+                } else {
+                    prompt.push_str(&format!("\n- {}", example.lines().next().unwrap_or("")));
+                }
+            }
+            if self.with_body {
+                prompt.push_str("\n---");
+            }
+        }
+
+        if !self.commits.is_empty() {
+            prompt.push_str(
+                "\n\nThe diff combines the following commits, oldest first. Write one message that describes what they do together:",
+            );
+            for commit in &self.commits {
+                prompt.push_str(&format!("\n- {}", commit.summary()));
+            }
+        }
+
+        prompt.push_str("\n\n");
+        prompt.push_str(&self.diff);
+        prompt
+    }
+}
+
+/// Returns the English name of a common ISO 639-1 language code. Anything else,
+/// such as `Brazilian Portuguese`, is returned as it is.
+pub fn language_name(language: &str) -> &str {
+    const NAMES: &[(&str, &str)] = &[
+        ("ar", "Arabic"),
+        ("de", "German"),
+        ("en", "English"),
+        ("es", "Spanish"),
+        ("fr", "French"),
+        ("hi", "Hindi"),
+        ("it", "Italian"),
+        ("ja", "Japanese"),
+        ("ko", "Korean"),
+        ("nl", "Dutch"),
+        ("pl", "Polish"),
+        ("pt", "Portuguese"),
+        ("ru", "Russian"),
+        ("sv", "Swedish"),
+        ("tr", "Turkish"),
+        ("uk", "Ukrainian"),
+        ("zh", "Chinese"),
+    ];
+    NAMES
+        .iter()
+        .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        .map_or(language, |(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn asks_for_the_configured_language() {
+        let prompt = Prompt::new("diff").with_language(Some("es".to_string()));
+        assert!(
+            prompt
+                .render()
+                .contains(" Write the subject in Spanish, but keep the type")
+        );
+
+        let prompt = prompt.with_body(true);
+        assert!(
+            prompt
+                .render()
+                .contains(" Write the subject and body in Spanish,")
+        );
+
+        let prompt = Prompt::new("diff").with_language(Some("Brazilian Portuguese".to_string()));
+        assert!(prompt.render().contains(" in Brazilian Portuguese,"));
+    }
+
+    #[test]
+    fn omits_the_language_when_unset() {
+        for with_body in [false, true] {
+            let rendered = Prompt::new("diff").with_body(with_body).render();
+            assert!(!rendered.contains("Write the subject"), "{}", rendered);
+            assert!(!rendered.contains("in English"), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn names_common_languages() {
+        assert_eq!(language_name("ja"), "Japanese");
+        assert_eq!(language_name("PT"), "Portuguese");
+        assert_eq!(language_name("Klingon"), "Klingon");
+    }
+}
@@ -0,0 +1,80 @@
+use super::language_name;
+use crate::git::CommitInfo;
+
+/// The sections a pull request description covers when no template is given.
+const DEFAULT_SECTIONS: &str =
+    "## Summary\n\n## Motivation\n\n## Changes\n\n## Testing\n\n## Risk\n";
+
+/// Builds the prompt for a pull request title and description.
+#[derive(Debug, Clone, Default)]
+pub struct PullRequestPrompt {
+    /// The (possibly truncated) diff against the base branch.
+    pub diff: String,
+    /// The commits on the branch, oldest first.
+    pub commits: Vec<CommitInfo>,
+    /// A Markdown template the description must follow.
+    pub template: Option<String>,
+    /// The language of the title and description, e.g. `es`.
+    pub language: Option<String>,
+}
+
+impl PullRequestPrompt {
+    pub fn new(diff: impl Into<String>, commits: Vec<CommitInfo>) -> Self {
+        Self {
+            diff: diff.into(),
+            commits,
+            template: None,
+            language: None,
+        }
+    }
+
+    pub fn with_template(mut self, template: Option<String>) -> Self {
+        self.template = template;
+        self
+    }
+
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Renders the full prompt text.
+    pub fn render(&self) -> String {
+        let mut prompt = String::from(
+            "Write a pull request for the following branch. On the first line, write only a concise title. Then leave a blank line and write the description in Markdown.",
+        );
+
+        if let Some(language) = &self.language {
+            prompt.push_str(&format!(
+                " Write the title and description in {}.",
+                language_name(language)
+            ));
+        }
+
+        match &self.template {
+            Some(template) => {
+                prompt.push_str(
+                    " Fill in the following template, keeping its headings and checklists, and remove HTML comments:\n\n",
+                );
+                prompt.push_str(template.trim());
+            },
+            None => {
+                prompt.push_str(
+                    " Cover what the change does, why it is needed, a bullet list of the individual changes, how it was or should be tested, and its risk. Use these sections:\n\n",
+                );
+                prompt.push_str(DEFAULT_SECTIONS.trim());
+            },
+        }
+
+        if !self.commits.is_empty() {
+            prompt.push_str("\n\nCommits on the branch, oldest first:");
+            for commit in &self.commits {
+                prompt.push_str(&format!("\n- {}", commit.summary()));
+            }
+        }
+
+        prompt.push_str("\n\nDiff against the base branch:\n\n");
+        prompt.push_str(&self.diff);
+        prompt
+    }
+}
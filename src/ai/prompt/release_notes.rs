@@ -0,0 +1,57 @@
+use super::language_name;
+
+/// Builds the prompt that rewrites terse commit subjects into user-facing
+/// release notes.
+#[derive(Debug, Clone, Default)]
+pub struct ReleaseNotesPrompt {
+    pub subjects: Vec<String>,
+    /// The language of the release notes, e.g. `es`.
+    pub language: Option<String>,
+}
+
+impl ReleaseNotesPrompt {
+    pub fn new(subjects: Vec<String>) -> Self {
+        Self {
+            subjects,
+            language: None,
+        }
+    }
+
+    pub fn with_language(mut self, language: Option<String>) -> Self {
+        self.language = language;
+        self
+    }
+
+    /// Renders the full prompt text.
+    pub fn render(&self) -> String {
+        let mut prompt = format!(
+            "Rewrite each of the following {} commit subjects as a short, user-facing release note. Keep the meaning, start with a capital letter and do not end with a period. Answer with exactly one line per subject, in the same order, without numbering or bullets.",
+            self.subjects.len()
+        );
+        if let Some(language) = &self.language {
+            prompt.push_str(&format!(" Write the release notes in {}.", language_name(language)));
+        }
+        prompt.push('\n');
+        for subject in &self.subjects {
+            prompt.push('\n');
+            prompt.push_str(subject);
+        }
+        prompt
+    }
+
+    /// Parses the model's answer. Returns `None` if it does not contain one line
+    /// per subject.
+    pub fn parse_response(&self, text: &str) -> Option<Vec<String>> {
+        let lines: Vec<String> = text
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .trim_start_matches(['-', '*'])
+                    .trim()
+                    .to_string()
+            })
+            .filter(|line| !line.is_empty())
+            .collect();
+        (lines.len() == self.subjects.len()).then_some(lines)
+    }
+}
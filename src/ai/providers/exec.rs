@@ -0,0 +1,259 @@
+use async_trait::async_trait;
+use log::debug;
+use serde::Serialize;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::ai::prompt::Prompt;
+use crate::ai::repository::{AIProvider, CommitMessage, Completion, TokenSink, Usage};
+use crate::config::ExecConfig;
+use crate::error::{Error, Result};
+
+/// The version of the request format, bumped on incompatible changes.
+const REQUEST_VERSION: u32 = 1;
+
+/// How long the command may run when no timeout is configured.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The JSON document written to the command's stdin.
+#[derive(Serialize)]
+struct ExecRequest<'a> {
+    version: u32,
+    /// `commit` when a commit message is wanted, `complete` for any other
+    /// prompt, such as a pull request description.
+    kind: &'a str,
+    /// The full prompt, as sent to the other providers.
+    prompt: &'a str,
+    /// The diff being described, for commit messages only.
+    diff: Option<&'a str>,
+    settings: Settings<'a>,
+}
+
+#[derive(Serialize, Default)]
+struct Settings<'a> {
+    model: &'a str,
+    with_body: bool,
+    language: Option<&'a str>,
+    scope: Option<&'a str>,
+    known_scopes: &'a [String],
+    examples: &'a [String],
+}
+
+/// Delegates generation to an external command. The command reads a JSON
+/// request from stdin and writes the answer to stdout: for commit messages
+/// either a JSON `CommitMessage` or plain text in the Conventional Commits
+/// format.
+pub struct ExecProvider {
+    config: ExecConfig,
+    model: String,
+    timeout: Duration,
+}
+
+impl ExecProvider {
+    pub fn new(config: ExecConfig, model: String) -> Result<Self> {
+        let timeout = match config.timeout.as_deref() {
+            Some(timeout) => humantime::parse_duration(timeout)
+                .map_err(|e| Error::Config(format!("Invalid exec timeout '{}': {}", timeout, e)))?,
+            None => DEFAULT_TIMEOUT,
+        };
+        Ok(Self {
+            config,
+            model,
+            timeout,
+        })
+    }
+
+    /// Runs the command with `request` on stdin and returns its stdout.
+    async fn run(&self, request: &ExecRequest<'_>) -> Result<String> {
+        let input = serde_json::to_vec(request).map_err(std::io::Error::from)?;
+        let command = &self.config.command;
+        let mut child = Command::new(command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| Error::Ai(format!("Failed to run `{}`: {}", command, e)))?;
+
+        // Write from a separate task so a command that answers before reading
+        // all of its input cannot deadlock us.
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let writer = tokio::spawn(async move {
+            // The command may exit without reading everything.
+            let _ = stdin.write_all(&input).await;
+        });
+
+        let output = tokio::time::timeout(self.timeout, child.wait_with_output())
+            .await
+            .map_err(|_| {
+                Error::Ai(format!(
+                    "`{}` did not finish within {}",
+                    command,
+                    humantime::format_duration(self.timeout)
+                ))
+            })??;
+        let _ = writer.await;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::Ai(format!(
+                "`{}` failed with {}: {}",
+                command,
+                output.status,
+                stderr.trim()
+            )));
+        }
+        if !stderr.trim().is_empty() {
+            debug!("`{}` wrote to stderr:\n{}", command, stderr.trim_end());
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if stdout.is_empty() {
+            return Err(Error::Ai(format!("`{}` printed nothing", command)));
+        }
+        Ok(stdout)
+    }
+}
+
+#[async_trait]
+impl AIProvider for ExecProvider {
+    async fn complete(&self, prompt: &str) -> Result<Completion> {
+        self.run(&ExecRequest {
+            version: REQUEST_VERSION,
+            kind: "complete",
+            prompt,
+            diff: None,
+            settings: Settings {
+                model: &self.model,
+                ..Settings::default()
+            },
+        })
+        .await
+        .map(Completion::text)
+    }
+
+    /// Commands do not report token usage.
+    async fn analyze_diff(&self, prompt: &Prompt) -> Result<(CommitMessage, Option<Usage>)> {
+        let rendered = prompt.render();
+        let text = self
+            .run(&ExecRequest {
+                version: REQUEST_VERSION,
+                kind: "commit",
+                prompt: &rendered,
+                diff: Some(&prompt.diff),
+                settings: Settings {
+                    model: &self.model,
+                    with_body: prompt.with_body,
+                    language: prompt.language.as_deref(),
+                    scope: prompt.scope.as_deref(),
+                    known_scopes: &prompt.known_scopes,
+                    examples: &prompt.examples,
+                },
+            })
+            .await?;
+
+        // Anything that looks like JSON must be a valid `CommitMessage`, so a
+        // broken script is not mistaken for a message.
+        let msg = if text.starts_with('{') {
+            serde_json::from_str(&text).map_err(|e| {
+                Error::Ai(format!(
+                    "`{}` printed an invalid commit message: {}",
+                    self.config.command, e
+                ))
+            })?
+        } else {
+            CommitMessage::parse(&text)
+        };
+        Ok((msg, None))
+    }
+
+    /// The answer is only read once the command exits, so there is nothing to
+    /// stream.
+    async fn analyze_diff_streaming(
+        &self,
+        prompt: &Prompt,
+        _on_token: TokenSink<'_>,
+    ) -> Result<(CommitMessage, Option<Usage>)> {
+        self.analyze_diff(prompt).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A provider running `script` with `sh -c`.
+    fn script(script: &str, timeout: Option<&str>) -> ExecProvider {
+        let config = ExecConfig {
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout: timeout.map(str::to_string),
+        };
+        ExecProvider::new(config, "model".to_string()).unwrap()
+    }
+
+    async fn describe(provider: &ExecProvider) -> Result<CommitMessage> {
+        provider
+            .analyze_diff(&Prompt::new("diff --git a/a b/a"))
+            .await
+            .map(|(msg, _)| msg)
+    }
+
+    #[tokio::test]
+    async fn reads_json_messages() {
+        let provider = script(
+            r#"cat >/dev/null; echo '{"commit_type": "feat", "scope": "cli", "message": "add a flag"}'"#,
+            None,
+        );
+        let msg = describe(&provider).await.unwrap();
+        assert_eq!(msg.commit_type, "feat");
+        assert_eq!(msg.scope.as_deref(), Some("cli"));
+        assert_eq!(msg.message, "add a flag");
+    }
+
+    #[tokio::test]
+    async fn reads_plain_text_messages() {
+        let provider = script("cat >/dev/null; echo 'fix(git): handle empty diffs'", None);
+        let msg = describe(&provider).await.unwrap();
+        assert_eq!(msg.commit_type, "fix");
+        assert_eq!(msg.scope.as_deref(), Some("git"));
+        assert_eq!(msg.message, "handle empty diffs");
+    }
+
+    #[tokio::test]
+    async fn passes_the_request_on_stdin() {
+        let provider = script("grep -q '\"kind\":\"commit\"' && echo 'chore: ok'", None);
+        assert_eq!(describe(&provider).await.unwrap().message, "ok");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_json() {
+        let provider = script(r#"echo '{"message": "no type"}'"#, None);
+        let err = describe(&provider).await.unwrap_err();
+        assert!(matches!(&err, Error::Ai(e) if e.contains("invalid commit message")), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_command_fails() {
+        let provider = script("echo 'no model' >&2; exit 3", None);
+        let err = describe(&provider).await.unwrap_err();
+        assert!(matches!(&err, Error::Ai(e) if e.contains("no model")), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_command_prints_nothing() {
+        let provider = script("cat >/dev/null", None);
+        let err = describe(&provider).await.unwrap_err();
+        assert!(matches!(&err, Error::Ai(e) if e.contains("printed nothing")), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn stops_commands_that_take_too_long() {
+        let provider = script("sleep 5", Some("100ms"));
+        let err = describe(&provider).await.unwrap_err();
+        assert!(matches!(&err, Error::Ai(e) if e.contains("did not finish")), "{}", err);
+    }
+}
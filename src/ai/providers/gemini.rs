@@ -3,7 +3,8 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::env;
 
-use crate::ai::repository::{AIProvider, CommitMessage};
+use super::stream::{check_status, for_each_line, parse_json, sse_data};
+use crate::ai::repository::{AIProvider, Completion, TokenSink, Usage};
 use crate::error::{Error, Result};
 
 #[derive(Serialize)]
@@ -22,22 +23,57 @@ struct Part {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GeminiResponse {
+    #[serde(default)]
     candidates: Vec<Candidate>,
+    usage_metadata: Option<UsageMetadata>,
 }
 
+/// Token counts. Streamed chunks carry the running totals.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u64,
+    #[serde(default)]
+    candidates_token_count: u64,
+}
+
+impl From<&UsageMetadata> for Usage {
+    fn from(metadata: &UsageMetadata) -> Self {
+        Self {
+            prompt_tokens: metadata.prompt_token_count,
+            completion_tokens: metadata.candidates_token_count,
+        }
+    }
+}
+
+impl GeminiResponse {
+    /// The text of the first candidate.
+    fn text(&self) -> Option<String> {
+        self.candidates
+            .first()
+            .map(|c| c.content.parts.iter().map(|p| p.text.as_str()).collect())
+    }
+}
+
+// The final chunk of a stream may carry only the finish reason.
 #[derive(Deserialize)]
 struct Candidate {
+    #[serde(default)]
     content: ContentResponse,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct ContentResponse {
+    #[serde(default)]
     parts: Vec<PartResponse>,
 }
 
 #[derive(Deserialize)]
 struct PartResponse {
+    #[serde(default)]
     text: String,
 }
 
@@ -57,31 +93,36 @@ impl GeminiProvider {
             model,
         })
     }
-}
-
-#[async_trait]
-impl AIProvider for GeminiProvider {
-    async fn analyze_diff(&self, diff: &str) -> Result<CommitMessage> {
-        let prompt = format!(
-            "Analyze the following git diff and generate a **concise single-line commit message** in the format 'type(scope): subject':\n\n{}",
-            diff
-        );
 
+    /// Builds a request to `generateContent` or, with `stream`, to
+    /// `streamGenerateContent` as server-sent events.
+    fn request(&self, prompt: &str, stream: bool) -> reqwest::RequestBuilder {
         let request_body = GeminiRequest {
             contents: vec![Content {
-                parts: vec![Part { text: prompt }],
+                parts: vec![Part {
+                    text: prompt.to_string(),
+                }],
             }],
         };
+        let method = if stream {
+            "streamGenerateContent?alt=sse&"
+        } else {
+            "generateContent?"
+        };
 
-        let response = self
-            .client
+        self.client
             .post(format!(
-                "https://generativelanguage.googleapis.com/v1/models/{}:generateContent?key={}",
-                self.model, self.api_key
+                "https://generativelanguage.googleapis.com/v1/models/{}:{}key={}",
+                self.model, method, self.api_key
             ))
             .json(&request_body)
-            .send()
-            .await?;
+    }
+}
+
+#[async_trait]
+impl AIProvider for GeminiProvider {
+    async fn complete(&self, prompt: &str) -> Result<Completion> {
+        let response = self.request(prompt, false).send().await?;
 
         let response_text = response.text().await?;
         let response_json: GeminiResponse = serde_json::from_str(&response_text).map_err(|e| {
@@ -92,28 +133,42 @@ impl AIProvider for GeminiProvider {
         })?;
 
         let text = response_json
-            .candidates
-            .get(0)
-            .and_then(|c| c.content.parts.get(0))
-            .map(|p| p.text.clone())
-            .ok_or_else(|| Error::Ai("Failed to get commit message from Gemini".to_string()))?;
-
-        // Basic parsing, assuming "type(scope): message"
-        let mut parts = text.splitn(2, ':');
-        let header = parts.next().unwrap_or("").trim();
-        let message = parts.next().unwrap_or("").trim().to_string();
-
-        let mut type_scope = header.splitn(2, '(');
-        let commit_type = type_scope.next().unwrap_or("").to_string();
-        let scope = type_scope
-            .next()
-            .and_then(|s| s.strip_suffix(')'))
-            .map(|s| s.to_string());
-
-        Ok(CommitMessage {
-            commit_type,
-            scope,
-            message,
+            .text()
+            .ok_or_else(|| Error::Ai("Failed to get a response from Gemini".to_string()))?;
+
+        Ok(Completion {
+            text,
+            usage: response_json.usage_metadata.as_ref().map(Usage::from),
+        })
+    }
+
+    async fn complete_streaming(
+        &self,
+        prompt: &str,
+        on_token: TokenSink<'_>,
+    ) -> Result<Completion> {
+        let response = check_status(self.request(prompt, true).send().await?, "Gemini").await?;
+
+        let mut text = String::new();
+        let mut usage = None;
+        for_each_line(response, |line| {
+            if let Some(data) = sse_data(line) {
+                let chunk: GeminiResponse = parse_json(data, "Gemini")?;
+                if let Some(metadata) = &chunk.usage_metadata {
+                    usage = Some(metadata.into());
+                }
+                if let Some(piece) = chunk.text() {
+                    on_token(&piece);
+                    text.push_str(&piece);
+                }
+            }
+            Ok(true)
         })
+        .await?;
+
+        if text.is_empty() {
+            return Err(Error::Ai("Failed to get a response from Gemini".to_string()));
+        }
+        Ok(Completion { text, usage })
     }
 }
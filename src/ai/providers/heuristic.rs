@@ -0,0 +1,613 @@
+use async_trait::async_trait;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use crate::ai::prompt::Prompt;
+use crate::ai::repository::{AIProvider, CommitMessage, Completion, TokenSink, Usage};
+use crate::error::{Error, Result};
+use crate::git::{ChangeKind, Diff, FileDiff, LineKind};
+
+/// The longest subject the clauses are combined into.
+const MAX_SUBJECT_LEN: usize = 60;
+
+/// Matches the definition of a named item in the common languages, e.g.
+/// `pub async fn parse`, `class Parser` or `def parse`.
+static DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:(?:pub(?:\([^)]*\))?|export|default|async|unsafe|public|private|protected|static|abstract|final)\s+)*(?:fn|struct|enum|trait|type|mod|class|def|function|interface|func)\s+([A-Za-z_][A-Za-z0-9_]*)",
+    )
+    .unwrap()
+});
+
+/// What a changed file is, judged by its path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum FileKind {
+    Docs,
+    Test,
+    Ci,
+    Build,
+    Lockfile,
+    Source,
+}
+
+/// Describes a diff without any model, from the kinds of files it touches and
+/// the files and symbols it adds, removes or renames. Messages are always in
+/// English.
+#[derive(Debug, Default)]
+pub struct HeuristicProvider;
+
+impl HeuristicProvider {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Builds the commit message for the diff in a prompt.
+    pub fn describe(&self, prompt: &Prompt) -> CommitMessage {
+        // Without the structured diff, the text of a truncated one is all there is.
+        let parsed;
+        let files = match &prompt.changes {
+            Some(changes) => &changes.files,
+            None => {
+                parsed = Diff::parse(&prompt.diff).files;
+                &parsed
+            },
+        };
+        let kinds: BTreeSet<FileKind> = files.iter().map(|f| classify(f.path())).collect();
+        let only = |allowed: &[FileKind]| kinds.iter().all(|kind| allowed.contains(kind));
+
+        let (commit_type, deps) = if kinds.is_empty() {
+            ("chore", false)
+        } else if only(&[FileKind::Lockfile]) {
+            ("chore", true)
+        } else if only(&[FileKind::Docs]) {
+            ("docs", false)
+        } else if only(&[FileKind::Test]) {
+            ("test", false)
+        } else if only(&[FileKind::Ci]) {
+            ("ci", false)
+        } else if only(&[FileKind::Ci, FileKind::Build, FileKind::Lockfile]) {
+            ("build", kinds.contains(&FileKind::Lockfile))
+        } else if !kinds.contains(&FileKind::Source) {
+            ("chore", false)
+        } else {
+            (source_type(files), false)
+        };
+
+        let scope = if deps {
+            Some("deps".to_string())
+        } else {
+            match &prompt.scope {
+                Some(scope) => Some(scope.clone()),
+                None if prompt.known_scopes.is_empty() => module_scope(files),
+                None => None,
+            }
+        };
+
+        CommitMessage {
+            commit_type: commit_type.to_string(),
+            scope,
+            message: subject(files),
+            breaking: false,
+            body: prompt
+                .with_body
+                .then(|| body(files))
+                .filter(|b| !b.is_empty()),
+            footers: Vec::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl AIProvider for HeuristicProvider {
+    async fn complete(&self, _prompt: &str) -> Result<Completion> {
+        Err(Error::Ai("The heuristic provider can only describe commits".to_string()))
+    }
+
+    async fn analyze_diff(&self, prompt: &Prompt) -> Result<(CommitMessage, Option<Usage>)> {
+        Ok((self.describe(prompt), None))
+    }
+
+    async fn analyze_diff_streaming(
+        &self,
+        prompt: &Prompt,
+        _on_token: TokenSink<'_>,
+    ) -> Result<(CommitMessage, Option<Usage>)> {
+        Ok((self.describe(prompt), None))
+    }
+}
+
+fn classify(path: &Path) -> FileKind {
+    const LOCKFILES: &[&str] = &[
+        "Cargo.lock",
+        "package-lock.json",
+        "npm-shrinkwrap.json",
+        "yarn.lock",
+        "pnpm-lock.yaml",
+        "bun.lockb",
+        "go.sum",
+        "poetry.lock",
+        "Pipfile.lock",
+        "uv.lock",
+        "Gemfile.lock",
+        "composer.lock",
+        "flake.lock",
+    ];
+    const CI: &[&str] = &[
+        ".gitlab-ci.yml",
+        ".travis.yml",
+        "azure-pipelines.yml",
+        "Jenkinsfile",
+        "bitbucket-pipelines.yml",
+    ];
+    const BUILD: &[&str] = &[
+        "Cargo.toml",
+        "build.rs",
+        "rust-toolchain",
+        "rust-toolchain.toml",
+        "package.json",
+        "go.mod",
+        "pyproject.toml",
+        "setup.py",
+        "setup.cfg",
+        "requirements.txt",
+        "Gemfile",
+        "composer.json",
+        "pom.xml",
+        "build.gradle",
+        "build.gradle.kts",
+        "Makefile",
+        "CMakeLists.txt",
+        "Dockerfile",
+        "flake.nix",
+    ];
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let text = path.to_string_lossy();
+    let dirs: Vec<String> = path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+        .collect();
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if LOCKFILES.contains(&name.as_ref()) {
+        FileKind::Lockfile
+    } else if CI.contains(&name.as_ref())
+        || text.starts_with(".github/workflows/")
+        || text.starts_with(".circleci/")
+    {
+        FileKind::Ci
+    } else if BUILD.contains(&name.as_ref()) || text.starts_with(".cargo/") {
+        FileKind::Build
+    } else if matches!(extension.as_str(), "md" | "markdown" | "mdx" | "rst" | "adoc")
+        || ["readme", "license", "changelog", "contributing"]
+            .iter()
+            .any(|doc| stem.starts_with(doc))
+        || dirs.iter().any(|d| d == "docs" || d == "doc")
+    {
+        FileKind::Docs
+    } else if dirs
+        .iter()
+        .any(|d| matches!(d.as_str(), "test" | "tests" | "__tests__" | "spec" | "testdata"))
+        || stem == "tests"
+        || stem.starts_with("test_")
+        || ["_test", "_tests", "_spec", ".test", ".spec"]
+            .iter()
+            .any(|suffix| stem.ends_with(suffix))
+    {
+        FileKind::Test
+    } else {
+        FileKind::Source
+    }
+}
+
+/// Picks the type of a change that touches source files: `feat` when it adds
+/// files or definitions, `refactor` when it mostly removes code, and `fix`
+/// otherwise.
+fn source_type(files: &[FileDiff]) -> &'static str {
+    let sources: Vec<&FileDiff> = files
+        .iter()
+        .filter(|f| classify(f.path()) == FileKind::Source)
+        .collect();
+    let symbols = Symbols::collect(sources.iter().copied());
+
+    if sources.iter().any(|f| f.kind == ChangeKind::Added) || !symbols.added.is_empty() {
+        "feat"
+    } else if sources
+        .iter()
+        .all(|f| matches!(f.kind, ChangeKind::Renamed | ChangeKind::Deleted))
+        || sources.iter().map(|f| f.removed_lines()).sum::<usize>()
+            >= sources.iter().map(|f| f.added_lines()).sum::<usize>()
+    {
+        "refactor"
+    } else {
+        "fix"
+    }
+}
+
+/// The module every changed file lives in, if they agree on one. Only used
+/// when the repository layout did not give a scope.
+fn module_scope(files: &[FileDiff]) -> Option<String> {
+    let modules: BTreeSet<String> = files
+        .iter()
+        .filter_map(|file| {
+            let mut components = file.path().components();
+            match (components.next(), components.next(), components.next()) {
+                (Some(first), Some(module), Some(_)) if first.as_os_str() == "src" => {
+                    Some(module.as_os_str().to_string_lossy().into_owned())
+                },
+                _ => None,
+            }
+        })
+        .collect();
+    if modules.len() == 1 {
+        modules.into_iter().next()
+    } else {
+        None
+    }
+}
+
+/// The definitions added, removed and changed by the modified files.
+#[derive(Debug, Default)]
+struct Symbols {
+    added: Vec<String>,
+    removed: Vec<String>,
+    changed: Vec<String>,
+}
+
+impl Symbols {
+    fn collect<'a>(files: impl Iterator<Item = &'a FileDiff>) -> Self {
+        let mut symbols = Self::default();
+        for file in files.filter(|f| f.kind == ChangeKind::Modified) {
+            let mut added = Vec::new();
+            let mut removed = Vec::new();
+            for hunk in &file.hunks {
+                for line in &hunk.lines {
+                    let Some(name) = definition(&line.content) else {
+                        continue;
+                    };
+                    match line.kind {
+                        LineKind::Added => added.push(name),
+                        LineKind::Removed => removed.push(name),
+                        LineKind::Context => {},
+                    }
+                }
+                if let Some(name) = definition(&hunk.section) {
+                    symbols.changed.push(name);
+                }
+            }
+            for name in &added {
+                if removed.contains(name) {
+                    symbols.changed.push(name.clone());
+                } else {
+                    symbols.added.push(name.clone());
+                }
+            }
+            symbols
+                .removed
+                .extend(removed.into_iter().filter(|name| !added.contains(name)));
+        }
+        symbols
+    }
+}
+
+fn definition(line: &str) -> Option<String> {
+    DEFINITION
+        .captures(line)
+        .map(|captures| captures[1].to_string())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+/// Builds the subject from the added, removed and renamed files and symbols,
+/// falling back to the modified files.
+fn subject(files: &[FileDiff]) -> String {
+    let symbols = Symbols::collect(files.iter());
+    let mut adds = Vec::new();
+    let mut removes = Vec::new();
+    let mut renames = Vec::new();
+    let mut updates = Vec::new();
+
+    for file in files {
+        match file.kind {
+            ChangeKind::Added | ChangeKind::Copied => adds.push(file_name(file.path())),
+            ChangeKind::Deleted => removes.push(file_name(file.path())),
+            ChangeKind::Renamed => {
+                let old = file.old_path.as_deref().unwrap_or(Path::new(""));
+                let new = file.path();
+                // A move keeps the file name, so show where it went instead.
+                renames.push(if old.file_name() == new.file_name() {
+                    format!("move {} to {}", old.display(), new.display())
+                } else {
+                    format!("rename {} to {}", file_name(old), file_name(new))
+                });
+            },
+            ChangeKind::Modified | ChangeKind::ModeChanged => updates.push(file_name(file.path())),
+        }
+    }
+    adds.extend(symbols.added);
+    removes.extend(symbols.removed);
+
+    let mut clauses = Vec::new();
+    if !adds.is_empty() {
+        clauses.push(format!("add {}", list(adds)));
+    }
+    if !removes.is_empty() {
+        clauses.push(format!("remove {}", list(removes)));
+    }
+    if renames.len() > 2 {
+        clauses.push(format!("rename {} files", renames.len()));
+    } else {
+        clauses.extend(renames);
+    }
+    if clauses.is_empty() {
+        let changed = if symbols.changed.is_empty() {
+            updates
+        } else {
+            symbols.changed
+        };
+        clauses.push(if changed.is_empty() {
+            "update files".to_string()
+        } else {
+            format!("update {}", list(changed))
+        });
+    }
+
+    // Keep the first clause whole and add the others while they fit.
+    let mut subject = clauses.remove(0);
+    for clause in clauses {
+        if subject.len() + clause.len() + 2 > MAX_SUBJECT_LEN {
+            break;
+        }
+        subject.push_str(", ");
+        subject.push_str(&clause);
+    }
+    subject
+}
+
+/// Joins names as `a, b and c`, summarizing all but the first two when there
+/// are more than three.
+fn list(names: Vec<String>) -> String {
+    let mut unique: Vec<String> = Vec::new();
+    for name in names {
+        if !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    match unique.as_slice() {
+        [] => String::new(),
+        [one] => one.clone(),
+        [first, second] => format!("{} and {}", first, second),
+        [first, second, third] => format!("{}, {} and {}", first, second, third),
+        [first, second, rest @ ..] => format!("{}, {} and {} more", first, second, rest.len()),
+    }
+}
+
+/// Lists every file with its line counts, like the summary `analyze_diff`
+/// prints for truncated diffs.
+fn body(files: &[FileDiff]) -> String {
+    files
+        .iter()
+        .map(|file| {
+            let change = match file.kind {
+                ChangeKind::Added => " (new)".to_string(),
+                ChangeKind::Deleted => " (deleted)".to_string(),
+                ChangeKind::Renamed | ChangeKind::Copied => format!(
+                    " (from {})",
+                    file.old_path.as_deref().unwrap_or(Path::new("")).display()
+                ),
+                ChangeKind::Modified | ChangeKind::ModeChanged => String::new(),
+            };
+            format!(
+                "- {}{} (+{} -{})",
+                file.path().display(),
+                change,
+                file.added_lines(),
+                file.removed_lines()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{Hunk, Line};
+    use std::path::PathBuf;
+
+    fn file(kind: ChangeKind, old: &str, new: &str, lines: &[(LineKind, &str)]) -> FileDiff {
+        let hunks = if lines.is_empty() {
+            Vec::new()
+        } else {
+            vec![Hunk {
+                old_start: 1,
+                old_lines: 1,
+                new_start: 1,
+                new_lines: 1,
+                section: String::new(),
+                lines: lines
+                    .iter()
+                    .map(|&(kind, content)| Line {
+                        kind,
+                        content: content.to_string(),
+                        missing_newline: false,
+                    })
+                    .collect(),
+            }]
+        };
+        FileDiff {
+            kind,
+            old_path: (kind != ChangeKind::Added).then(|| PathBuf::from(old)),
+            new_path: (kind != ChangeKind::Deleted).then(|| PathBuf::from(new)),
+            old_mode: None,
+            new_mode: None,
+            binary: false,
+            similarity: None,
+            hunks,
+        }
+    }
+
+    fn modified(path: &str) -> FileDiff {
+        file(ChangeKind::Modified, path, path, &[(LineKind::Added, "text")])
+    }
+
+    fn describe(files: Vec<FileDiff>) -> CommitMessage {
+        let prompt = Prompt::new("").with_changes(Diff { files });
+        HeuristicProvider::new().describe(&prompt)
+    }
+
+    #[test]
+    fn classifies_paths() {
+        let cases = [
+            ("README.md", FileKind::Docs),
+            ("LICENSE", FileKind::Docs),
+            ("docs/guide.txt", FileKind::Docs),
+            ("tests/cli.rs", FileKind::Test),
+            ("pkg/parse_test.go", FileKind::Test),
+            ("web/app.spec.ts", FileKind::Test),
+            ("Cargo.lock", FileKind::Lockfile),
+            ("web/yarn.lock", FileKind::Lockfile),
+            (".github/workflows/ci.yml", FileKind::Ci),
+            (".gitlab-ci.yml", FileKind::Ci),
+            ("Cargo.toml", FileKind::Build),
+            ("src/main.rs", FileKind::Source),
+        ];
+        for (path, kind) in cases {
+            assert_eq!(classify(Path::new(path)), kind, "{}", path);
+        }
+    }
+
+    #[test]
+    fn describes_changes_by_the_kinds_of_files() {
+        let cases = [
+            (
+                "docs only",
+                vec![modified("README.md"), modified("docs/guide.md")],
+                ("docs", None, "update README.md and guide.md"),
+            ),
+            (
+                "tests only",
+                vec![file(ChangeKind::Added, "", "tests/cli.rs", &[])],
+                ("test", None, "add cli.rs"),
+            ),
+            (
+                "lockfile only",
+                vec![modified("Cargo.lock")],
+                ("chore", Some("deps"), "update Cargo.lock"),
+            ),
+            (
+                "ci only",
+                vec![modified(".github/workflows/ci.yml")],
+                ("ci", None, "update ci.yml"),
+            ),
+            (
+                "manifest and lockfile",
+                vec![modified("Cargo.toml"), modified("Cargo.lock")],
+                ("build", Some("deps"), "update Cargo.toml and Cargo.lock"),
+            ),
+            (
+                "rename",
+                vec![file(
+                    ChangeKind::Renamed,
+                    "src/git/old.rs",
+                    "src/git/new.rs",
+                    &[],
+                )],
+                ("refactor", Some("git"), "rename old.rs to new.rs"),
+            ),
+            (
+                "move",
+                vec![file(
+                    ChangeKind::Renamed,
+                    "src/git/util.rs",
+                    "src/util/util.rs",
+                    &[],
+                )],
+                ("refactor", Some("util"), "move src/git/util.rs to src/util/util.rs"),
+            ),
+            (
+                "new definition",
+                vec![file(
+                    ChangeKind::Modified,
+                    "src/lib.rs",
+                    "src/lib.rs",
+                    &[(LineKind::Added, "pub fn parse() {}")],
+                )],
+                ("feat", None, "add parse"),
+            ),
+            (
+                "changed lines",
+                vec![file(
+                    ChangeKind::Modified,
+                    "src/lib.rs",
+                    "src/lib.rs",
+                    &[
+                        (LineKind::Removed, "    x"),
+                        (LineKind::Added, "    let y = x;"),
+                        (LineKind::Added, "    y + 1"),
+                    ],
+                )],
+                ("fix", None, "update lib.rs"),
+            ),
+            (
+                "removed lines",
+                vec![file(
+                    ChangeKind::Modified,
+                    "src/lib.rs",
+                    "src/lib.rs",
+                    &[(LineKind::Removed, "    dbg!(x);")],
+                )],
+                ("refactor", None, "update lib.rs"),
+            ),
+        ];
+        for (name, files, (commit_type, scope, subject)) in cases {
+            let msg = describe(files);
+            assert_eq!(msg.commit_type, commit_type, "{}", name);
+            assert_eq!(msg.scope.as_deref(), scope, "{}", name);
+            assert_eq!(msg.message, subject, "{}", name);
+        }
+    }
+
+    #[test]
+    fn reads_the_rendered_diff_without_the_structured_one() {
+        let prompt = Prompt::new(
+            "diff --git a/README.md b/README.md\n--- a/README.md\n+++ b/README.md\n@@ -1 +1 @@\n-old\n+new\n",
+        );
+        let msg = HeuristicProvider::new().describe(&prompt);
+        assert_eq!(msg.commit_type, "docs");
+        assert_eq!(msg.message, "update README.md");
+    }
+
+    #[test]
+    fn joins_names() {
+        let cases: [(&[&str], &str); 6] = [
+            (&[], ""),
+            (&["a"], "a"),
+            (&["a", "b"], "a and b"),
+            (&["a", "b", "c"], "a, b and c"),
+            (&["a", "b", "c", "d"], "a, b and 2 more"),
+            (&["a", "a", "b"], "a and b"),
+        ];
+        for (names, expected) in cases {
+            let names = names.iter().map(|n| n.to_string()).collect();
+            assert_eq!(list(names), expected);
+        }
+    }
+}
@@ -1,6 +1,54 @@
+pub mod exec;
 pub mod gemini;
+pub mod heuristic;
 pub mod ollama;
 pub mod openai;
+mod stream;
 
+pub use exec::ExecProvider;
 pub use gemini::GeminiProvider;
+pub use heuristic::HeuristicProvider;
 pub use ollama::OllamaProvider;
+pub use openai::OpenAIProvider;
+
+use crate::ai::repository::AIProvider;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use std::env;
+
+/// Creates the AI provider selected in the configuration.
+pub fn create_provider(config: &Config) -> Result<Box<dyn AIProvider + Send + Sync>> {
+    match config.provider.as_str() {
+        "gemini" => GeminiProvider::new(config.model.clone())
+            .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>),
+        "ollama" => {
+            let ollama_url = config
+                .ollama_url
+                .clone()
+                .unwrap_or_else(|| ollama::DEFAULT_OLLAMA_URL.to_string());
+            OllamaProvider::new(ollama_url, config.model.clone())
+                .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>)
+        },
+        "openai" => {
+            let base_url = config
+                .openai_url
+                .clone()
+                .unwrap_or_else(|| openai::DEFAULT_OPENAI_URL.to_string());
+            let api_key = config
+                .api_key
+                .clone()
+                .or_else(|| env::var("OPENAI_API_KEY").ok());
+            OpenAIProvider::new(base_url, api_key, config.model.clone())
+                .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>)
+        },
+        "exec" => {
+            let exec_config = config.exec.clone().ok_or_else(|| {
+                Error::Config("The exec provider needs an `exec` command".to_string())
+            })?;
+            ExecProvider::new(exec_config, config.model.clone())
+                .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>)
+        },
+        "heuristic" => Ok(Box::new(HeuristicProvider::new())),
+        other => Err(Error::Config(format!("Unsupported AI provider '{}'", other))),
+    }
+}
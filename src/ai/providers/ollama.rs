@@ -1,8 +1,10 @@
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
-use crate::ai::repository::{AIProvider, CommitMessage};
+use super::stream::{check_status, for_each_line, parse_json};
+use crate::ai::repository::{AIProvider, Completion, TokenSink, Usage};
 use crate::error::{Error, Result};
 
 #[derive(Serialize)]
@@ -15,8 +17,56 @@ struct OllamaRequest {
 #[derive(Deserialize)]
 struct OllamaResponse {
     response: String,
+    #[serde(flatten)]
+    counts: EvalCounts,
 }
 
+/// The token counts Ollama adds to the final response.
+#[derive(Deserialize, Default)]
+struct EvalCounts {
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+impl EvalCounts {
+    fn usage(&self) -> Option<Usage> {
+        if self.prompt_eval_count.is_none() && self.eval_count.is_none() {
+            return None;
+        }
+        Some(Usage {
+            prompt_tokens: self.prompt_eval_count.unwrap_or(0),
+            completion_tokens: self.eval_count.unwrap_or(0),
+        })
+    }
+}
+
+/// One line of a streamed response.
+#[derive(Deserialize)]
+struct OllamaChunk {
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    done: bool,
+    error: Option<String>,
+    #[serde(flatten)]
+    counts: EvalCounts,
+}
+
+/// The models installed on the server, from `/api/tags`.
+#[derive(Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<ModelTag>,
+}
+
+#[derive(Deserialize)]
+struct ModelTag {
+    name: String,
+}
+
+/// The server used when `ollama_url` is not set.
+pub const DEFAULT_OLLAMA_URL: &str = "http://localhost:11434";
+
 pub struct OllamaProvider {
     client: Client,
     ollama_url: String,
@@ -31,28 +81,33 @@ impl OllamaProvider {
             model,
         })
     }
-}
 
-#[async_trait]
-impl AIProvider for OllamaProvider {
-    async fn analyze_diff(&self, diff: &str) -> Result<CommitMessage> {
-        let prompt = format!(
-            "Analyze the following git diff and generate a **concise single-line commit message** in the format 'type(scope): subject':\n\n{}",
-            diff
-        );
+    /// Lists the models installed on an Ollama server, which also checks
+    /// that it can be reached.
+    pub async fn list_models(ollama_url: &str) -> Result<Vec<String>> {
+        let client = Client::builder().timeout(Duration::from_secs(5)).build()?;
+        let url = format!("{}/api/tags", ollama_url.trim_end_matches('/'));
+        let response = check_status(client.get(url).send().await?, "Ollama").await?;
+        let tags: TagsResponse = response.json().await?;
+        Ok(tags.models.into_iter().map(|model| model.name).collect())
+    }
 
+    fn request(&self, prompt: &str, stream: bool) -> reqwest::RequestBuilder {
         let request_body = OllamaRequest {
             model: self.model.clone(),
-            prompt,
-            stream: false,
+            prompt: prompt.to_string(),
+            stream,
         };
-
-        let response = self
-            .client
+        self.client
             .post(format!("{}/api/generate", self.ollama_url))
             .json(&request_body)
-            .send()
-            .await?;
+    }
+}
+
+#[async_trait]
+impl AIProvider for OllamaProvider {
+    async fn complete(&self, prompt: &str) -> Result<Completion> {
+        let response = self.request(prompt, false).send().await?;
 
         let response_text = response.text().await?;
         let response_json: OllamaResponse = serde_json::from_str(&response_text).map_err(|e| {
@@ -62,24 +117,86 @@ impl AIProvider for OllamaProvider {
             ))
         })?;
 
-        let text = response_json.response.trim().to_string();
+        Ok(Completion {
+            text: response_json.response.trim().to_string(),
+            usage: response_json.counts.usage(),
+        })
+    }
 
-        // Basic parsing, assuming "type(scope): message"
-        let mut parts = text.splitn(2, ':');
-        let header = parts.next().unwrap_or("").trim();
-        let message = parts.next().unwrap_or("").trim().to_string();
+    /// Reads the newline-delimited JSON Ollama sends with `stream: true`.
+    async fn complete_streaming(
+        &self,
+        prompt: &str,
+        on_token: TokenSink<'_>,
+    ) -> Result<Completion> {
+        let response = check_status(self.request(prompt, true).send().await?, "Ollama").await?;
 
-        let mut type_scope = header.splitn(2, '(');
-        let commit_type = type_scope.next().unwrap_or("").to_string();
-        let scope = type_scope
-            .next()
-            .and_then(|s| s.strip_suffix(')'))
-            .map(|s| s.to_string());
+        let mut text = String::new();
+        let mut usage = None;
+        for_each_line(response, |line| {
+            if line.trim().is_empty() {
+                return Ok(true);
+            }
+            let chunk: OllamaChunk = parse_json(line, "Ollama")?;
+            if let Some(error) = chunk.error {
+                return Err(Error::Ai(format!("Ollama error: {}", error)));
+            }
+            on_token(&chunk.response);
+            text.push_str(&chunk.response);
+            if chunk.done {
+                usage = chunk.counts.usage();
+            }
+            Ok(!chunk.done)
+        })
+        .await?;
 
-        Ok(CommitMessage {
-            commit_type,
-            scope,
-            message,
+        Ok(Completion {
+            text: text.trim().to_string(),
+            usage,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::stream::serve_chunks;
+
+    #[tokio::test]
+    async fn streams_until_the_done_record() {
+        let chunks: &[&[u8]] = &[
+            b"{\"response\":\"feat: \",\"done\":false}\n",
+            // Blank lines between records are skipped.
+            b"\n{\"response\":\"add\",\"do",
+            b"ne\":false}\n{\"response\":\" export\",\"done\":false}\n",
+            b"{\"response\":\"\",\"done\":true,\"prompt_eval_count\":12,\"eval_count\":4}\n",
+            b"{\"response\":\" ignored\",\"done\":false}\n",
+        ];
+        let url = serve_chunks(chunks).await;
+        let provider = OllamaProvider::new(url, "llama3".to_string()).unwrap();
+
+        let mut tokens = Vec::new();
+        let completion = provider
+            .complete_streaming("prompt", &mut |token| tokens.push(token.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(completion.text, "feat: add export");
+        assert_eq!(tokens, ["feat: ", "add", " export", ""]);
+        assert_eq!(
+            completion.usage,
+            Some(Usage {
+                prompt_tokens: 12,
+                completion_tokens: 4,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_errors_in_the_stream() {
+        let url = serve_chunks(&[b"{\"error\":\"model 'x' not found\"}\n"]).await;
+        let provider = OllamaProvider::new(url, "x".to_string()).unwrap();
+        let result = provider.complete_streaming("prompt", &mut |_| {}).await;
+        assert!(matches!(result, Err(Error::Ai(e)) if e.contains("model 'x' not found")));
+    }
+}
@@ -1 +1,232 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
 
+use super::stream::{check_status, for_each_line, parse_json, sse_data};
+use crate::ai::repository::{AIProvider, Completion, TokenSink, Usage};
+use crate::error::{Error, Result};
+
+/// The API used when `openai_url` is not set.
+pub const DEFAULT_OPENAI_URL: &str = "https://api.openai.com/v1";
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+}
+
+/// Asks for a final chunk with the token usage when streaming.
+#[derive(Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
+#[derive(Serialize)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Deserialize)]
+struct ChatResponse {
+    choices: Vec<Choice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct TokenUsage {
+    #[serde(default)]
+    prompt_tokens: u64,
+    #[serde(default)]
+    completion_tokens: u64,
+}
+
+impl From<TokenUsage> for Usage {
+    fn from(usage: TokenUsage) -> Self {
+        Self {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage,
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// One server-sent event of a streamed response.
+#[derive(Deserialize)]
+struct ChatChunk {
+    #[serde(default)]
+    choices: Vec<ChunkChoice>,
+    usage: Option<TokenUsage>,
+}
+
+#[derive(Deserialize)]
+struct ChunkChoice {
+    #[serde(default)]
+    delta: Delta,
+}
+
+#[derive(Deserialize, Default)]
+struct Delta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A provider for the OpenAI chat completions API and the many servers that
+/// speak it, such as vLLM, llama.cpp or LM Studio.
+pub struct OpenAIProvider {
+    client: Client,
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAIProvider {
+    /// `api_key` may be omitted for local servers that do not check it.
+    pub fn new(base_url: String, api_key: Option<String>, model: String) -> Result<Self> {
+        Ok(Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+        })
+    }
+
+    fn request(&self, prompt: &str, stream: bool) -> reqwest::RequestBuilder {
+        let request_body = ChatRequest {
+            model: &self.model,
+            messages: vec![ChatMessage {
+                role: "user",
+                content: prompt,
+            }],
+            stream,
+            stream_options: stream.then_some(StreamOptions {
+                include_usage: true,
+            }),
+        };
+        let request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(&request_body);
+        match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        }
+    }
+}
+
+#[async_trait]
+impl AIProvider for OpenAIProvider {
+    async fn complete(&self, prompt: &str) -> Result<Completion> {
+        let response = check_status(self.request(prompt, false).send().await?, "OpenAI").await?;
+
+        let response_text = response.text().await?;
+        let response_json: ChatResponse = serde_json::from_str(&response_text).map_err(|e| {
+            Error::Ai(format!(
+                "Failed to parse OpenAI response: {}. Raw response: {}",
+                e, response_text
+            ))
+        })?;
+
+        let text = response_json
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .map(|text| text.trim().to_string())
+            .ok_or_else(|| Error::Ai("Failed to get a response from OpenAI".to_string()))?;
+        Ok(Completion {
+            text,
+            usage: response_json.usage.map(Usage::from),
+        })
+    }
+
+    async fn complete_streaming(
+        &self,
+        prompt: &str,
+        on_token: TokenSink<'_>,
+    ) -> Result<Completion> {
+        let response = check_status(self.request(prompt, true).send().await?, "OpenAI").await?;
+
+        let mut text = String::new();
+        let mut usage = None;
+        for_each_line(response, |line| {
+            let Some(data) = sse_data(line) else {
+                return Ok(true);
+            };
+            if data == "[DONE]" {
+                return Ok(false);
+            }
+            let chunk: ChatChunk = parse_json(data, "OpenAI")?;
+            if let Some(chunk_usage) = chunk.usage {
+                usage = Some(chunk_usage.into());
+            }
+            if let Some(piece) = chunk
+                .choices
+                .into_iter()
+                .next()
+                .and_then(|c| c.delta.content)
+            {
+                on_token(&piece);
+                text.push_str(&piece);
+            }
+            Ok(true)
+        })
+        .await?;
+
+        Ok(Completion {
+            text: text.trim().to_string(),
+            usage,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::providers::stream::serve_chunks;
+
+    #[tokio::test]
+    async fn streams_events_until_done() {
+        let chunks: &[&[u8]] = &[
+            // Keep-alive comments and blank lines carry no data.
+            b": keep-alive\n\n",
+            b"data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"}}]}\n\n",
+            b"data: {\"choices\":[{\"delta\":{\"content\":\"fix: \"}}]}\n\nda",
+            b"ta: {\"choices\":[{\"delta\":{\"content\":\"handle timeouts\"}}]}\r\n\r\n",
+            b"data: {\"choices\":[],\"usage\":{\"prompt_tokens\":20,\"completion_tokens\":5}}\n\n",
+            b"data: [DONE]\n\n",
+            b"data: {\"choices\":[{\"delta\":{\"content\":\" ignored\"}}]}\n\n",
+        ];
+        let url = serve_chunks(chunks).await;
+        let provider = OpenAIProvider::new(url, None, "gpt-4o-mini".to_string()).unwrap();
+
+        let mut tokens = Vec::new();
+        let completion = provider
+            .complete_streaming("prompt", &mut |token| tokens.push(token.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(completion.text, "fix: handle timeouts");
+        assert_eq!(tokens, ["fix: ", "handle timeouts"]);
+        assert_eq!(
+            completion.usage,
+            Some(Usage {
+                prompt_tokens: 20,
+                completion_tokens: 5,
+            })
+        );
+    }
+}
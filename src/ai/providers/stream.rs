@@ -0,0 +1,161 @@
+//! Helpers for reading streamed responses: newline-delimited JSON and
+//! server-sent events.
+
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+
+use crate::error::{Error, Result};
+
+/// Fails with the response body unless the request succeeded.
+pub(crate) async fn check_status(response: Response, provider: &str) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    Err(Error::Ai(format!("{} returned {}: {}", provider, status, body.trim())))
+}
+
+/// Calls `on_line` with every line of the body as it arrives, without the
+/// line break. Returns early if `on_line` asks to stop.
+pub(crate) async fn for_each_line(
+    mut response: Response,
+    mut on_line: impl FnMut(&str) -> Result<bool>,
+) -> Result<()> {
+    let mut buffer: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            if !on_line(line.trim_end_matches(['\r', '\n']))? {
+                return Ok(());
+            }
+        }
+    }
+    if !buffer.is_empty() {
+        on_line(String::from_utf8_lossy(&buffer).trim_end())?;
+    }
+    Ok(())
+}
+
+/// Returns the payload of a server-sent event `data:` line.
+pub(crate) fn sse_data(line: &str) -> Option<&str> {
+    line.strip_prefix("data:").map(str::trim_start)
+}
+
+/// Parses one streamed JSON document.
+pub(crate) fn parse_json<T: DeserializeOwned>(text: &str, provider: &str) -> Result<T> {
+    serde_json::from_str(text).map_err(|e| {
+        Error::Ai(format!("Failed to parse {} stream: {}. Raw chunk: {}", provider, e, text))
+    })
+}
+
+/// Serves one HTTP response whose body is sent in `chunks`, with a pause
+/// after each so that the client reads them one by one. Returns the base URL.
+#[cfg(test)]
+pub(crate) async fn serve_chunks(chunks: &[&[u8]]) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    let chunks: Vec<Vec<u8>> = chunks.iter().map(|c| c.to_vec()).collect();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        // Read the whole request, so the client is not cut off while sending.
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text[..end]
+                    .lines()
+                    .find_map(|l| {
+                        l.to_ascii_lowercase()
+                            .strip_prefix("content-length:")
+                            .map(|v| v.trim().parse::<usize>().unwrap())
+                    })
+                    .unwrap_or(0);
+                if request.len() >= end + 4 + length {
+                    break;
+                }
+            }
+            if read == 0 {
+                break;
+            }
+        }
+
+        socket
+            .write_all(
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        for chunk in chunks {
+            let mut frame = format!("{:x}\r\n", chunk.len()).into_bytes();
+            frame.extend_from_slice(&chunk);
+            frame.extend_from_slice(b"\r\n");
+            socket.write_all(&frame).await.unwrap();
+            socket.flush().await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        socket.write_all(b"0\r\n\r\n").await.unwrap();
+    });
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn lines(chunks: &[&[u8]], stop_at: Option<&str>) -> Vec<String> {
+        let url = serve_chunks(chunks).await;
+        let response = reqwest::get(url).await.unwrap();
+        let mut lines = Vec::new();
+        for_each_line(response, |line| {
+            lines.push(line.to_string());
+            Ok(Some(line) != stop_at)
+        })
+        .await
+        .unwrap();
+        lines
+    }
+
+    #[tokio::test]
+    async fn joins_lines_split_across_chunks() {
+        let chunks: &[&[u8]] = &[
+            b"data: {\"a\"",
+            b":1}\r\n\r\nda",
+            // A character split between chunks.
+            b"ta: caf\xc3",
+            b"\xa9\n",
+            b"tail",
+        ];
+        assert_eq!(lines(chunks, None).await, ["data: {\"a\":1}", "", "data: caf\u{e9}", "tail"]);
+    }
+
+    #[tokio::test]
+    async fn stops_when_asked() {
+        let chunks: &[&[u8]] = &[b"data: 1\n\ndata: [DONE]\n", b"data: 2\n"];
+        assert_eq!(lines(chunks, Some("data: [DONE]")).await, ["data: 1", "", "data: [DONE]"]);
+    }
+
+    #[test]
+    fn reads_event_data() {
+        let cases = [
+            ("data: {\"a\":1}", Some("{\"a\":1}")),
+            ("data:[DONE]", Some("[DONE]")),
+            ("data:", Some("")),
+            // Keep-alive comments, blank lines and other fields.
+            (": keep-alive", None),
+            ("", None),
+            ("event: ping", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(sse_data(line), expected, "{}", line);
+        }
+    }
+}
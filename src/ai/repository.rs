@@ -1,26 +1,309 @@
+use crate::ai::prompt::Prompt;
 use crate::error::Result;
 use async_trait::async_trait;
 
 /// Represents a structured commit message.
-#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct CommitMessage {
     pub commit_type: String,
     pub scope: Option<String>,
     pub message: String,
+    /// Set by a `!` after the type or scope, or by a `BREAKING CHANGE` footer.
+    #[serde(default)]
+    pub breaking: bool,
+    #[serde(default)]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub footers: Vec<Footer>,
 }
 
+/// A git trailer such as `Refs: #123` or `Co-authored-by: Name <email>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Footer {
+    pub token: String,
+    pub value: String,
+}
+
+impl CommitMessage {
+    /// Parses a Conventional Commit message, as returned by the AI providers or
+    /// read from the history.
+    ///
+    /// The header is `type(scope)!: subject`. A final paragraph made only of
+    /// `Token: value` lines is read as footers, and anything in between is the
+    /// body. Markdown code fences and quotes around the message are ignored.
+    pub fn parse(text: &str) -> Self {
+        let text = strip_wrapping(text);
+        let (header, rest) = match text.split_once('\n') {
+            Some((header, rest)) => (header.trim(), rest),
+            None => (text.trim(), ""),
+        };
+
+        let (commit_type, scope, breaking, message) = parse_header(header);
+        let mut commit = Self {
+            commit_type,
+            scope,
+            message,
+            breaking,
+            body: None,
+            footers: Vec::new(),
+        };
+
+        let mut paragraphs: Vec<&str> = rest
+            .split("\n\n")
+            .map(|p| p.trim_matches('\n'))
+            .filter(|p| !p.trim().is_empty())
+            .collect();
+
+        if let Some(last) = paragraphs.last()
+            && let Some(footers) = parse_footers(last)
+        {
+            commit.footers = footers;
+            paragraphs.pop();
+        }
+
+        if !paragraphs.is_empty() {
+            commit.body = Some(paragraphs.join("\n\n"));
+        }
+
+        if commit.footers.iter().any(Footer::is_breaking_change) {
+            commit.breaking = true;
+        }
+
+        commit
+    }
+
+    /// Checks that the message has a Conventional Commit header: a word-like
+    /// type and a non-empty subject.
+    pub fn is_conventional(&self) -> bool {
+        !self.commit_type.is_empty()
+            && self.commit_type.chars().all(|c| c.is_ascii_alphanumeric())
+            && !self.message.is_empty()
+    }
+}
+
+impl Footer {
+    pub fn new(token: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            token: token.into(),
+            value: value.into(),
+        }
+    }
+
+    pub fn is_breaking_change(&self) -> bool {
+        self.token == "BREAKING CHANGE" || self.token == "BREAKING-CHANGE"
+    }
+}
+
+/// Removes Markdown code fences and surrounding quotes that models like to add.
+fn strip_wrapping(text: &str) -> &str {
+    let mut text = text.trim();
+    if let Some(inner) = text.strip_prefix("```") {
+        // Skip the optional language tag on the opening fence.
+        let inner = inner.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+        text = inner.trim_end().strip_suffix("```").unwrap_or(inner).trim();
+    }
+    for quote in ['`', '"', '\''] {
+        if text.len() > 1 && text.starts_with(quote) && text.ends_with(quote) {
+            text = text[1..text.len() - 1].trim();
+        }
+    }
+    text
+}
+
+fn parse_header(header: &str) -> (String, Option<String>, bool, String) {
+    let mut parts = header.splitn(2, ':');
+    let prefix = parts.next().unwrap_or("").trim();
+    let message = parts.next().unwrap_or("").trim().to_string();
+
+    let (prefix, breaking) = match prefix.strip_suffix('!') {
+        Some(prefix) => (prefix, true),
+        None => (prefix, false),
+    };
+
+    let mut type_scope = prefix.splitn(2, '(');
+    let commit_type = type_scope.next().unwrap_or("").trim().to_string();
+    let scope = type_scope
+        .next()
+        .and_then(|s| s.strip_suffix(')'))
+        .map(|s| s.to_string());
+
+    (commit_type, scope, breaking, message)
+}
+
+/// Parses a paragraph as git trailers. Returns `None` if any line is neither a
+/// trailer nor the continuation of one.
+fn parse_footers(paragraph: &str) -> Option<Vec<Footer>> {
+    let mut footers: Vec<Footer> = Vec::new();
+    for line in paragraph.lines() {
+        if line.starts_with([' ', '\t']) {
+            let last = footers.last_mut()?;
+            last.value.push('\n');
+            last.value.push_str(line.trim());
+            continue;
+        }
+
+        let (token, value) = split_footer(line)?;
+        footers.push(Footer::new(token, value));
+    }
+    Some(footers)
+}
+
+fn split_footer(line: &str) -> Option<(&str, &str)> {
+    for breaking in ["BREAKING CHANGE", "BREAKING-CHANGE"] {
+        if let Some(value) = line
+            .strip_prefix(breaking)
+            .and_then(|r| r.strip_prefix(':'))
+        {
+            return Some((breaking, value.trim()));
+        }
+    }
+
+    let (token, value) = match line.split_once(": ") {
+        Some(pair) => pair,
+        // `Fixes #123` style footers.
+        None => line
+            .split_once(" #")
+            .map(|(t, _)| (t, &line[t.len() + 1..]))?,
+    };
+    let valid_token =
+        !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    valid_token.then_some((token, value.trim()))
+}
+
+/// The tokens a provider reported for one response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// A model's text response, with the tokens it used if the provider reports
+/// them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub usage: Option<Usage>,
+}
+
+impl Completion {
+    /// A response without usage, from providers that do not report it.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            usage: None,
+        }
+    }
+}
+
+/// Receives the text of a response piece by piece, as it is generated.
+pub type TokenSink<'a> = &'a mut (dyn FnMut(&str) + Send);
+
 /// A trait for AI providers that can analyze a diff and suggest a commit
 /// message.
 #[async_trait]
 pub trait AIProvider {
+    /// Sends a prompt to the model and returns its raw text response.
+    async fn complete(&self, prompt: &str) -> Result<Completion>;
+
+    /// Like `complete`, but hands the response to `on_token` as it arrives.
+    /// Providers that cannot stream pass the whole response at once.
+    async fn complete_streaming(
+        &self,
+        prompt: &str,
+        on_token: TokenSink<'_>,
+    ) -> Result<Completion> {
+        let completion = self.complete(prompt).await?;
+        on_token(&completion.text);
+        Ok(completion)
+    }
+
     /// Analyzes a git diff and returns a structured commit message.
     ///
     /// # Arguments
     ///
-    /// * `diff` - A string slice that holds the git diff to be analyzed.
+    /// * `prompt` - The prompt holding the git diff to be analyzed.
     ///
     /// # Returns
     ///
-    /// A `Result` containing the `CommitMessage`.
-    async fn analyze_diff(&self, diff: &str) -> Result<CommitMessage>;
+    /// A `Result` containing the `CommitMessage` and the tokens the provider
+    /// reported for it.
+    async fn analyze_diff(&self, prompt: &Prompt) -> Result<(CommitMessage, Option<Usage>)> {
+        let completion = self.complete(&prompt.render()).await?;
+        Ok((CommitMessage::parse(&completion.text), completion.usage))
+    }
+
+    /// Like `analyze_diff`, but shows the response through `on_token` while it
+    /// is generated. The parsed message is the same.
+    async fn analyze_diff_streaming(
+        &self,
+        prompt: &Prompt,
+        on_token: TokenSink<'_>,
+    ) -> Result<(CommitMessage, Option<Usage>)> {
+        let completion = self.complete_streaming(&prompt.render(), on_token).await?;
+        Ok((CommitMessage::parse(&completion.text), completion.usage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_headers() {
+        let cases = [
+            ("feat: add login", ("feat", None, false, "add login")),
+            ("fix(api): handle 404", ("fix", Some("api"), false, "handle 404")),
+            ("feat!: drop v1", ("feat", None, true, "drop v1")),
+            (
+                "refactor(core)!: rename Config",
+                ("refactor", Some("core"), true, "rename Config"),
+            ),
+            ("```\nfix: strip fences\n```", ("fix", None, false, "strip fences")),
+            ("\"docs: quoted\"", ("docs", None, false, "quoted")),
+        ];
+        for (text, (commit_type, scope, breaking, message)) in cases {
+            let msg = CommitMessage::parse(text);
+            assert_eq!(msg.commit_type, commit_type, "{}", text);
+            assert_eq!(msg.scope.as_deref(), scope, "{}", text);
+            assert_eq!(msg.breaking, breaking, "{}", text);
+            assert_eq!(msg.message, message, "{}", text);
+        }
+    }
+
+    #[test]
+    fn parses_bodies_and_footers() {
+        let msg = CommitMessage::parse(
+            "feat(cli): add --json\n\nPrint the result as JSON.\n\nThe schema is documented.\n\nRefs: #12\nBREAKING CHANGE: the output\n  format changed\nCo-authored-by: A <a@example.com>",
+        );
+        assert_eq!(
+            msg.body.as_deref(),
+            Some("Print the result as JSON.\n\nThe schema is documented.")
+        );
+        assert_eq!(
+            msg.footers,
+            [
+                Footer::new("Refs", "#12"),
+                Footer::new("BREAKING CHANGE", "the output\nformat changed"),
+                Footer::new("Co-authored-by", "A <a@example.com>"),
+            ]
+        );
+        assert!(msg.breaking);
+    }
+
+    #[test]
+    fn reads_a_paragraph_with_other_lines_as_body() {
+        let msg = CommitMessage::parse("fix: typo\n\nSee: the docs\nfor details.");
+        assert_eq!(msg.body.as_deref(), Some("See: the docs\nfor details."));
+        assert!(msg.footers.is_empty());
+
+        let msg = CommitMessage::parse("fix: close the file\n\nFixes #42");
+        assert_eq!(msg.footers, [Footer::new("Fixes", "#42")]);
+        assert!(!msg.breaking);
+    }
 }
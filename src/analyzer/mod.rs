@@ -1,23 +1,66 @@
 use crate::config::SmartCommitConfig;
+use crate::git::Diff;
 use log::debug;
 
-pub fn analyze_diff(diff: &str, smart_commit_config: &SmartCommitConfig) -> String {
-    let lines: Vec<&str> = diff.lines().collect();
+pub mod scope;
+
+pub use scope::{ScopeInference, ScopeResolver};
+
+/// Renders the diff for the AI provider, keeping it within the configured
+/// line threshold.
+///
+/// Files are included whole while they fit. The file that crosses the
+/// threshold is cut off, and any remaining files are listed by path with their
+/// line counts so the model still knows they changed.
+pub fn analyze_diff(diff: &Diff, smart_commit_config: &SmartCommitConfig) -> String {
     let threshold = smart_commit_config.line_threshold as usize;
+    let total_lines = diff.line_count();
 
-    if lines.len() > threshold {
-        debug!(
-            "Diff exceeds line threshold ({} > {}). Truncating diff.",
-            lines.len(),
-            threshold
-        );
-        let truncated_diff = lines[..threshold].join("\n");
-        format!(
-            "{}
-... [Diff truncated to {} lines] ...",
-            truncated_diff, threshold
-        )
-    } else {
-        diff.to_string()
+    if total_lines <= threshold {
+        return diff.to_string();
     }
+
+    debug!(
+        "Diff exceeds line threshold ({} > {}). Truncating diff.",
+        total_lines, threshold
+    );
+
+    let mut output = String::new();
+    let mut used = 0;
+    let mut omitted = Vec::new();
+
+    for file in &diff.files {
+        if used >= threshold {
+            omitted.push(file);
+            continue;
+        }
+
+        let rendered = file.to_string();
+        let remaining = threshold - used;
+        let lines: Vec<&str> = rendered.lines().collect();
+        if lines.len() <= remaining {
+            output.push_str(&rendered);
+            used += lines.len();
+        } else {
+            output.push_str(&lines[..remaining].join("\n"));
+            output.push('\n');
+            used = threshold;
+        }
+    }
+
+    output.push_str(&format!("... [Diff truncated to {} lines] ...", threshold));
+
+    if !omitted.is_empty() {
+        output.push_str("\nOther changed files:");
+        for file in omitted {
+            output.push_str(&format!(
+                "\n{} (+{} -{})",
+                file.path().display(),
+                file.added_lines(),
+                file.removed_lines()
+            ));
+        }
+    }
+
+    output
 }
@@ -0,0 +1,316 @@
+use crate::config::ScopeConfig;
+use crate::git::Diff;
+use globset::{Glob, GlobBuilder, GlobMatcher};
+use log::debug;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The scope inferred from the paths of a diff.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScopeInference {
+    /// The scope shared by every changed file that maps to one.
+    pub scope: Option<String>,
+    /// Every distinct scope touched by the diff, sorted.
+    pub candidates: Vec<String>,
+}
+
+/// A set of workspace member patterns, e.g. `crates/*` or `!crates/legacy`.
+#[derive(Debug, Default)]
+struct MemberPatterns {
+    include: Vec<GlobMatcher>,
+    exclude: Vec<GlobMatcher>,
+}
+
+impl MemberPatterns {
+    fn add(&mut self, pattern: &str) {
+        let (pattern, negated) = match pattern.strip_prefix('!') {
+            Some(p) => (p, true),
+            None => (pattern, false),
+        };
+        let pattern = pattern.trim_start_matches("./").trim_end_matches('/');
+        match GlobBuilder::new(pattern).literal_separator(true).build() {
+            Ok(glob) if negated => self.exclude.push(glob.compile_matcher()),
+            Ok(glob) => self.include.push(glob.compile_matcher()),
+            Err(e) => debug!("Ignoring invalid workspace pattern '{}': {}", pattern, e),
+        }
+    }
+
+    fn is_member(&self, dir: &Path) -> bool {
+        self.include.iter().any(|g| g.is_match(dir))
+            && !self.exclude.iter().any(|g| g.is_match(dir))
+    }
+}
+
+/// Maps changed paths to scope names using the repository layout.
+///
+/// In order of precedence, a path's scope comes from the configured path
+/// rules, the Cargo/npm/pnpm workspace member it belongs to, the nearest Go
+/// module below the root, or its `src/<module>` directory.
+#[derive(Debug)]
+pub struct ScopeResolver {
+    root: PathBuf,
+    rules: Vec<(GlobMatcher, String)>,
+    members: MemberPatterns,
+}
+
+impl ScopeResolver {
+    /// Reads the workspace layout at `root`. Unreadable or malformed manifests
+    /// are skipped.
+    pub fn new(root: &Path, config: &ScopeConfig) -> Self {
+        let rules = config
+            .rules
+            .iter()
+            .filter_map(|rule| match Glob::new(&rule.path) {
+                Ok(glob) => Some((glob.compile_matcher(), rule.scope.clone())),
+                Err(e) => {
+                    debug!("Ignoring invalid scope rule '{}': {}", rule.path, e);
+                    None
+                },
+            })
+            .collect();
+
+        let mut members = MemberPatterns::default();
+        for pattern in workspace_patterns(root) {
+            members.add(&pattern);
+        }
+
+        Self {
+            root: root.to_path_buf(),
+            rules,
+            members,
+        }
+    }
+
+    /// Returns the scope for a path relative to the repository root.
+    pub fn scope_for(&self, path: &Path) -> Option<String> {
+        if let Some((_, scope)) = self.rules.iter().find(|(glob, _)| glob.is_match(path)) {
+            return Some(scope.clone());
+        }
+
+        // Shallowest first, so `packages/**` maps to the package directory.
+        let mut ancestors: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .filter(|a| !a.as_os_str().is_empty())
+            .collect();
+        ancestors.reverse();
+
+        if let Some(member) = ancestors.iter().find(|dir| self.members.is_member(dir)) {
+            return dir_name(member);
+        }
+
+        if let Some(module) = ancestors
+            .iter()
+            .rev()
+            .find(|dir| self.root.join(dir).join("go.mod").is_file())
+        {
+            return dir_name(module);
+        }
+
+        let mut components = path.components();
+        match (components.next(), components.next(), components.next()) {
+            (Some(first), Some(module), Some(_)) if first.as_os_str() == "src" => {
+                Some(module.as_os_str().to_string_lossy().into_owned())
+            },
+            _ => None,
+        }
+    }
+
+    /// Infers a single scope for the whole diff. Files that do not map to any
+    /// scope, such as root-level manifests, do not count against it.
+    pub fn infer(&self, diff: &Diff) -> ScopeInference {
+        let candidates: BTreeSet<String> = diff
+            .files
+            .iter()
+            .filter_map(|file| self.scope_for(file.path()))
+            .collect();
+
+        ScopeInference {
+            scope: if candidates.len() == 1 {
+                candidates.first().cloned()
+            } else {
+                None
+            },
+            candidates: candidates.into_iter().collect(),
+        }
+    }
+}
+
+fn dir_name(dir: &Path) -> Option<String> {
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Collects workspace member patterns from `Cargo.toml`, `package.json` and
+/// `pnpm-workspace.yaml` at the repository root.
+fn workspace_patterns(root: &Path) -> Vec<String> {
+    let mut patterns = Vec::new();
+
+    if let Ok(content) = fs::read_to_string(root.join("Cargo.toml"))
+        && let Ok(document) = content.parse::<toml_edit::DocumentMut>()
+        && let Some(members) = document
+            .get("workspace")
+            .and_then(|w| w.get("members"))
+            .and_then(|m| m.as_array())
+    {
+        patterns.extend(members.iter().filter_map(|m| m.as_str()).map(String::from));
+    }
+
+    if let Ok(content) = fs::read_to_string(root.join("package.json"))
+        && let Ok(json) = serde_json::from_str::<serde_json::Value>(&content)
+    {
+        let workspaces = json.get("workspaces");
+        let list = workspaces.and_then(|w| w.as_array()).or_else(|| {
+            workspaces
+                .and_then(|w| w.get("packages"))
+                .and_then(|p| p.as_array())
+        });
+        if let Some(list) = list {
+            patterns.extend(list.iter().filter_map(|m| m.as_str()).map(String::from));
+        }
+    }
+
+    if let Ok(content) = fs::read_to_string(root.join("pnpm-workspace.yaml"))
+        && let Ok(yaml) = serde_yaml::from_str::<serde_yaml::Value>(&content)
+        && let Some(list) = yaml.get("packages").and_then(|p| p.as_sequence())
+    {
+        patterns.extend(list.iter().filter_map(|m| m.as_str()).map(String::from));
+    }
+
+    patterns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScopeRule;
+    use crate::git::testing::TempRepo;
+    use crate::git::{ChangeKind, FileDiff};
+
+    fn resolver(repo: &TempRepo, rules: &[(&str, &str)]) -> ScopeResolver {
+        let config = ScopeConfig {
+            rules: rules
+                .iter()
+                .map(|(path, scope)| ScopeRule {
+                    path: path.to_string(),
+                    scope: scope.to_string(),
+                })
+                .collect(),
+            ..ScopeConfig::default()
+        };
+        ScopeResolver::new(&repo.path, &config)
+    }
+
+    fn scope(resolver: &ScopeResolver, path: &str) -> Option<String> {
+        resolver.scope_for(Path::new(path))
+    }
+
+    fn diff(paths: &[&str]) -> Diff {
+        Diff {
+            files: paths
+                .iter()
+                .map(|path| FileDiff {
+                    kind: ChangeKind::Modified,
+                    old_path: Some(PathBuf::from(path)),
+                    new_path: Some(PathBuf::from(path)),
+                    old_mode: None,
+                    new_mode: None,
+                    binary: false,
+                    similarity: None,
+                    hunks: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn rules_take_precedence_over_the_layout() {
+        let repo = TempRepo::new();
+        repo.write("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n");
+        let resolver = resolver(&repo, &[("crates/core/src/net/**", "net"), ("docs/**", "docs")]);
+
+        assert_eq!(scope(&resolver, "crates/core/src/net/tcp.rs").as_deref(), Some("net"));
+        assert_eq!(scope(&resolver, "crates/core/src/lib.rs").as_deref(), Some("core"));
+        assert_eq!(scope(&resolver, "docs/guide/intro.md").as_deref(), Some("docs"));
+    }
+
+    #[test]
+    fn maps_paths_to_workspace_members() {
+        let repo = TempRepo::new();
+        repo.write("Cargo.toml", "[workspace]\nmembers = [\"crates/*\", \"!crates/legacy\"]\n");
+        repo.write("package.json", r#"{"workspaces": {"packages": ["./apps/*/"]}}"#);
+        repo.write(
+            "pnpm-workspace.yaml",
+            "packages:\n  - 'packages/*'\n  - '!packages/internal'\n",
+        );
+        let resolver = resolver(&repo, &[]);
+
+        let cases = [
+            ("crates/core/src/lib.rs", Some("core")),
+            ("crates/legacy/src/lib.rs", None),
+            ("apps/web/index.ts", Some("web")),
+            ("packages/ui/src/button.tsx", Some("ui")),
+            ("packages/internal/index.ts", None),
+            // Members match whole directories, not nested ones.
+            ("crates/core/nested/lib.rs", Some("core")),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(scope(&resolver, path).as_deref(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn uses_the_nearest_go_module() {
+        let repo = TempRepo::new();
+        repo.write("go.mod", "module example.com/root\n");
+        repo.write("services/api/go.mod", "module example.com/api\n");
+        repo.write("services/api/auth/go.mod", "module example.com/auth\n");
+        let resolver = resolver(&repo, &[]);
+
+        let cases = [
+            ("services/api/handler.go", Some("api")),
+            ("services/api/auth/token.go", Some("auth")),
+            ("services/api/auth/jwt/sign.go", Some("auth")),
+            // The root module is not a scope.
+            ("cmd/main.go", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(scope(&resolver, path).as_deref(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_the_src_module() {
+        let repo = TempRepo::new();
+        let resolver = resolver(&repo, &[]);
+
+        let cases = [
+            ("src/git/cli.rs", Some("git")),
+            ("src/ai/cache/redis_cache.rs", Some("ai")),
+            ("src/main.rs", None),
+            ("tests/cli.rs", None),
+            ("README.md", None),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(scope(&resolver, path).as_deref(), expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn infers_a_scope_ignoring_root_level_files() {
+        let repo = TempRepo::new();
+        let resolver = resolver(&repo, &[]);
+
+        let single = resolver.infer(&diff(&["src/git/cli.rs", "src/git/mod.rs", "Cargo.toml"]));
+        assert_eq!(single.scope.as_deref(), Some("git"));
+        assert_eq!(single.candidates, ["git"]);
+
+        let several = resolver.infer(&diff(&["src/git/cli.rs", "src/ai/mod.rs", "README.md"]));
+        assert_eq!(several.scope, None);
+        assert_eq!(several.candidates, ["ai", "git"]);
+
+        let none = resolver.infer(&diff(&["Cargo.toml", "README.md"]));
+        assert_eq!(none, ScopeInference::default());
+    }
+}
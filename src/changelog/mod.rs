@@ -0,0 +1,304 @@
+use crate::ai::repository::CommitMessage;
+use crate::error::Result;
+use crate::git::CommitInfo;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+const CHANGELOG_HEADER: &str = "# Changelog\n\nAll notable changes to this project will be documented in this file.\n\nThe format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).\n";
+
+/// Conventional Commit types and the changelog section they are listed under,
+/// in display order. Types marked as hidden are only included on request.
+const SECTIONS: &[(&str, &str, bool)] = &[
+    ("feat", "Added", false),
+    ("fix", "Fixed", false),
+    ("perf", "Performance", false),
+    ("refactor", "Changed", false),
+    ("revert", "Reverted", false),
+    ("docs", "Documentation", false),
+    ("build", "Build", true),
+    ("ci", "Continuous Integration", true),
+    ("test", "Tests", true),
+    ("style", "Style", true),
+    ("chore", "Chores", true),
+];
+
+/// A single commit as it appears in the changelog.
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    pub id: String,
+    pub commit_type: String,
+    pub scope: Option<String>,
+    pub subject: String,
+    pub breaking: bool,
+    /// The text of the `BREAKING CHANGE` footer, if any.
+    pub breaking_note: Option<String>,
+}
+
+/// The entries sharing one changelog section.
+#[derive(Debug, Clone, Serialize)]
+pub struct Section {
+    pub title: String,
+    pub commit_type: String,
+    pub entries: Vec<Entry>,
+}
+
+/// The changelog of one release.
+#[derive(Debug, Clone, Serialize)]
+pub struct Changelog {
+    pub version: String,
+    pub date: Option<String>,
+    pub breaking: Vec<Entry>,
+    pub sections: Vec<Section>,
+}
+
+impl Entry {
+    /// Builds an entry from a commit, or `None` if its message is not a
+    /// Conventional Commit.
+    pub fn from_commit(commit: &CommitInfo) -> Option<Self> {
+        let parsed = CommitMessage::parse(&commit.message);
+        if !parsed.is_conventional() {
+            return None;
+        }
+
+        Some(Self {
+            id: commit.short_id().to_string(),
+            commit_type: parsed.commit_type.to_lowercase(),
+            scope: parsed.scope.filter(|s| !s.is_empty()),
+            subject: parsed.message,
+            breaking: parsed.breaking,
+            breaking_note: parsed
+                .footers
+                .into_iter()
+                .find(|f| f.is_breaking_change())
+                .map(|f| f.value),
+        })
+    }
+
+    fn to_markdown(&self) -> String {
+        let text = match (&self.breaking_note, self.breaking) {
+            (Some(note), true) => note.as_str(),
+            _ => self.subject.as_str(),
+        };
+        match &self.scope {
+            Some(scope) => format!("- **{}:** {} ({})", scope, text, self.id),
+            None => format!("- {} ({})", text, self.id),
+        }
+    }
+}
+
+impl Changelog {
+    /// Groups the commits by type, and by scope within each type. Breaking
+    /// changes are listed once, in their own section, whatever their type.
+    ///
+    /// Commits that are not Conventional Commits are skipped, as are types such
+    /// as `chore` and `ci` unless `include_all` is set.
+    pub fn from_commits(
+        version: impl Into<String>,
+        date: Option<String>,
+        commits: &[CommitInfo],
+        include_all: bool,
+    ) -> Self {
+        let (breaking, entries): (Vec<Entry>, Vec<Entry>) = commits
+            .iter()
+            .filter_map(Entry::from_commit)
+            .partition(|e| e.breaking);
+
+        let mut sections: Vec<Section> = SECTIONS
+            .iter()
+            .filter(|(_, _, hidden)| include_all || !hidden)
+            .map(|(commit_type, title, _)| Section {
+                title: title.to_string(),
+                commit_type: commit_type.to_string(),
+                entries: entries
+                    .iter()
+                    .filter(|e| e.commit_type == *commit_type)
+                    .cloned()
+                    .collect(),
+            })
+            .collect();
+
+        if include_all {
+            let others: Vec<Entry> = entries
+                .iter()
+                .filter(|e| !SECTIONS.iter().any(|(t, _, _)| *t == e.commit_type))
+                .cloned()
+                .collect();
+            sections.push(Section {
+                title: "Other".to_string(),
+                commit_type: "other".to_string(),
+                entries: others,
+            });
+        }
+
+        for section in &mut sections {
+            // Stable sort keeps the commit order within each scope.
+            section.entries.sort_by(|a, b| {
+                a.scope
+                    .is_some()
+                    .cmp(&b.scope.is_some())
+                    .then(a.scope.cmp(&b.scope))
+            });
+        }
+        sections.retain(|s| !s.entries.is_empty());
+
+        Self {
+            version: version.into(),
+            date,
+            breaking,
+            sections,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sections.is_empty() && self.breaking.is_empty()
+    }
+
+    /// Returns mutable references to every entry subject, in display order.
+    pub fn subjects_mut(&mut self) -> Vec<&mut String> {
+        self.breaking
+            .iter_mut()
+            .chain(self.sections.iter_mut().flat_map(|s| s.entries.iter_mut()))
+            .map(|e| &mut e.subject)
+            .collect()
+    }
+
+    /// Renders the release in Keep a Changelog Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut output = match &self.date {
+            Some(date) => format!("## [{}] - {}\n", self.version, date),
+            None => format!("## [{}]\n", self.version),
+        };
+
+        if !self.breaking.is_empty() {
+            output.push_str("\n### BREAKING CHANGES\n\n");
+            for entry in &self.breaking {
+                output.push_str(&entry.to_markdown());
+                output.push('\n');
+            }
+        }
+
+        for section in &self.sections {
+            output.push_str(&format!("\n### {}\n\n", section.title));
+            for entry in &section.entries {
+                output.push_str(&entry.to_markdown());
+                output.push('\n');
+            }
+        }
+
+        output
+    }
+}
+
+/// Inserts a release section at the top of a changelog file, below its header.
+/// The file is created with a Keep a Changelog header if it does not exist.
+pub fn prepend_to_file(path: &Path, release: &str) -> Result<()> {
+    let existing = if path.exists() {
+        fs::read_to_string(path)?
+    } else {
+        CHANGELOG_HEADER.to_string()
+    };
+
+    let content = match existing.find("\n## ") {
+        Some(index) => format!(
+            "{}\n\n{}\n{}",
+            existing[..index].trim_end(),
+            release.trim_end(),
+            &existing[index..]
+        ),
+        None => format!("{}\n\n{}", existing.trim_end(), release),
+    };
+
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// Formats a Unix timestamp as an ISO 8601 date (`YYYY-MM-DD`) in UTC.
+pub fn format_date(timestamp: i64) -> String {
+    // Howard Hinnant's days-to-civil algorithm.
+    let days = timestamp.div_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(id: &str, message: &str) -> CommitInfo {
+        CommitInfo {
+            id: id.repeat(40),
+            author_name: "Test".to_string(),
+            author_email: "test@example.com".to_string(),
+            time: 0,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn lists_breaking_changes_once() {
+        let commits = [
+            commit("a", "feat(api)!: drop v1\n\nBREAKING CHANGE: v1 endpoints are gone"),
+            commit("b", "feat: add v2"),
+            commit("c", "chore!: require rust 1.85"),
+        ];
+        let mut changelog = Changelog::from_commits("2.0.0", None, &commits, false);
+
+        assert_eq!(changelog.breaking.len(), 2);
+        assert_eq!(changelog.sections.len(), 1);
+        assert_eq!(changelog.sections[0].entries.len(), 1);
+        assert_eq!(changelog.sections[0].entries[0].subject, "add v2");
+        assert_eq!(changelog.subjects_mut().len(), 3);
+
+        let markdown = changelog.to_markdown();
+        assert_eq!(markdown.matches("v1 endpoints are gone").count(), 1);
+        assert_eq!(markdown.matches("drop v1").count(), 0);
+        assert!(
+            markdown
+                .contains("### BREAKING CHANGES\n\n- **api:** v1 endpoints are gone (aaaaaaa)\n")
+        );
+    }
+
+    #[test]
+    fn groups_by_type_then_scope() {
+        let commits = [
+            commit("a", "fix(ui): b"),
+            commit("b", "fix: a"),
+            commit("c", "fix(core): c"),
+            commit("d", "ci: e"),
+            commit("e", "not conventional"),
+        ];
+        let changelog =
+            Changelog::from_commits("1.0.1", Some("2024-01-01".to_string()), &commits, false);
+        let subjects: Vec<&str> = changelog.sections[0]
+            .entries
+            .iter()
+            .map(|e| e.subject.as_str())
+            .collect();
+        assert_eq!(subjects, ["a", "c", "b"]);
+        assert_eq!(changelog.sections.len(), 1);
+        assert!(
+            changelog
+                .to_markdown()
+                .starts_with("## [1.0.1] - 2024-01-01\n\n### Fixed\n")
+        );
+
+        let all = Changelog::from_commits("1.0.1", None, &commits, true);
+        assert_eq!(all.sections.last().unwrap().title, "Continuous Integration");
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(951_782_400), "2000-02-29");
+        assert_eq!(format_date(1_704_067_199), "2023-12-31");
+    }
+}
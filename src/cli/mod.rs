@@ -1,4 +1,11 @@
-use clap::Parser;
+use crate::ai::cache::parse_size;
+use crate::config::FlagOverride;
+use crate::git::DiffSource;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::io::{self, BufRead, Write};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
 
 /// A smart Git commit message generator.
 #[derive(Parser, Debug)]
@@ -19,4 +26,306 @@ pub struct Cli {
     /// Enable verbose output and developer debug logging.
     #[arg(short, long)]
     pub debug: bool,
+
+    /// Describe unstaged changes in the working tree instead of staged ones.
+    #[arg(long, group = "source")]
+    pub unstaged: bool,
+
+    /// Describe staged and unstaged changes together.
+    #[arg(short, long, group = "source")]
+    pub all: bool,
+
+    /// Describe the changes introduced by an existing commit.
+    #[arg(long, value_name = "COMMIT", group = "source")]
+    pub rev: Option<String>,
+
+    /// Describe what the commits in a range do together (e.g. `main..HEAD`).
+    #[arg(long, value_name = "A..B", group = "source")]
+    pub range: Option<String>,
+
+    /// Use this provider instead of the configured one.
+    #[arg(long, global = true)]
+    pub provider: Option<String>,
+
+    /// Use this model instead of the configured one.
+    #[arg(long, global = true)]
+    pub model: Option<String>,
+
+    /// Write generated text in this language (e.g. `es`, `de`, `ja`).
+    #[arg(long, value_name = "LANG", global = true)]
+    pub lang: Option<String>,
+
+    /// Wait for the whole response instead of showing it as it is generated.
+    #[arg(long, global = true)]
+    pub no_stream: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Regenerate the messages of existing commits and rewrite the history.
+    Reword(RewordArgs),
+    /// Generate one squash-merge message for every commit on the current branch.
+    SquashMsg(SquashMsgArgs),
+    /// Generate a pull request title and description for the current branch.
+    Pr(PrArgs),
+    /// Generate a changelog from the Conventional Commits in a range.
+    Changelog(ChangelogArgs),
+    /// Recommend the next semantic version from the commits since the last tag.
+    NextVersion(NextVersionArgs),
+    /// Inspect and clean up the response cache.
+    Cache(CacheArgs),
+    /// Show the tokens used per provider and model, and what they cost.
+    Usage(UsageArgs),
+    /// Create, inspect and change the configuration.
+    Config(ConfigArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RewordArgs {
+    /// The commits to reword (e.g. `main..HEAD`). The range must end at HEAD.
+    pub range: String,
+
+    /// Rewrite commits even if they were already pushed to the upstream.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Rewrite without asking for confirmation.
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+impl Cli {
+    /// Returns the diff source selected on the command line.
+    pub fn diff_source(&self) -> DiffSource {
+        if self.unstaged {
+            DiffSource::Unstaged
+        } else if self.all {
+            DiffSource::All
+        } else if let Some(rev) = &self.rev {
+            DiffSource::Rev(rev.clone())
+        } else if let Some(range) = &self.range {
+            DiffSource::Range(range.clone())
+        } else {
+            DiffSource::Staged
+        }
+    }
+
+    /// Returns the config values set by flags, the top configuration layer.
+    pub fn config_overrides(&self) -> Vec<FlagOverride> {
+        let mut overrides = Vec::new();
+        let mut push = |key, flag, value: &str| {
+            overrides.push(FlagOverride {
+                key,
+                flag,
+                value: value.into(),
+            })
+        };
+        if let Some(provider) = &self.provider {
+            push("provider", "--provider", provider);
+        }
+        if let Some(model) = &self.model {
+            push("model", "--model", model);
+        }
+        if let Some(lang) = &self.lang {
+            push("language", "--lang", lang);
+        }
+        if self.no_stream {
+            overrides.push(FlagOverride {
+                key: "stream",
+                flag: "--no-stream",
+                value: false.into(),
+            });
+        }
+        overrides
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct SquashMsgArgs {
+    /// The branch the current branch will be squash-merged into.
+    #[arg(long, default_value = "main")]
+    pub base: String,
+}
+
+#[derive(Args, Debug)]
+pub struct PrArgs {
+    /// The branch the pull request targets.
+    #[arg(long, default_value = "main")]
+    pub base: String,
+
+    /// A Markdown template for the description. Defaults to the repository's
+    /// `.github/pull_request_template.md`, if present.
+    #[arg(long, value_name = "FILE")]
+    pub template: Option<PathBuf>,
+
+    /// Write the pull request to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct ChangelogArgs {
+    /// The tag or commit the previous release was made from.
+    #[arg(long)]
+    pub from: String,
+
+    /// The last commit to include.
+    #[arg(long, default_value = "HEAD")]
+    pub to: String,
+
+    /// The version heading of the release. Defaults to `--to`, or `Unreleased`
+    /// when it is `HEAD`.
+    #[arg(long)]
+    pub release: Option<String>,
+
+    /// The output format.
+    #[arg(long, value_enum, default_value_t = ChangelogFormat::Markdown)]
+    pub format: ChangelogFormat,
+
+    /// Include commit types such as `chore`, `ci` and `test`, and types gitsc
+    /// does not know.
+    #[arg(long)]
+    pub all: bool,
+
+    /// Rewrite terse commit subjects into user-facing release notes with the
+    /// AI provider.
+    #[arg(long)]
+    pub ai: bool,
+
+    /// Prepend the release to a Markdown changelog file instead of printing it.
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "CHANGELOG.md")]
+    pub prepend: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ChangelogFormat {
+    Markdown,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct NextVersionArgs {
+    /// Produce a pre-release on this channel (e.g. `rc` gives `1.3.0-rc.1`).
+    #[arg(long, value_name = "CHANNEL")]
+    pub pre: Option<String>,
+
+    /// Write the version to the `Cargo.toml` and/or `package.json` at the
+    /// repository root.
+    #[arg(long)]
+    pub write: bool,
+
+    /// Only print the version, without the reasons.
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheCommand {
+    /// Show the number of entries, their size and the hit rate.
+    Stats,
+    /// List the cached entries, newest first.
+    List,
+    /// Show a cached entry. A unique prefix of the key is enough.
+    Show { key: String },
+    /// Remove every entry and reset the statistics.
+    Purge {
+        /// Purge without asking for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Remove expired entries, and old ones beyond the configured limits.
+    Prune {
+        /// Also remove entries older than this (e.g. `7d`, `12h`). Defaults to
+        /// `cache_max_age`.
+        #[arg(long, value_name = "AGE", value_parser = humantime::parse_duration)]
+        older_than: Option<Duration>,
+
+        /// Remove the oldest entries until the cache fits in this size (e.g.
+        /// `10MB`). Defaults to `cache_max_size`.
+        #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Share the cache with other machines over HTTP. Clients use
+    /// `cache.backend: http` with `cache.url` pointing here.
+    Serve {
+        /// The address to listen on. Other than loopback, it needs a token.
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        addr: SocketAddr,
+
+        /// Require this bearer token. Defaults to `cache.token`.
+        #[arg(long)]
+        token: Option<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct UsageArgs {
+    /// Only count calls made within this period (e.g. `7d`, `12h`).
+    #[arg(long, value_name = "AGE", default_value = "30d", value_parser = humantime::parse_duration)]
+    pub since: Duration,
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the configuration merged from every layer.
+    Show {
+        /// Show which layer each value came from.
+        #[arg(long)]
+        origin: bool,
+    },
+    /// Create the user config interactively: pick a provider and a model, and
+    /// test the connection.
+    Init {
+        /// Replace an existing config without asking.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the effective value of a setting, e.g. `cache.backend`.
+    Get { key: String },
+    /// Change a setting in the user config. Comments in the file are not kept.
+    Set { key: String, value: String },
+    /// Open the user config in `$VISUAL` or `$EDITOR` and check it afterwards.
+    Edit,
+    /// Print a JSON Schema of the config file for editor autocompletion.
+    Schema,
+}
+
+/// Asks a question on the terminal and returns the trimmed answer, or
+/// `default` if it is empty.
+pub fn ask(question: &str, default: &str) -> io::Result<String> {
+    if default.is_empty() {
+        print!("{}: ", question);
+    } else {
+        print!("{} [{}]: ", question, default);
+    }
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// Asks a yes/no question on the terminal. Anything but `y` or `yes` counts as
+/// no.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
@@ -0,0 +1,78 @@
+//! `gitsc changelog`: release notes for the Conventional Commits in a range.
+
+use gitsc::Generator;
+use gitsc::ai::prompt::ReleaseNotesPrompt;
+use gitsc::changelog::{Changelog, format_date, prepend_to_file};
+use gitsc::cli::{ChangelogArgs, ChangelogFormat};
+use gitsc::config::Config;
+use gitsc::error::{Error, Result};
+use gitsc::git::GitBackend;
+use log::{debug, error, info};
+
+/// Generates a changelog for the Conventional Commits between two revisions.
+pub async fn run(
+    config: &Config,
+    git: &dyn GitBackend,
+    generator: &Generator<'_>,
+    args: &ChangelogArgs,
+) -> Result<()> {
+    if args.prepend.is_some() && args.format != ChangelogFormat::Markdown {
+        return Err(Error::Config("--prepend only supports the markdown format.".to_string()));
+    }
+
+    let range = format!("{}..{}", args.from, args.to);
+    let commits = git.commits(&range)?;
+
+    let version = args
+        .release
+        .clone()
+        .unwrap_or_else(|| match args.to.as_str() {
+            "HEAD" => "Unreleased".to_string(),
+            to => to.to_string(),
+        });
+    let date = commits.last().map(|c| format_date(c.time));
+    let mut changelog = Changelog::from_commits(version, date, &commits, args.all);
+
+    if changelog.is_empty() {
+        info!("No Conventional Commits found in {}.", range);
+        return Ok(());
+    }
+
+    if args.ai {
+        let mut subjects = changelog.subjects_mut();
+        let prompt = ReleaseNotesPrompt::new(subjects.iter().map(|s| s.to_string()).collect())
+            .with_language(config.language.clone());
+        debug!("Calling AI provider to rewrite {} subjects...", subjects.len());
+        // The changelog is still useful with the original subjects.
+        match super::complete(config, generator, "changelog", &prompt.render()).await {
+            Ok(text) => match prompt.parse_response(&text) {
+                Some(notes) => {
+                    for (subject, note) in subjects.iter_mut().zip(notes) {
+                        **subject = note;
+                    }
+                },
+                None => {
+                    error!("AI response did not match the commit list, keeping original subjects.")
+                },
+            },
+            Err(e) => error!("Error rewriting release notes: {}", e),
+        }
+    }
+
+    let output = match args.format {
+        ChangelogFormat::Markdown => changelog.to_markdown(),
+        ChangelogFormat::Json => {
+            serde_json::to_string_pretty(&changelog).map_err(std::io::Error::from)?
+        },
+    };
+
+    match &args.prepend {
+        Some(path) => {
+            prepend_to_file(path, &output)?;
+            info!("Changelog written to {}", path.display());
+        },
+        None => println!("{}", output.trim_end()),
+    }
+
+    Ok(())
+}
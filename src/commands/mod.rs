@@ -0,0 +1,82 @@
+//! The subcommands that generate text with the provider. They return their
+//! errors, and `main` turns them into the exit code.
+
+use gitsc::Generator;
+use gitsc::ai::prompt::Prompt;
+use gitsc::ai::repository::CommitMessage;
+use gitsc::config::Config;
+use gitsc::error::Result;
+use std::io::{self, IsTerminal, Write};
+
+pub mod changelog;
+pub mod pr;
+pub mod reword;
+pub mod squash;
+
+/// Shows a response on stderr while it is generated, dimmed so it is not
+/// mistaken for the final output on stdout.
+#[derive(Default)]
+pub struct TokenPrinter {
+    started: bool,
+}
+
+impl TokenPrinter {
+    /// Streaming is on unless disabled, and only when someone is watching.
+    pub fn enabled(config: &Config) -> bool {
+        config.stream.unwrap_or(true) && io::stderr().is_terminal()
+    }
+
+    pub fn print(&mut self, token: &str) {
+        let mut stderr = io::stderr().lock();
+        if !self.started {
+            let _ = write!(stderr, "\x1b[2m");
+            self.started = true;
+        }
+        let _ = write!(stderr, "{}", token);
+        let _ = stderr.flush();
+    }
+
+    pub fn finish(self) {
+        if self.started {
+            eprintln!("\x1b[0m");
+        }
+    }
+}
+
+/// Sends a free-form prompt through the generator, showing the response as it
+/// is generated when streaming is enabled. `kind` labels the call in the
+/// cache and the usage log.
+async fn complete(
+    config: &Config,
+    generator: &Generator<'_>,
+    kind: &str,
+    prompt: &str,
+) -> Result<String> {
+    if !TokenPrinter::enabled(config) {
+        return generator.complete(kind, prompt).await;
+    }
+    let mut printer = TokenPrinter::default();
+    let result = generator
+        .complete_streaming(kind, prompt, &mut |token: &str| printer.print(token))
+        .await;
+    printer.finish();
+    result
+}
+
+/// Returns the commit message for a prompt, showing the response as it is
+/// generated when streaming is enabled.
+async fn generate_message(
+    config: &Config,
+    generator: &Generator<'_>,
+    prompt: &Prompt,
+) -> Result<CommitMessage> {
+    if !TokenPrinter::enabled(config) {
+        return generator.message(prompt).await.map(|(msg, _)| msg);
+    }
+    let mut printer = TokenPrinter::default();
+    let result = generator
+        .message_streaming(prompt, &mut |token: &str| printer.print(token))
+        .await;
+    printer.finish();
+    result.map(|(msg, _)| msg)
+}
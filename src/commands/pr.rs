@@ -0,0 +1,57 @@
+//! `gitsc pr`: a pull request title and description for the current branch.
+
+use gitsc::Generator;
+use gitsc::ai::prompt::PullRequestPrompt;
+use gitsc::analyzer;
+use gitsc::cli::PrArgs;
+use gitsc::config::Config;
+use gitsc::error::Result;
+use gitsc::git::{DiffSource, GitBackend, get_diff};
+use gitsc::pr::{PullRequest, find_template};
+use log::{debug, info};
+use std::fs;
+
+/// Generates a pull request title and description for the commits on the
+/// current branch since it diverged from the base branch.
+pub async fn run(
+    config: &Config,
+    git: &dyn GitBackend,
+    generator: &Generator<'_>,
+    args: &PrArgs,
+) -> Result<()> {
+    let range = format!("{}..HEAD", args.base);
+    let commits = git.commits(&range)?;
+    if commits.is_empty() {
+        info!("No commits found on the current branch since {}.", args.base);
+        return Ok(());
+    }
+
+    let template = match &args.template {
+        Some(path) => Some(fs::read_to_string(path)?),
+        None => find_template(&git.root()?),
+    };
+    if template.is_some() {
+        debug!("Using a pull request template.");
+    }
+
+    // Used when the model does not write a title.
+    let fallback_title = commits[commits.len() - 1].summary().to_string();
+    let diff = get_diff(git, &DiffSource::Range(range))?;
+    let prompt =
+        PullRequestPrompt::new(analyzer::analyze_diff(&diff, &config.smart_commit), commits)
+            .with_template(template)
+            .with_language(config.language.clone());
+
+    let text = super::complete(config, generator, "pr", &prompt.render()).await?;
+    let pr = PullRequest::parse(&text, &fallback_title);
+
+    match &args.output {
+        Some(path) => {
+            fs::write(path, pr.render())?;
+            info!("Pull request written to {}", path.display());
+        },
+        None => print!("{}", pr.render()),
+    }
+
+    Ok(())
+}
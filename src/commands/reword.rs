@@ -0,0 +1,83 @@
+//! `gitsc reword`: new messages for the commits in a range.
+
+use gitsc::Generator;
+use gitsc::cli::{RewordArgs, confirm};
+use gitsc::config::Config;
+use gitsc::error::{Error, Result};
+use gitsc::formatter::format_commit_message;
+use gitsc::git::{DiffSource, GitBackend, get_diff};
+use gitsc::reword::{RewordEntry, render_table, replace_subject};
+use gitsc::ticket;
+use log::{debug, error, info};
+use std::collections::HashMap;
+
+/// Regenerates the messages of the commits in a range and rewrites them after
+/// confirmation.
+pub async fn run(
+    config: &Config,
+    git: &dyn GitBackend,
+    generator: &Generator<'_>,
+    args: &RewordArgs,
+) -> Result<()> {
+    // Checked before any message is generated, as `reword` would only
+    // reject the range after all of them.
+    git.check_reword_range(&args.range)?;
+    let commits = git.commits(&args.range)?;
+    if commits.is_empty() {
+        info!("No commits found in range {}.", args.range);
+        return Ok(());
+    }
+
+    if !args.force {
+        for commit in &commits {
+            if git.is_published(&commit.id)? {
+                return Err(Error::Git(format!(
+                    "Commit {} was already pushed to the upstream. Use --force to reword it anyway.",
+                    commit.short_id()
+                )));
+            }
+        }
+    }
+
+    let mut entries = Vec::with_capacity(commits.len());
+    for commit in commits {
+        debug!("Generating message for commit {}", commit.short_id());
+        let diff = match get_diff(git, &DiffSource::Rev(commit.id.clone())) {
+            Ok(diff) => diff,
+            Err(Error::NoChanges(_)) => {
+                info!("Commit {} has no changes, keeping its message.", commit.short_id());
+                continue;
+            },
+            Err(e) => return Err(e),
+        };
+        let prompt = generator.prompt(&diff).await;
+        let msg = match super::generate_message(config, generator, &prompt).await {
+            Ok(msg) => msg,
+            Err(e) => {
+                error!("Could not generate a message for commit {}.", commit.short_id());
+                return Err(e);
+            },
+        };
+        // The branch of an old commit is unknown, so `{ticket}` is left empty.
+        let template = ticket::fill_template(&config.commit_format, &[]);
+        let subject = format_commit_message(&msg, &template);
+        let message = replace_subject(&commit.message, &subject);
+        entries.push(RewordEntry { commit, message });
+    }
+
+    print!("{}", render_table(&entries));
+
+    if !args.yes && !confirm("Rewrite these commits?")? {
+        info!("Aborted, history left unchanged.");
+        return Ok(());
+    }
+
+    let messages: HashMap<String, String> = entries
+        .into_iter()
+        .map(|entry| (entry.commit.id, entry.message))
+        .collect();
+    let new_head = git.reword(&args.range, &messages)?;
+    info!("Rewrote {} commit(s), HEAD is now {}.", messages.len(), new_head);
+
+    Ok(())
+}
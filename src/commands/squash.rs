@@ -0,0 +1,40 @@
+//! `gitsc squash-msg`: one message for the commits of a branch.
+
+use gitsc::Generator;
+use gitsc::cli::SquashMsgArgs;
+use gitsc::config::Config;
+use gitsc::error::Result;
+use gitsc::formatter::format_full_commit_message;
+use gitsc::git::{DiffSource, GitBackend, get_diff};
+use gitsc::squash::build_squash_message;
+use log::info;
+
+/// Generates a single squash-merge message for the commits on the current
+/// branch since it diverged from the base branch.
+pub async fn run(
+    config: &Config,
+    git: &dyn GitBackend,
+    generator: &Generator<'_>,
+    args: &SquashMsgArgs,
+) -> Result<()> {
+    let range = format!("{}..HEAD", args.base);
+    let commits = git.commits(&range)?;
+    if commits.is_empty() {
+        info!("No commits found on the current branch since {}.", args.base);
+        return Ok(());
+    }
+
+    let diff = get_diff(git, &DiffSource::Range(range))?;
+    let prompt = generator
+        .prompt(&diff)
+        .await
+        .with_commits(commits.clone())
+        .with_body(true);
+
+    let msg = super::generate_message(config, generator, &prompt).await?;
+    let msg = build_squash_message(msg, &commits);
+    let (msg, template) = generator.apply_tickets(msg)?;
+    println!("{}", format_full_commit_message(&msg, &template));
+
+    Ok(())
+}
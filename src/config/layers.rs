@@ -0,0 +1,668 @@
+//! Merges the configuration from several layers, each overriding the ones
+//! before it, and remembers which layer every value came from.
+
+use log::warn;
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::validate::{Problem, Severity, did_you_mean};
+use super::{Config, schema};
+use crate::error::{Error, Result};
+
+/// The configuration layers, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    /// Built into gitsc.
+    Default,
+    /// `/etc/gitsc/config.yml`, shared by every user of the machine.
+    System,
+    /// `~/.config/gitsc/config.yml`.
+    User,
+    /// `.gitsc.yml` at the root of the repository, for team conventions.
+    Repo,
+    /// `GITSC_*` environment variables.
+    Env,
+    /// Command line flags such as `--model`.
+    Cli,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Layer::Default => "default",
+            Layer::System => "system",
+            Layer::User => "user",
+            Layer::Repo => "repo",
+            Layer::Env => "env",
+            Layer::Cli => "cli",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Where a value came from: the layer and, except for defaults, the file,
+/// variable or flag that set it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub layer: Layer,
+    pub source: Option<String>,
+}
+
+impl Origin {
+    pub fn new(layer: Layer, source: impl Into<String>) -> Self {
+        Self {
+            layer,
+            source: Some(source.into()),
+        }
+    }
+
+    fn file(layer: Layer, path: &Path) -> Self {
+        Self::new(layer, path.display().to_string())
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{} ({})", self.layer, source),
+            None => write!(f, "{}", self.layer),
+        }
+    }
+}
+
+/// A value set on the command line.
+#[derive(Debug, Clone)]
+pub struct FlagOverride {
+    /// The dotted config key, e.g. `model`.
+    pub key: &'static str,
+    /// The flag that set it, e.g. `--model`.
+    pub flag: &'static str,
+    pub value: Value,
+}
+
+/// Top-level keys a repository's `.gitsc.yml` may not set, since a cloned
+/// repository could otherwise run commands or send diffs and credentials to
+/// servers of its choosing.
+const REPO_FORBIDDEN_KEYS: &[&str] = &[
+    "api_key",
+    "cache",
+    "cache_path",
+    "exec",
+    "log",
+    "ollama_url",
+    "openai_url",
+    "redis_url",
+    "usage",
+];
+
+/// The merged configuration tree, before it is turned into a `Config`.
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    value: Value,
+    /// The origin of every leaf value, keyed by its dotted path.
+    origins: BTreeMap<String, Origin>,
+    /// The files merged so far, in order.
+    files: Vec<PathBuf>,
+}
+
+impl Default for LayeredConfig {
+    fn default() -> Self {
+        Self {
+            value: Value::Mapping(Mapping::new()),
+            origins: BTreeMap::new(),
+            files: Vec::new(),
+        }
+    }
+}
+
+impl LayeredConfig {
+    /// Merges a layer over the current tree. Mappings are merged key by key;
+    /// any other value, lists included, replaces what was there.
+    pub fn merge(&mut self, layer: Value, origin: &Origin) {
+        merge_into(&mut self.value, layer, "", origin, &mut self.origins);
+    }
+
+    /// Sets a single value by its dotted path, e.g. `log.path`.
+    pub fn set(&mut self, key: &str, value: Value, origin: &Origin) {
+        let layer = key.rsplit('.').fold(value, |value, part| {
+            let mut mapping = Mapping::new();
+            mapping.insert(Value::String(part.to_string()), value);
+            Value::Mapping(mapping)
+        });
+        self.merge(layer, origin);
+    }
+
+    /// The merged tree.
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Finds a merged value by its dotted path, e.g. `log.path`.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        key.split('.')
+            .try_fold(&self.value, |value, part| value.get(part))
+    }
+
+    /// The config files that were found and merged, lowest precedence first.
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// The origin of every leaf value, keyed by its dotted path.
+    pub fn origins(&self) -> &BTreeMap<String, Origin> {
+        &self.origins
+    }
+
+    /// Turns the merged tree into a `Config` and validates it. Warnings are
+    /// logged, and every error is reported with the file and line that set
+    /// the value and a suggestion.
+    pub fn build(&self) -> Result<Config> {
+        let config: Config = serde_path_to_error::deserialize(self.value.clone())
+            .map_err(|e| self.parse_error(e))?;
+
+        let mut errors = Vec::new();
+        for problem in self.unknown_keys().into_iter().chain(config.validate()) {
+            let report = format!(
+                "{}: {}\n    {}",
+                self.locate(&problem.key),
+                problem.message,
+                problem.suggestion
+            );
+            match problem.severity {
+                Severity::Warning => warn!("{}", report),
+                Severity::Error => errors.push(report),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(Error::Config(format!(
+                "Invalid configuration:\n  {}",
+                errors.join("\n  ")
+            )));
+        }
+        Ok(config)
+    }
+
+    /// Names where the value that failed to parse was set, with a line
+    /// number for files.
+    fn parse_error(&self, error: serde_path_to_error::Error<serde_yaml::Error>) -> Error {
+        // `scope.rules[0].path` was set as part of `scope.rules`.
+        let path = error.path().to_string();
+        let key = path.split('[').next().unwrap_or_default();
+        match key {
+            "" | "." => Error::Config(format!("Failed to parse config: {}", error.inner())),
+            key => Error::Config(format!("{}: {}", self.locate(key), error.inner())),
+        }
+    }
+
+    /// Warns about top-level keys `Config` does not have, which are most
+    /// likely typos. Variables are skipped: other `GITSC_*` variables may
+    /// exist for unrelated reasons.
+    fn unknown_keys(&self) -> Vec<Problem> {
+        let known = match serde_yaml::to_value(Config::default()) {
+            Ok(Value::Mapping(mapping)) => mapping,
+            _ => return Vec::new(),
+        };
+        let known: Vec<&str> = known.keys().filter_map(Value::as_str).collect();
+        let Value::Mapping(mapping) = &self.value else {
+            return Vec::new();
+        };
+        mapping
+            .keys()
+            .filter_map(Value::as_str)
+            .filter(|key| !known.contains(key))
+            .filter(|key| {
+                self.origin(key)
+                    .is_none_or(|origin| origin.layer != Layer::Env)
+            })
+            .map(|key| {
+                let suggestion = match did_you_mean(key, &known) {
+                    Some(candidate) => format!("Did you mean `{}`?", candidate),
+                    None => "Remove it, or check `gitsc config schema` for the known settings."
+                        .to_string(),
+                };
+                Problem::warning(key, format!("`{}` is not a known setting.", key), suggestion)
+            })
+            .collect()
+    }
+
+    /// The origin of a key, or of the nearest key above or below it.
+    fn origin(&self, key: &str) -> Option<&Origin> {
+        if let Some(origin) = self.origins.get(key) {
+            return Some(origin);
+        }
+        let prefix = format!("{}.", key);
+        if let Some((_, origin)) = self.origins.iter().find(|(k, _)| k.starts_with(&prefix)) {
+            return Some(origin);
+        }
+        let mut parent = key;
+        while let Some((rest, _)) = parent.rsplit_once('.') {
+            if let Some(origin) = self.origins.get(rest) {
+                return Some(origin);
+            }
+            parent = rest;
+        }
+        None
+    }
+
+    /// Describes where a key was set: `file:line`, a variable or a flag.
+    pub fn locate(&self, key: &str) -> String {
+        let Some(origin) = self.origin(key) else {
+            return format!("`{}`", key);
+        };
+        match (origin.layer, &origin.source) {
+            (Layer::System | Layer::User | Layer::Repo, Some(path)) => {
+                let line = fs::read_to_string(path)
+                    .ok()
+                    .and_then(|content| find_line(&content, key));
+                match line {
+                    Some(line) => format!("{}:{}", path, line),
+                    None => path.clone(),
+                }
+            },
+            (_, Some(source)) => source.clone(),
+            (_, None) => format!("`{}` (default)", key),
+        }
+    }
+}
+
+/// Returns the 1-based line of a dotted key in a block-style YAML document,
+/// or of its deepest parent that could be found.
+fn find_line(content: &str, key: &str) -> Option<usize> {
+    let mut parts = key.split('.');
+    let mut part = parts.next()?;
+    let mut found = None;
+    let mut parent_indent: Option<usize> = None;
+    let mut child_indent: Option<usize> = Some(0);
+    for (number, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        if parent_indent.is_some_and(|parent| indent <= parent) {
+            break;
+        }
+        if *child_indent.get_or_insert(indent) != indent {
+            continue;
+        }
+        let name = trimmed
+            .split_once(':')
+            .map(|(name, _)| name.trim().trim_matches(['"', '\'']));
+        if name == Some(part) {
+            found = Some(number + 1);
+            match parts.next() {
+                Some(next) => part = next,
+                None => break,
+            }
+            parent_indent = Some(indent);
+            child_indent = None;
+        }
+    }
+    found
+}
+
+fn merge_into(
+    target: &mut Value,
+    layer: Value,
+    path: &str,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) {
+    match (target, layer) {
+        (Value::Mapping(target), Value::Mapping(layer)) => {
+            for (key, value) in layer {
+                let Some(name) = key.as_str().map(str::to_string) else {
+                    continue;
+                };
+                let child_path = if path.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", path, name)
+                };
+                match target.get_mut(&key) {
+                    Some(existing) => merge_into(existing, value, &child_path, origin, origins),
+                    None => {
+                        record_origins(&value, &child_path, origin, origins);
+                        target.insert(key, value);
+                    },
+                }
+            }
+        },
+        (target, layer) => {
+            // A replaced subtree takes its origins with it.
+            let prefix = format!("{}.", path);
+            origins.retain(|key, _| key != path && !key.starts_with(&prefix));
+            record_origins(&layer, path, origin, origins);
+            *target = layer;
+        },
+    }
+}
+
+fn record_origins(
+    value: &Value,
+    path: &str,
+    origin: &Origin,
+    origins: &mut BTreeMap<String, Origin>,
+) {
+    match value {
+        Value::Mapping(mapping) if !mapping.is_empty() => {
+            for (key, value) in mapping {
+                if let Some(name) = key.as_str() {
+                    record_origins(value, &format!("{}.{}", path, name), origin, origins);
+                }
+            }
+        },
+        _ => {
+            origins.insert(path.to_string(), origin.clone());
+        },
+    }
+}
+
+/// The default value of every field that has one, as a layer.
+fn defaults() -> Result<Value> {
+    let mut value = serde_yaml::to_value(Config::default())
+        .map_err(|e| Error::Config(format!("Invalid built-in config: {}", e)))?;
+    strip_nulls(&mut value);
+    Ok(value)
+}
+
+/// Removes the unset optional fields, which have no default to show.
+fn strip_nulls(value: &mut Value) {
+    if let Value::Mapping(mapping) = value {
+        mapping.retain(|_, value| !value.is_null());
+        mapping.values_mut().for_each(strip_nulls);
+    }
+}
+
+/// Reads the value of `key` given as text, from a variable or the command
+/// line, with the type the schema expects: booleans, numbers, lists and
+/// mappings are read as YAML, so `true` and `150` keep their types. Anything
+/// else, such as `{type}: {message}` for `commit_format` or `12345` for
+/// `api_key`, stays the string it was given.
+pub fn parse_value(key: &str, raw: &str) -> Value {
+    let string = || Value::String(raw.to_string());
+    match schema::key_type(key).as_deref() {
+        Some("boolean" | "integer" | "number") => match serde_yaml::from_str(raw) {
+            // Left for validation to report as the wrong type.
+            Ok(Value::Mapping(_) | Value::Sequence(_)) | Err(_) => string(),
+            Ok(value) => value,
+        },
+        Some("array" | "object") => serde_yaml::from_str(raw).unwrap_or_else(|_| string()),
+        _ => string(),
+    }
+}
+
+/// Sets a value in a YAML tree by its dotted path, creating the mappings on
+/// the way and replacing anything that is not one.
+pub fn set_key(tree: &mut Value, key: &str, value: Value) {
+    let (parents, last) = match key.rsplit_once('.') {
+        Some((parents, last)) => (Some(parents), last),
+        None => (None, key),
+    };
+    let mut node = tree;
+    for part in parents.into_iter().flat_map(|parents| parents.split('.')) {
+        if !node.is_mapping() {
+            *node = Value::Mapping(Mapping::new());
+        }
+        let mapping = node.as_mapping_mut().expect("just made a mapping");
+        node = mapping
+            .entry(Value::String(part.to_string()))
+            .or_insert(Value::Null);
+    }
+    if !node.is_mapping() {
+        *node = Value::Mapping(Mapping::new());
+    }
+    node.as_mapping_mut()
+        .expect("just made a mapping")
+        .insert(Value::String(last.to_string()), value);
+}
+
+/// Reads a YAML layer, or `None` if the file does not exist.
+fn read_layer(path: &Path) -> Result<Option<Value>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let value: Value = serde_yaml::from_str(&content)
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+    // An empty file is an empty layer.
+    Ok(Some(match value {
+        Value::Null => Value::Mapping(Mapping::new()),
+        value => value,
+    }))
+}
+
+/// Returns the `.gitsc.yml` at the root of the repository containing the
+/// current directory, if there is one.
+pub fn repo_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let root = cwd.ancestors().find(|dir| dir.join(".git").exists())?;
+    Some(root.join(".gitsc.yml"))
+}
+
+/// Collects the `GITSC_*` environment variables as config values. A double
+/// underscore separates nested keys, so `GITSC_LOG__PATH` sets `log.path`.
+/// Values are typed as `parse_value` describes.
+fn env_overrides() -> Vec<(String, String, Value)> {
+    let mut overrides: Vec<_> = std::env::vars()
+        .filter_map(|(name, raw)| {
+            let key = name
+                .strip_prefix("GITSC_")?
+                .to_lowercase()
+                .replace("__", ".");
+            let value = parse_value(&key, &raw);
+            Some((key, name, value))
+        })
+        .collect();
+    overrides.sort_by(|a, b| a.1.cmp(&b.1));
+    overrides
+}
+
+/// Loads every layer in order: the defaults, the system config, the user
+/// config, the repository's `.gitsc.yml`, `GITSC_*` variables and `flags`.
+pub fn load_layers(user_path: &Path, flags: &[FlagOverride]) -> Result<LayeredConfig> {
+    let mut config = LayeredConfig::default();
+    config.merge(
+        defaults()?,
+        &Origin {
+            layer: Layer::Default,
+            source: None,
+        },
+    );
+
+    let system_path = Path::new(super::SYSTEM_CONFIG_PATH);
+    if let Some(layer) = read_layer(system_path)? {
+        config.merge(layer, &Origin::file(Layer::System, system_path));
+        config.files.push(system_path.to_path_buf());
+    }
+
+    if let Some(layer) = read_layer(user_path)? {
+        config.merge(layer, &Origin::file(Layer::User, user_path));
+        config.files.push(user_path.to_path_buf());
+    }
+
+    if let Some(repo_path) = repo_config_path()
+        && let Some(mut layer) = read_layer(&repo_path)?
+    {
+        if let Value::Mapping(mapping) = &mut layer {
+            for key in REPO_FORBIDDEN_KEYS {
+                if mapping.remove(*key).is_some() {
+                    warn!(
+                        "Ignoring `{}` in {}: only the user and system configs may set it.",
+                        key,
+                        repo_path.display()
+                    );
+                }
+            }
+        }
+        config.merge(layer, &Origin::file(Layer::Repo, &repo_path));
+        config.files.push(repo_path);
+    }
+
+    for (key, name, value) in env_overrides() {
+        config.set(&key, value, &Origin::new(Layer::Env, name));
+    }
+
+    for flag in flags {
+        config.set(flag.key, flag.value.clone(), &Origin::new(Layer::Cli, flag.flag));
+    }
+
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::testing::TempRepo;
+
+    #[test]
+    fn parses_values_with_the_type_of_their_key() {
+        let cases = [
+            ("commit_format", "{type}: {message}", Value::from("{type}: {message}")),
+            ("api_key", "12345", Value::from("12345")),
+            ("model", "3.5", Value::from("3.5")),
+            ("language", "no", Value::from("no")),
+            ("stream", "false", Value::from(false)),
+            ("cache_enabled", "yes", Value::from("yes")),
+            ("smart_commit.line_threshold", "150", Value::from(150)),
+            ("examples.count", "[1]", Value::from("[1]")),
+            ("usage.prices.gpt-4o.input", "2.5", Value::from(2.5)),
+            ("exec.args", "[-c, echo]", Value::from(vec!["-c", "echo"])),
+            ("unknown", "true", Value::from("true")),
+        ];
+        for (key, raw, expected) in cases {
+            assert_eq!(parse_value(key, raw), expected, "{}={}", key, raw);
+        }
+    }
+
+    #[test]
+    fn loads_string_values_that_look_like_yaml() {
+        let mut config = LayeredConfig::default();
+        let origin = Origin {
+            layer: Layer::Default,
+            source: None,
+        };
+        config.merge(defaults().unwrap(), &origin);
+        for (key, raw) in [("commit_format", "{type}: {message}"), ("api_key", "12345")] {
+            config.set(key, parse_value(key, raw), &Origin::new(Layer::Env, key));
+        }
+        let config = config.build().unwrap();
+        assert_eq!(config.commit_format, "{type}: {message}");
+        assert_eq!(config.api_key.as_deref(), Some("12345"));
+    }
+
+    #[test]
+    fn finds_the_line_of_a_key() {
+        let content = "\
+# gitsc
+provider: ollama
+cache:
+  # shared
+  backend: http
+  url: http://cache
+log:
+  path: gitsc.log
+\"ticket\":
+  placement: prefix
+";
+        let cases = [
+            ("provider", Some(2)),
+            ("cache", Some(3)),
+            ("cache.backend", Some(5)),
+            ("cache.url", Some(6)),
+            ("log.path", Some(8)),
+            ("ticket.placement", Some(10)),
+            // The deepest parent that exists.
+            ("cache.token", Some(3)),
+            ("path", None),
+            ("model", None),
+        ];
+        for (key, expected) in cases {
+            assert_eq!(find_line(content, key), expected, "{}", key);
+        }
+    }
+
+    #[test]
+    fn warns_about_unknown_keys_outside_the_environment() {
+        let mut config = LayeredConfig::default();
+        let user = Origin::file(Layer::User, Path::new("config.yml"));
+        config.merge(
+            serde_yaml::from_str("provder: ollama\nmodel: llama3\nfrobnicate: 1\n").unwrap(),
+            &user,
+        );
+        config.set(
+            "gitsc_unrelated",
+            Value::from("x"),
+            &Origin::new(Layer::Env, "GITSC_GITSC_UNRELATED"),
+        );
+
+        let problems: Vec<(String, String)> = config
+            .unknown_keys()
+            .into_iter()
+            .map(|p| (p.key, p.suggestion))
+            .collect();
+        assert_eq!(
+            problems,
+            [
+                ("provder".to_string(), "Did you mean `provider`?".to_string()),
+                (
+                    "frobnicate".to_string(),
+                    "Remove it, or check `gitsc config schema` for the known settings.".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_where_a_value_that_fails_to_parse_was_set() {
+        let dir = TempRepo::new();
+        let user = dir.path.join("config.yml");
+        let repo = dir.path.join(".gitsc.yml");
+        dir.write("config.yml", "examples:\n  count: 3\n");
+        dir.write(".gitsc.yml", "model: llama3\nexamples:\n  depth: 50\n  count: many\n");
+
+        let mut config = LayeredConfig::default();
+        for (layer, path) in [(Layer::User, &user), (Layer::Repo, &repo)] {
+            let content = fs::read_to_string(path).unwrap();
+            config.merge(serde_yaml::from_str(&content).unwrap(), &Origin::file(layer, path));
+        }
+        let error = config.build().unwrap_err().to_string();
+        assert!(error.contains(&format!("{}:4: invalid type", repo.display())), "{}", error);
+
+        config.set(
+            "examples.count",
+            Value::from(3),
+            &Origin::new(Layer::Env, "GITSC_EXAMPLES__COUNT"),
+        );
+        config.set("stream", Value::from("sometimes"), &Origin::new(Layer::Env, "GITSC_STREAM"));
+        let error = config.build().unwrap_err().to_string();
+        assert!(error.contains("GITSC_STREAM: invalid type"), "{}", error);
+    }
+
+    /// Mirrors `gitsc config set`, which edits the user config this way.
+    #[test]
+    fn sets_values_as_config_set_does() {
+        let mut tree: Value = serde_yaml::from_str("provider: openai\nstream: true\n").unwrap();
+        for (key, raw) in [
+            ("commit_format", "{type}: {message}"),
+            ("model", "3.5"),
+            ("api_key", "12345"),
+            ("stream", "false"),
+            ("smart_commit.line_threshold", "300"),
+        ] {
+            set_key(&mut tree, key, parse_value(key, raw));
+        }
+        let config: Config = serde_yaml::from_value(tree.clone()).unwrap();
+        assert_eq!(config.commit_format, "{type}: {message}");
+        assert_eq!(config.model, "3.5");
+        assert_eq!(config.api_key.as_deref(), Some("12345"));
+        assert_eq!(config.stream, Some(false));
+        assert_eq!(config.smart_commit.line_threshold, 300);
+
+        set_key(&mut tree, "stream", parse_value("stream", "sometimes"));
+        assert!(serde_yaml::from_value::<Config>(tree).is_err());
+    }
+}
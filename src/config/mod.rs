@@ -1,70 +1,324 @@
 use crate::error::{Error, Result};
-use serde::Deserialize;
-use std::fs;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
-#[derive(Debug, Deserialize)]
+pub mod layers;
+pub mod schema;
+pub mod validate;
+
+pub use layers::{
+    FlagOverride, Layer, LayeredConfig, Origin, load_layers, parse_value, repo_config_path, set_key,
+};
+pub use schema::json_schema;
+pub use validate::{Problem, Severity};
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
     pub provider: String,
     pub model: String,
     pub cache_enabled: Option<bool>,
+    pub cache: Option<CacheConfig>,
     pub cache_path: Option<PathBuf>,
+    /// How long cached values stay valid, e.g. `1h` (the default) or `never`.
+    pub cache_ttl: Option<String>,
+    /// Evict the oldest entries beyond this size, e.g. `50MB`.
+    pub cache_max_size: Option<String>,
+    /// Evict entries stored longer ago than this, e.g. `30d`.
+    pub cache_max_age: Option<String>,
     pub ollama_url: Option<String>,
+    /// The base URL of an OpenAI-compatible API, e.g. `http://localhost:8000/v1`.
+    pub openai_url: Option<String>,
     pub api_key: Option<String>,
+    pub redis_url: Option<String>,
+    /// The command the `exec` provider runs.
+    pub exec: Option<ExecConfig>,
+    pub git_backend: Option<GitBackendKind>,
+    pub scope: Option<ScopeConfig>,
+    pub ticket: Option<TicketConfig>,
+    pub examples: Option<ExamplesConfig>,
+    /// The language generated messages are written in, e.g. `es`.
+    pub language: Option<String>,
+    /// Describe the diff with the `heuristic` provider when the configured one
+    /// cannot be reached or fails to answer. Off by default.
+    pub heuristic_fallback: Option<bool>,
+    /// Show responses as they are generated when stderr is a terminal. On by
+    /// default.
+    pub stream: Option<bool>,
+    /// Where token usage is recorded and what each model costs.
+    pub usage: Option<UsageConfig>,
     pub commit_format: String,
     pub log: LogConfig,
     pub smart_commit: SmartCommitConfig,
 }
 
-#[derive(Debug, Deserialize)]
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            provider: "gemini".to_string(),
+            model: "gemini-2.5-flash".to_string(),
+            cache_enabled: None,
+            cache: None,
+            cache_path: Some(PathBuf::from(DEFAULT_CACHE_PATH)),
+            cache_ttl: None,
+            cache_max_size: None,
+            cache_max_age: None,
+            ollama_url: None,
+            openai_url: None,
+            api_key: None,
+            redis_url: None,
+            exec: None,
+            git_backend: None,
+            scope: None,
+            ticket: None,
+            examples: None,
+            language: None,
+            heuristic_fallback: None,
+            stream: None,
+            usage: None,
+            commit_format: DEFAULT_COMMIT_FORMAT.to_string(),
+            log: LogConfig::default(),
+            smart_commit: SmartCommitConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LogConfig {
     pub path: PathBuf,
     pub format: LogFormat,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("/tmp/gitsc.log"),
+            format: LogFormat::Nmap,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum LogFormat {
     Nmap,
     Json,
 }
 
-#[derive(Debug, Deserialize)]
+/// Selects where generated messages are cached.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CacheConfig {
+    pub backend: Option<CacheBackendKind>,
+    /// The base URL of the `http` backend, e.g. `http://cache.internal:7878`.
+    pub url: Option<String>,
+    /// The bearer token sent to the `http` backend and required by
+    /// `gitsc cache serve`.
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackendKind {
+    /// A database file at `cache_path`.
+    Sqlite,
+    /// A Redis server at `redis_url`. Needs the `redis` feature.
+    Redis,
+    /// A map that lives as long as the process.
+    Memory,
+    /// A key-value HTTP endpoint at `cache.url`, such as `gitsc cache serve`.
+    Http,
+}
+
+/// How gitsc reads the repository.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum GitBackendKind {
+    /// Use libgit2 when available, otherwise the `git` binary.
+    #[default]
+    Auto,
+    Libgit2,
+    Cli,
+}
+
+/// Controls how the commit scope is inferred from the changed paths.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScopeConfig {
+    /// Infer the scope from the repository layout and pass it to the model.
+    pub infer: bool,
+    /// Replace the scope chosen by the model with the inferred one.
+    #[serde(rename = "override")]
+    pub override_model: bool,
+    /// Path globs mapped to scope names, checked in order before the layout.
+    pub rules: Vec<ScopeRule>,
+}
+
+impl Default for ScopeConfig {
+    fn default() -> Self {
+        Self {
+            infer: true,
+            override_model: false,
+            rules: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScopeRule {
+    pub path: String,
+    pub scope: String,
+}
+
+/// Controls how issue keys found in the branch name are added to messages.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TicketConfig {
+    /// Regexes matched against the branch name. A capture group, if present,
+    /// selects the key.
+    pub patterns: Vec<String>,
+    pub placement: TicketPlacement,
+    /// The trailer token used with the `footer` placement.
+    pub footer_token: String,
+}
+
+impl Default for TicketConfig {
+    fn default() -> Self {
+        Self {
+            patterns: crate::ticket::DEFAULT_PATTERNS
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
+            placement: TicketPlacement::Footer,
+            footer_token: "Refs".to_string(),
+        }
+    }
+}
+
+/// Where issue keys are added to the commit message.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TicketPlacement {
+    /// As a trailer, e.g. `Refs: PROJ-1234`.
+    #[default]
+    Footer,
+    /// In front of the subject, e.g. `feat: PROJ-1234 add login`.
+    Prefix,
+    /// Only through the `{ticket}` placeholder of `commit_format`.
+    None,
+}
+
+/// Controls which commits from the repository history are shown to the model
+/// as style examples.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExamplesConfig {
+    /// How many examples to include. `0` disables them.
+    pub count: usize,
+    /// How many recent commits to sample from.
+    pub depth: usize,
+    /// Only use commits by the configured `user.email`.
+    pub same_author: bool,
+    /// Only use commits that touched the files or directories being changed.
+    pub same_paths: bool,
+    /// Only use commits whose message is a valid Conventional Commit.
+    pub conventional_only: bool,
+}
+
+impl Default for ExamplesConfig {
+    fn default() -> Self {
+        Self {
+            count: 5,
+            depth: 200,
+            same_author: false,
+            same_paths: false,
+            conventional_only: true,
+        }
+    }
+}
+
+/// An external command that generates messages, for the `exec` provider.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExecConfig {
+    /// The program to run, looked up in `PATH`.
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// How long to wait for the command, e.g. `30s`. Defaults to one minute.
+    pub timeout: Option<String>,
+}
+
+/// Controls the token usage log shown by `gitsc usage`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct UsageConfig {
+    /// Record the tokens of every provider call. On by default.
+    pub enabled: bool,
+    /// The database file. Defaults to `~/.cache/gitsc/usage.db`.
+    pub path: Option<PathBuf>,
+    /// Prices keyed by model name or glob, e.g. `gpt-4o*`.
+    pub prices: BTreeMap<String, Price>,
+}
+
+impl Default for UsageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            path: None,
+            prices: BTreeMap::new(),
+        }
+    }
+}
+
+/// The price of a model in US dollars per million tokens.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Price {
+    pub input: f64,
+    pub output: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SmartCommitConfig {
     pub line_threshold: u32,
 }
 
-/// Loads the configuration. In debug builds, it loads from the current
-/// directory.
-pub fn load_config() -> Result<Config> {
+impl Default for SmartCommitConfig {
+    fn default() -> Self {
+        Self {
+            line_threshold: 150,
+        }
+    }
+}
+
+/// The machine-wide config, read before the user's.
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/gitsc/config.yml";
+
+/// Where the SQLite cache lives unless `cache_path` is set.
+pub const DEFAULT_CACHE_PATH: &str = "~/.cache/gitsc/cache.db";
+
+/// The header template used unless `commit_format` is set.
+pub const DEFAULT_COMMIT_FORMAT: &str = "{type}({scope}): {message}";
+
+/// Returns the path of the user config, `~/.config/gitsc/config.yml` on
+/// Linux.
+pub fn user_config_path() -> Result<PathBuf> {
     let config_dir = dirs::config_dir()
         .ok_or_else(|| Error::Config("Could not find config directory".to_string()))?
         .join("gitsc");
+    Ok(config_dir.join("config.yml"))
+}
 
-    fs::create_dir_all(&config_dir)?;
-
-    let config_path = config_dir.join("config.yml");
-
-    if !config_path.exists() {
-        const DEFAULT_CONFIG_CONTENT: &str = r#"
-provider: gemini
-model: "gemini-2.5-flash"
-cache_enabled: true
-cache_path: "~/.cache/gitsc/cache.db"
-commit_format: "{type}({scope}): {message}"
-log:
-  path: "/tmp/gitsc.log"
-  format: "nmap"
-smart_commit:
-  line_threshold: 150
-"#;
-        fs::write(&config_path, DEFAULT_CONFIG_CONTENT.trim())?;
-    }
-
-    let config_content = fs::read_to_string(&config_path)?;
-
-    let config: Config = serde_yaml::from_str(&config_content)
-        .map_err(|e| Error::Config(format!("Failed to parse config file: {}", e)))?;
+/// Loads the configuration from every layer.
+pub fn load_config() -> Result<Config> {
+    load_layered(&[])?.build()
+}
 
-    Ok(config)
+/// Loads every configuration layer, with `flags` from the command line on
+/// top, keeping track of where each value came from. A missing user config
+/// is not an error: `gitsc config init` creates one.
+pub fn load_layered(flags: &[FlagOverride]) -> Result<LayeredConfig> {
+    load_layers(&user_config_path()?, flags)
 }
@@ -0,0 +1,287 @@
+//! A JSON Schema of `config.yml`, for editor autocompletion and checks, e.g.
+//! with a `# yaml-language-server: $schema=...` comment.
+
+use serde_json::{Value, json};
+
+use super::validate::PROVIDERS;
+use super::{
+    Config, DEFAULT_CACHE_PATH, DEFAULT_COMMIT_FORMAT, ExamplesConfig, ScopeConfig, TicketConfig,
+};
+use crate::ai::providers::ollama::DEFAULT_OLLAMA_URL;
+use crate::ai::providers::openai::DEFAULT_OPENAI_URL;
+
+fn string(description: &str) -> Value {
+    json!({ "type": "string", "description": description })
+}
+
+fn boolean(description: &str, default: bool) -> Value {
+    json!({ "type": "boolean", "description": description, "default": default })
+}
+
+fn integer(description: &str, default: impl Into<Value>) -> Value {
+    json!({ "type": "integer", "minimum": 0, "description": description, "default": default.into() })
+}
+
+fn one_of(description: &str, values: &[&str], default: Option<&str>) -> Value {
+    let mut schema = json!({ "type": "string", "enum": values, "description": description });
+    if let Some(default) = default {
+        schema["default"] = json!(default);
+    }
+    schema
+}
+
+fn object(description: &str, properties: Value) -> Value {
+    json!({
+        "type": "object",
+        "description": description,
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// Returns the schema of every setting `Config` reads.
+pub fn json_schema() -> Value {
+    let defaults = Config::default();
+    let scope = ScopeConfig::default();
+    let ticket = TicketConfig::default();
+    let examples = ExamplesConfig::default();
+
+    let properties = json!({
+        "provider": one_of("The provider that generates messages.", PROVIDERS, Some(&defaults.provider)),
+        "model": {
+            "type": "string",
+            "description": "The model to use, e.g. `gemini-2.5-flash` or `llama3`.",
+            "default": defaults.model,
+        },
+        "ollama_url": {
+            "type": "string",
+            "description": "The address of the Ollama server.",
+            "default": DEFAULT_OLLAMA_URL,
+        },
+        "openai_url": {
+            "type": "string",
+            "description": "The base URL of an OpenAI-compatible API.",
+            "default": DEFAULT_OPENAI_URL,
+        },
+        "api_key": string("The key of the OpenAI-compatible API. Defaults to `OPENAI_API_KEY`."),
+        "exec": {
+            "type": "object",
+            "description": "The command the `exec` provider runs.",
+            "properties": {
+                "command": string("The program to run, looked up in `PATH`."),
+                "args": { "type": "array", "items": { "type": "string" }, "default": [] },
+                "timeout": string("How long to wait for the command, e.g. `30s`. Defaults to one minute."),
+            },
+            "required": ["command"],
+            "additionalProperties": false,
+        },
+        "heuristic_fallback": boolean("Describe the diff with the `heuristic` provider when the configured one cannot be reached or fails to answer.", false),
+        "stream": boolean("Show responses as they are generated when stderr is a terminal.", true),
+        "language": string("The language generated messages are written in, e.g. `es`."),
+        "commit_format": {
+            "type": "string",
+            "description": "The header template. Placeholders: {type}, {scope}, {message}, {breaking} and {ticket}.",
+            "default": DEFAULT_COMMIT_FORMAT,
+        },
+        "cache_enabled": boolean("Cache generated messages.", false),
+        "cache": object("Where generated messages are cached.", json!({
+            "backend": one_of("The cache backend. Defaults to `redis` when `redis_url` is set and built in, `sqlite` otherwise.", &["sqlite", "redis", "memory", "http"], None),
+            "url": string("The base URL of the `http` backend, e.g. `http://cache.internal:7878`."),
+            "token": string("The bearer token sent to the `http` backend and required by `gitsc cache serve`."),
+        })),
+        "cache_path": {
+            "type": "string",
+            "description": "The SQLite cache file.",
+            "default": DEFAULT_CACHE_PATH,
+        },
+        "cache_ttl": string("How long cached values stay valid, e.g. `1h` (the default) or `never`."),
+        "cache_max_size": string("Evict the oldest entries beyond this size, e.g. `50MB`."),
+        "cache_max_age": string("Evict entries stored longer ago than this, e.g. `30d`."),
+        "redis_url": string("The Redis server of the `redis` cache backend, e.g. `redis://127.0.0.1/`."),
+        "git_backend": one_of("How the repository is read.", &["auto", "libgit2", "cli"], Some("auto")),
+        "scope": object("How the commit scope is chosen.", json!({
+            "infer": boolean("Infer the scope from the repository layout.", scope.infer),
+            "override": boolean("Replace the scope chosen by the model with the inferred one.", scope.override_model),
+            "rules": {
+                "type": "array",
+                "description": "Path globs mapped to scopes, checked before the layout.",
+                "items": {
+                    "type": "object",
+                    "properties": { "path": { "type": "string" }, "scope": { "type": "string" } },
+                    "required": ["path", "scope"],
+                    "additionalProperties": false,
+                },
+            },
+        })),
+        "ticket": object("How issue keys found in the branch name are added.", json!({
+            "patterns": {
+                "type": "array",
+                "description": "Regexes matched against the branch name. A capture group selects the key.",
+                "items": { "type": "string" },
+                "default": ticket.patterns,
+            },
+            "placement": one_of("Where keys are added.", &["footer", "prefix", "none"], Some("footer")),
+            "footer_token": {
+                "type": "string",
+                "description": "The trailer token of the `footer` placement.",
+                "default": ticket.footer_token,
+            },
+        })),
+        "examples": object("Which commits from the history are shown to the model as style examples.", json!({
+            "count": integer("How many examples to include. `0` disables them.", examples.count),
+            "depth": integer("How many recent commits to sample from.", examples.depth),
+            "same_author": boolean("Only use commits by the configured `user.email`.", examples.same_author),
+            "same_paths": boolean("Only use commits that touched the paths being changed.", examples.same_paths),
+            "conventional_only": boolean("Only use Conventional Commits.", examples.conventional_only),
+        })),
+        "usage": object("The token usage log shown by `gitsc usage`.", json!({
+            "enabled": boolean("Record the tokens of every provider call.", true),
+            "path": string("The database file. Defaults to `~/.cache/gitsc/usage.db`."),
+            "prices": {
+                "type": "object",
+                "description": "US dollars per million tokens, keyed by model name or glob.",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": { "input": { "type": "number" }, "output": { "type": "number" } },
+                    "required": ["input", "output"],
+                    "additionalProperties": false,
+                },
+            },
+        })),
+        "log": object("The log file.", json!({
+            "path": { "type": "string", "default": defaults.log.path },
+            "format": one_of("The log format.", &["nmap", "json"], Some("nmap")),
+        })),
+        "smart_commit": object("How much of the diff is sent to the model.", json!({
+            "line_threshold": integer("Diff lines sent in full; larger diffs are cut and the remaining files listed.", defaults.smart_commit.line_threshold),
+        })),
+    });
+
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "gitsc configuration",
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// The JSON type the schema gives a dotted key, e.g. `boolean` for
+/// `scope.infer`, or `None` for keys it does not describe.
+pub fn key_type(key: &str) -> Option<String> {
+    let schema = json_schema();
+    let mut node = &schema;
+    for part in key.split('.') {
+        node = match node.get("properties").and_then(|p| p.get(part)) {
+            Some(property) => property,
+            // Maps such as `usage.prices` describe their values once.
+            None => node.get("additionalProperties").filter(|a| a.is_object())?,
+        };
+    }
+    node.get("type")?.as_str().map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CacheConfig, ExecConfig, Price, ScopeRule, UsageConfig};
+
+    /// The defaults with every optional section filled in, so their keys
+    /// are serialized too.
+    fn full_config() -> Config {
+        let mut usage = UsageConfig::default();
+        usage.prices.insert(
+            "gpt-4o*".to_string(),
+            Price {
+                input: 2.5,
+                output: 10.0,
+            },
+        );
+        let mut scope = ScopeConfig::default();
+        scope.rules.push(ScopeRule {
+            path: "src/**".to_string(),
+            scope: "core".to_string(),
+        });
+        Config {
+            cache: Some(CacheConfig::default()),
+            exec: Some(ExecConfig {
+                command: "llm".to_string(),
+                args: Vec::new(),
+                timeout: None,
+            }),
+            scope: Some(scope),
+            ticket: Some(TicketConfig::default()),
+            examples: Some(ExamplesConfig::default()),
+            usage: Some(usage),
+            ..Config::default()
+        }
+    }
+
+    /// Collects the dotted key and value of every setting in a tree.
+    fn keys(prefix: &str, value: &Value, out: &mut Vec<(String, Value)>) {
+        let Some(map) = value.as_object() else { return };
+        for (name, value) in map {
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            out.push((key.clone(), value.clone()));
+            keys(&key, value, out);
+        }
+    }
+
+    fn json_type(value: &Value) -> Option<&'static str> {
+        match value {
+            Value::Null => None,
+            Value::Bool(_) => Some("boolean"),
+            Value::Number(n) if n.is_f64() => Some("number"),
+            Value::Number(_) => Some("integer"),
+            Value::String(_) => Some("string"),
+            Value::Array(_) => Some("array"),
+            Value::Object(_) => Some("object"),
+        }
+    }
+
+    #[test]
+    fn describes_every_key_of_the_config() {
+        let mut settings = Vec::new();
+        keys("", &serde_json::to_value(full_config()).unwrap(), &mut settings);
+        for (key, value) in settings {
+            let typ = key_type(&key);
+            assert!(typ.is_some(), "the schema does not describe `{}`", key);
+            if let Some(expected) = json_type(&value) {
+                assert_eq!(typ.as_deref(), Some(expected), "type of `{}`", key);
+            }
+        }
+    }
+
+    /// Collects the dotted key of every property the schema describes.
+    fn described(prefix: &str, schema: &Value, out: &mut Vec<String>) {
+        let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        for (name, property) in properties {
+            let key = if prefix.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            out.push(key.clone());
+            described(&key, property, out);
+        }
+    }
+
+    #[test]
+    fn describes_only_keys_of_the_config() {
+        let config = serde_json::to_value(full_config()).unwrap();
+        let mut keys = Vec::new();
+        described("", &json_schema(), &mut keys);
+        for key in keys {
+            let found = key
+                .split('.')
+                .try_fold(&config, |node, part| node.get(part));
+            assert!(found.is_some(), "`{}` is not a config key", key);
+        }
+    }
+}
@@ -0,0 +1,442 @@
+//! Semantic checks that deserialization alone cannot catch, such as a
+//! provider missing its URL or a template with an unknown placeholder.
+
+use regex::Regex;
+use reqwest::Url;
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use super::Config;
+use crate::ai::cache::{configured_backend, expand_home, parse_size};
+use crate::ai::providers::ollama::DEFAULT_OLLAMA_URL;
+use crate::config::CacheBackendKind;
+
+/// The providers `provider` accepts.
+pub const PROVIDERS: &[&str] = &["gemini", "ollama", "openai", "exec", "heuristic"];
+
+/// The placeholders `commit_format` accepts.
+pub const PLACEHOLDERS: &[&str] = &["type", "scope", "message", "breaking", "ticket"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// gitsc cannot work with the value.
+    Error,
+    /// The value is ignored or probably a mistake.
+    Warning,
+}
+
+/// A problem with one configuration key.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    /// The dotted key to fix, e.g. `cache.url`.
+    pub key: String,
+    pub message: String,
+    /// How to fix it.
+    pub suggestion: String,
+}
+
+impl Problem {
+    pub fn error(key: &str, message: String, suggestion: String) -> Self {
+        Self {
+            severity: Severity::Error,
+            key: key.to_string(),
+            message,
+            suggestion,
+        }
+    }
+
+    pub fn warning(key: &str, message: String, suggestion: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(key, message, suggestion)
+        }
+    }
+}
+
+/// Returns the candidate closest to `value`, if any is close enough to be a
+/// likely typo.
+pub fn did_you_mean<'a>(value: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::levenshtein(value, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+impl Config {
+    /// Checks the configuration for problems and returns all of them.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        self.check_provider(&mut problems);
+        self.check_urls(&mut problems);
+        self.check_durations(&mut problems);
+        self.check_cache(&mut problems);
+        check_template(&self.commit_format, &mut problems);
+        self.check_patterns(&mut problems);
+        problems
+    }
+
+    fn check_provider(&self, problems: &mut Vec<Problem>) {
+        match self.provider.as_str() {
+            "exec" if self.exec.is_none() => problems.push(Problem::error(
+                "provider",
+                "The exec provider needs an `exec` section.".to_string(),
+                "Add `exec:` with the `command` to run.".to_string(),
+            )),
+            provider if !PROVIDERS.contains(&provider) => {
+                let suggestion = match did_you_mean(provider, PROVIDERS) {
+                    Some(candidate) => format!("Did you mean `{}`?", candidate),
+                    None => format!("Use one of {}.", PROVIDERS.join(", ")),
+                };
+                problems.push(Problem::error(
+                    "provider",
+                    format!("`{}` is not a known provider.", provider),
+                    suggestion,
+                ));
+            },
+            _ => {},
+        }
+    }
+
+    fn check_urls(&self, problems: &mut Vec<Problem>) {
+        let http = &["http", "https"];
+        let urls = [
+            ("ollama_url", self.ollama_url.as_deref(), http.as_slice(), DEFAULT_OLLAMA_URL),
+            ("openai_url", self.openai_url.as_deref(), http, "https://api.openai.com/v1"),
+            (
+                "redis_url",
+                self.redis_url.as_deref(),
+                &["redis", "rediss", "redis+unix", "unix"],
+                "redis://127.0.0.1/",
+            ),
+            (
+                "cache.url",
+                self.cache.as_ref().and_then(|c| c.url.as_deref()),
+                http,
+                "http://cache.internal:7878",
+            ),
+        ];
+        for (key, url, schemes, example) in urls {
+            let Some(url) = url else {
+                continue;
+            };
+            let suggestion = format!("Use a full URL such as `{}`.", example);
+            match Url::parse(url) {
+                Ok(parsed) if schemes.contains(&parsed.scheme()) => {},
+                Ok(parsed) => problems.push(Problem::error(
+                    key,
+                    format!("`{}` uses the unsupported scheme `{}`.", url, parsed.scheme()),
+                    suggestion,
+                )),
+                Err(e) => problems.push(Problem::error(
+                    key,
+                    format!("`{}` is not a valid URL: {}.", url, e),
+                    suggestion,
+                )),
+            }
+        }
+    }
+
+    fn check_durations(&self, problems: &mut Vec<Problem>) {
+        let durations = [
+            (
+                "cache_ttl",
+                self.cache_ttl
+                    .as_deref()
+                    .filter(|ttl| ttl.trim() != "never"),
+            ),
+            ("cache_max_age", self.cache_max_age.as_deref()),
+            ("exec.timeout", self.exec.as_ref().and_then(|e| e.timeout.as_deref())),
+        ];
+        for (key, duration) in durations {
+            if let Some(duration) = duration
+                && let Err(e) = humantime::parse_duration(duration)
+            {
+                problems.push(Problem::error(
+                    key,
+                    format!("`{}` is not a duration: {}.", duration, e),
+                    "Use a duration such as `30s`, `1h` or `7d`.".to_string(),
+                ));
+            }
+        }
+        if let Some(size) = &self.cache_max_size
+            && let Err(e) = parse_size(size)
+        {
+            problems.push(Problem::error(
+                "cache_max_size",
+                format!("`{}` is not a size: {}.", size, e),
+                "Use a size such as `500KB` or `50MB`.".to_string(),
+            ));
+        }
+    }
+
+    fn check_cache(&self, problems: &mut Vec<Problem>) {
+        if !self.cache_enabled.unwrap_or(false) {
+            return;
+        }
+        match configured_backend(self) {
+            CacheBackendKind::Sqlite => self.check_cache_path(problems),
+            CacheBackendKind::Http
+                if self.cache.as_ref().and_then(|c| c.url.as_ref()).is_none() =>
+            {
+                problems.push(Problem::error(
+                    "cache.backend",
+                    "The http cache backend needs `cache.url`.".to_string(),
+                    "Add `url:` under `cache:`, e.g. `http://cache.internal:7878`.".to_string(),
+                ))
+            },
+            CacheBackendKind::Redis if self.redis_url.is_none() => problems.push(Problem::error(
+                "cache.backend",
+                "The redis cache backend needs `redis_url`.".to_string(),
+                "Add `redis_url: redis://127.0.0.1/`.".to_string(),
+            )),
+            _ => {},
+        }
+    }
+
+    /// Checks that the SQLite cache can be created or opened for writing,
+    /// without creating it or its directory.
+    fn check_cache_path(&self, problems: &mut Vec<Problem>) {
+        let Some(path) = &self.cache_path else {
+            problems.push(Problem::error(
+                "cache_path",
+                "The sqlite cache needs `cache_path`.".to_string(),
+                format!("Set `cache_path: {}`.", super::DEFAULT_CACHE_PATH),
+            ));
+            return;
+        };
+        let writable = expand_home(path)
+            .map_err(|e| e.to_string())
+            .and_then(|path| check_writable(&path));
+        if let Err(e) = writable {
+            problems.push(Problem::error(
+                "cache_path",
+                format!("The cache at `{}` is not writable: {}.", path.display(), e),
+                "Point `cache_path` at a writable location, or set `cache_enabled: false`."
+                    .to_string(),
+            ));
+        }
+    }
+
+    fn check_patterns(&self, problems: &mut Vec<Problem>) {
+        for pattern in self.ticket.iter().flat_map(|t| &t.patterns) {
+            if let Err(e) = Regex::new(pattern) {
+                problems.push(Problem::error(
+                    "ticket.patterns",
+                    format!("`{}` is not a valid regex: {}", pattern, e),
+                    "Quote patterns with single quotes so YAML keeps backslashes.".to_string(),
+                ));
+            }
+        }
+        for rule in self.scope.iter().flat_map(|s| &s.rules) {
+            if let Err(e) = globset::Glob::new(&rule.path) {
+                problems.push(Problem::error(
+                    "scope.rules",
+                    format!("`{}` is not a valid glob: {}.", rule.path, e),
+                    "Use a glob such as `docs/**` or `*.md`.".to_string(),
+                ));
+            }
+        }
+    }
+}
+
+/// Checks that `path` can be opened for writing, or created below its nearest
+/// existing ancestor. Permission bits do not tell what the current user may
+/// do, so a probe file is created and removed in that ancestor instead.
+fn check_writable(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        return Err("it is a directory".to_string());
+    }
+    if path.exists() {
+        // Without `create`, opening leaves the file as it is.
+        return OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map(drop)
+            .map_err(|e| e.to_string());
+    }
+    let Some(ancestor) = path
+        .ancestors()
+        .skip(1)
+        .map(|dir| {
+            if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            }
+        })
+        .find(|dir| dir.exists())
+    else {
+        return Ok(());
+    };
+    if !ancestor.is_dir() {
+        return Err(format!("`{}` is not a directory", ancestor.display()));
+    }
+    let probe = ancestor.join(format!(".gitsc-write-test-{}", std::process::id()));
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        },
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            Err(format!("`{}` is not writable", ancestor.display()))
+        },
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Checks that every `{...}` in a header template is a known placeholder and
+/// that the message itself is included.
+fn check_template(template: &str, problems: &mut Vec<Problem>) {
+    let known = PLACEHOLDERS
+        .iter()
+        .map(|p| format!("{{{}}}", p))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let Some(end) = after
+            .find(['}', '{'])
+            .filter(|&end| after.as_bytes()[end] == b'}')
+        else {
+            problems.push(Problem::error(
+                "commit_format",
+                format!("`{}` has a `{{` that is never closed.", template),
+                format!("Close the placeholder, e.g. `{}`.", super::DEFAULT_COMMIT_FORMAT),
+            ));
+            return;
+        };
+        let name = &after[..end];
+        if !PLACEHOLDERS.contains(&name) {
+            let suggestion = match did_you_mean(name, PLACEHOLDERS) {
+                Some(candidate) => format!("Did you mean `{{{}}}`?", candidate),
+                None => format!("Use {}.", known),
+            };
+            problems.push(Problem::error(
+                "commit_format",
+                format!("`{{{}}}` is not a known placeholder.", name),
+                suggestion,
+            ));
+        }
+        rest = &after[end + 1..];
+    }
+    if !template.contains("{message}") {
+        problems.push(Problem::error(
+            "commit_format",
+            format!("`{}` does not include `{{message}}`.", template),
+            format!("Add it, e.g. `{}`.", super::DEFAULT_COMMIT_FORMAT),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::testing::TempRepo;
+    use std::path::PathBuf;
+
+    fn template_problems(template: &str) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_template(template, &mut problems);
+        problems
+            .into_iter()
+            .map(|p| format!("{} {}", p.message, p.suggestion))
+            .collect()
+    }
+
+    #[test]
+    fn checks_templates() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("{type}({scope}): {message}", &[]),
+            ("[{ticket}] {type}{breaking}: {message}", &[]),
+            (
+                "{typ}: {message}",
+                &["`{typ}` is not a known placeholder. Did you mean `{type}`?"],
+            ),
+            (
+                "{kind}: {message}",
+                &[
+                    "`{kind}` is not a known placeholder. Use {type}, {scope}, {message}, {breaking}, {ticket}.",
+                ],
+            ),
+            (
+                "{type: {message}",
+                &[
+                    "`{type: {message}` has a `{` that is never closed. Close the placeholder, e.g. `{type}({scope}): {message}`.",
+                ],
+            ),
+            (
+                "{type}: done",
+                &[
+                    "`{type}: done` does not include `{message}`. Add it, e.g. `{type}({scope}): {message}`.",
+                ],
+            ),
+        ];
+        for (template, expected) in cases {
+            assert_eq!(template_problems(template), expected, "{}", template);
+        }
+    }
+
+    fn cache_path_problems(path: PathBuf) -> Vec<String> {
+        let config = Config {
+            cache_path: Some(path),
+            ..Config::default()
+        };
+        let mut problems = Vec::new();
+        config.check_cache_path(&mut problems);
+        problems.into_iter().map(|p| p.message).collect()
+    }
+
+    #[test]
+    fn checks_the_cache_path_without_creating_it() {
+        let dir = TempRepo::new();
+        let missing = dir.path.join("a/b/cache.db");
+        assert!(cache_path_problems(missing.clone()).is_empty());
+        assert!(!dir.path.join("a").exists());
+
+        dir.write("cache.db", "data");
+        assert!(cache_path_problems(dir.path.join("cache.db")).is_empty());
+        assert_eq!(fs::read_to_string(dir.path.join("cache.db")).unwrap(), "data");
+    }
+
+    #[test]
+    fn reports_unwritable_cache_paths() {
+        let dir = TempRepo::new();
+        dir.write("file", "");
+        let problems = cache_path_problems(dir.path.join("file/cache.db"));
+        assert!(problems[0].contains("is not a directory"), "{:?}", problems);
+
+        let problems = cache_path_problems(dir.path.clone());
+        assert!(problems[0].contains("it is a directory"), "{:?}", problems);
+
+        let read_only = dir.path.join("read-only");
+        fs::create_dir(&read_only).unwrap();
+        let mut permissions = fs::metadata(&read_only).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&read_only, permissions).unwrap();
+        // Permission bits do not stop root, and neither does the check.
+        if fs::write(read_only.join("probe"), "").is_ok() {
+            assert!(cache_path_problems(read_only.join("cache/cache.db")).is_empty());
+            return;
+        }
+        let problems = cache_path_problems(read_only.join("cache/cache.db"));
+        assert!(problems[0].contains("is not writable"), "{:?}", problems);
+        assert_eq!(fs::read_dir(&read_only).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn defaults_the_ollama_url() {
+        let config = Config {
+            provider: "ollama".to_string(),
+            model: "llama3".to_string(),
+            ..Config::default()
+        };
+        let mut problems = Vec::new();
+        config.check_provider(&mut problems);
+        assert!(problems.is_empty());
+    }
+}
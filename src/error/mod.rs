@@ -18,8 +18,12 @@ pub enum Error {
     Config(String),
     /// An error for when there are no staged changes to analyze.
     NoStagedChanges,
+    /// An error for when the requested diff source has no changes.
+    NoChanges(String),
     /// An error from the SQLite cache.
     Sqlite(tokio_rusqlite::Error),
+    /// An error from another cache backend.
+    Cache(String),
 }
 
 impl fmt::Display for Error {
@@ -33,7 +37,9 @@ impl fmt::Display for Error {
             Self::NoStagedChanges => {
                 write!(f, "No staged changes found to generate a commit message.")
             },
+            Self::NoChanges(source) => write!(f, "No changes found in the {}.", source),
             Self::Sqlite(err) => write!(f, "SQLite error: {}", err),
+            Self::Cache(msg) => write!(f, "Cache error: {}", msg),
         }
     }
 }
@@ -66,3 +72,17 @@ impl From<tokio_rusqlite::Error> for Error {
         Self::Sqlite(err)
     }
 }
+
+#[cfg(feature = "libgit2")]
+impl From<git2::Error> for Error {
+    fn from(err: git2::Error) -> Self {
+        Self::Git(err.message().to_string())
+    }
+}
+
+#[cfg(feature = "redis")]
+impl From<redis::RedisError> for Error {
+    fn from(err: redis::RedisError) -> Self {
+        Self::Cache(err.to_string())
+    }
+}
@@ -0,0 +1,274 @@
+use crate::ai::cache::{CACHE_KEY_VERSION, CacheRepository, diff_hasher};
+use crate::ai::repository::CommitMessage;
+use crate::config::ExamplesConfig;
+use crate::error::Result;
+use crate::git::{CommitInfo, Diff, GitBackend};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// A commit from the repository history that may serve as a style example.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExampleCommit {
+    pub id: String,
+    pub author_email: String,
+    pub message: String,
+    /// The changed paths, only collected when examples are filtered by path.
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+}
+
+/// Samples style examples for a diff from the repository history.
+///
+/// Reading the changed paths of every candidate is the expensive part, so the
+/// candidates are cached per repository and set of recent commits.
+pub async fn sample(
+    git: &dyn GitBackend,
+    cache: Option<&(dyn CacheRepository + Send + Sync)>,
+    config: &ExamplesConfig,
+    diff: &Diff,
+) -> Result<Vec<String>> {
+    if config.count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let commits = git.recent_commits(config.depth)?;
+    let key = cache_key(&git.root()?, &commits, config);
+
+    let cached = match cache {
+        Some(c) => c.get(&key).await?,
+        None => None,
+    };
+    let candidates = match cached.and_then(|v| serde_json::from_str(&v).ok()) {
+        Some(candidates) => {
+            debug!("Example commits retrieved from cache.");
+            candidates
+        },
+        None => {
+            let candidates = collect(git, commits, config)?;
+            if let Some(c) = cache {
+                let value = serde_json::to_string(&candidates).map_err(std::io::Error::from)?;
+                c.set(&key, &value).await?;
+            }
+            candidates
+        },
+    };
+
+    let author_email = if config.same_author {
+        git.user_email()?
+    } else {
+        None
+    };
+    Ok(select(&candidates, config, author_email.as_deref(), diff))
+}
+
+fn cache_key(root: &Path, commits: &[CommitInfo], config: &ExamplesConfig) -> String {
+    let mut key = format!(
+        "examples\n{}\n{}\n{}",
+        root.display(),
+        config.same_paths,
+        config.conventional_only
+    );
+    for commit in commits {
+        key.push('\n');
+        key.push_str(&commit.id);
+    }
+    format!("examples-v{}-{}", CACHE_KEY_VERSION, diff_hasher::generate_diff_hash(&key))
+}
+
+/// Turns recent commits into candidate examples, dropping those that fail the
+/// configured validation.
+pub fn collect(
+    git: &dyn GitBackend,
+    commits: Vec<CommitInfo>,
+    config: &ExamplesConfig,
+) -> Result<Vec<ExampleCommit>> {
+    let mut candidates = Vec::new();
+    for commit in commits {
+        if config.conventional_only && !CommitMessage::parse(&commit.message).is_conventional() {
+            continue;
+        }
+        let paths = if config.same_paths {
+            git.changed_paths(&commit.id)?
+        } else {
+            Vec::new()
+        };
+        candidates.push(ExampleCommit {
+            id: commit.id,
+            author_email: commit.author_email,
+            message: commit.message,
+            paths,
+        });
+    }
+    Ok(candidates)
+}
+
+/// Picks up to `config.count` example messages for a diff, most recent first.
+pub fn select(
+    candidates: &[ExampleCommit],
+    config: &ExamplesConfig,
+    author_email: Option<&str>,
+    diff: &Diff,
+) -> Vec<String> {
+    let touched: HashSet<&Path> = diff
+        .files
+        .iter()
+        .flat_map(|file| {
+            let path = file.path();
+            [
+                Some(path),
+                path.parent().filter(|p| !p.as_os_str().is_empty()),
+            ]
+        })
+        .flatten()
+        .collect();
+
+    candidates
+        .iter()
+        .filter(|c| {
+            !config.same_author
+                || author_email.is_some_and(|email| c.author_email.eq_ignore_ascii_case(email))
+        })
+        .filter(|c| {
+            !config.same_paths
+                || c.paths.iter().any(|p| {
+                    touched.contains(p.as_path())
+                        || p.parent().is_some_and(|parent| touched.contains(parent))
+                })
+        })
+        .take(config.count)
+        .map(|c| c.message.trim().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::{ChangeKind, FileDiff};
+
+    fn example(id: &str, author_email: &str, paths: &[&str]) -> ExampleCommit {
+        ExampleCommit {
+            id: id.to_string(),
+            author_email: author_email.to_string(),
+            message: format!("feat: change {}\n", id),
+            paths: paths.iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    fn diff(paths: &[&str]) -> Diff {
+        Diff {
+            files: paths
+                .iter()
+                .map(|path| FileDiff {
+                    kind: ChangeKind::Modified,
+                    old_path: Some(PathBuf::from(path)),
+                    new_path: Some(PathBuf::from(path)),
+                    old_mode: None,
+                    new_mode: None,
+                    binary: false,
+                    similarity: None,
+                    hunks: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    fn candidates() -> Vec<ExampleCommit> {
+        vec![
+            example("a", "ada@example.com", &["src/git/cli.rs"]),
+            example("b", "bob@example.com", &["src/git/mod.rs"]),
+            example("c", "Ada@Example.com", &["README.md"]),
+            example("d", "bob@example.com", &["src/ai/mod.rs"]),
+        ]
+    }
+
+    #[test]
+    fn selects_the_most_recent_candidates() {
+        let config = ExamplesConfig {
+            count: 2,
+            ..ExamplesConfig::default()
+        };
+        let selected = select(&candidates(), &config, None, &diff(&["src/git/cli.rs"]));
+        assert_eq!(selected, ["feat: change a", "feat: change b"]);
+    }
+
+    #[test]
+    fn selects_commits_by_the_same_author() {
+        let config = ExamplesConfig {
+            same_author: true,
+            ..ExamplesConfig::default()
+        };
+        let diff = diff(&["src/git/cli.rs"]);
+
+        let selected = select(&candidates(), &config, Some("ada@example.com"), &diff);
+        assert_eq!(selected, ["feat: change a", "feat: change c"]);
+        // Without a configured email, no commit is known to be the user's.
+        assert!(select(&candidates(), &config, None, &diff).is_empty());
+    }
+
+    #[test]
+    fn selects_commits_touching_the_same_paths() {
+        let config = ExamplesConfig {
+            same_paths: true,
+            ..ExamplesConfig::default()
+        };
+
+        // The same file, or a sibling in the same directory.
+        let selected = select(&candidates(), &config, None, &diff(&["src/git/cli.rs"]));
+        assert_eq!(selected, ["feat: change a", "feat: change b"]);
+        // A file in the changed directory.
+        let selected = select(&candidates(), &config, None, &diff(&["src/ai"]));
+        assert_eq!(selected, ["feat: change d"]);
+        // Root-level files only match themselves.
+        let selected = select(&candidates(), &config, None, &diff(&["Cargo.toml"]));
+        assert!(selected.is_empty());
+    }
+
+    #[test]
+    fn combines_filters() {
+        let config = ExamplesConfig {
+            same_author: true,
+            same_paths: true,
+            ..ExamplesConfig::default()
+        };
+        let selected =
+            select(&candidates(), &config, Some("bob@example.com"), &diff(&["src/git/diff.rs"]));
+        assert_eq!(selected, ["feat: change b"]);
+    }
+
+    #[cfg(feature = "libgit2")]
+    #[test]
+    fn collects_only_conventional_commits() {
+        use crate::git::LibGit2Backend;
+        use crate::git::testing::TempRepo;
+
+        let repo = TempRepo::new();
+        for (file, message) in [
+            ("a.txt", "feat: add a"),
+            ("b.txt", "Add b"),
+            ("c.txt", "fix(c): repair c"),
+        ] {
+            repo.write(file, message);
+            repo.add_all();
+            repo.commit(message);
+        }
+        let git = LibGit2Backend::new(&repo.path);
+        let messages = |config: &ExamplesConfig| -> Vec<String> {
+            let commits = git.recent_commits(config.depth).unwrap();
+            collect(&git, commits, config)
+                .unwrap()
+                .into_iter()
+                .map(|c| c.message.trim().to_string())
+                .collect()
+        };
+
+        let config = ExamplesConfig::default();
+        assert_eq!(messages(&config), ["fix(c): repair c", "feat: add a"]);
+        let config = ExamplesConfig {
+            conventional_only: false,
+            ..ExamplesConfig::default()
+        };
+        assert_eq!(messages(&config), ["fix(c): repair c", "Add b", "feat: add a"]);
+    }
+}
@@ -1,11 +1,15 @@
 use crate::ai::repository::CommitMessage;
 
+/// Formats the header line of a commit message from a template.
+///
+/// Breaking changes are marked with a `!` at the `{breaking}` placeholder, or
+/// right before the first `: ` when the template has none.
 pub fn format_commit_message(commit: &CommitMessage, format_template: &str) -> String {
     let base = format_template
         .replace("{type}", &commit.commit_type)
         .replace("{message}", &commit.message);
 
-    match &commit.scope {
+    let header = match &commit.scope {
         Some(scope) if !scope.is_empty() => base.replace("{scope}", scope),
         _ => {
             // If scope is None or empty, remove the placeholder and potential surrounding
@@ -15,5 +19,34 @@ pub fn format_commit_message(commit: &CommitMessage, format_template: &str) -> S
                 .trim()
                 .replace("  ", " ")
         },
+    };
+
+    if !commit.breaking {
+        header.replace("{breaking}", "")
+    } else if header.contains("{breaking}") {
+        header.replace("{breaking}", "!")
+    } else {
+        header.replacen(": ", "!: ", 1)
+    }
+}
+
+/// Formats the whole commit message: the header produced by
+/// `format_commit_message`, followed by the body and footers, each separated by
+/// a blank line.
+pub fn format_full_commit_message(commit: &CommitMessage, format_template: &str) -> String {
+    let mut output = format_commit_message(commit, format_template);
+
+    if let Some(body) = commit.body.as_deref().filter(|b| !b.trim().is_empty()) {
+        output.push_str("\n\n");
+        output.push_str(body.trim());
+    }
+
+    if !commit.footers.is_empty() {
+        output.push('\n');
+        for footer in &commit.footers {
+            output.push_str(&format!("\n{}: {}", footer.token, footer.value));
+        }
     }
+
+    output
 }
@@ -0,0 +1,482 @@
+use super::{CommitInfo, Diff, DiffSource, GitBackend, split_commit_range, split_range};
+use crate::error::{Error, Result};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// The hash of the empty tree, used as the base for root commits.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// A backend that shells out to the `git` binary.
+///
+/// External diff drivers, colors and custom path prefixes are disabled so the
+/// output can be parsed regardless of the user's git config.
+pub struct CliBackend {
+    path: PathBuf,
+}
+
+impl CliBackend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn git(&self) -> Command {
+        let mut command = Command::new("git");
+        command.current_dir(&self.path);
+        command.args(["-c", "core.quotePath=true", "-c", "color.ui=false"]);
+        // The diff parser expects the default `a/` and `b/` prefixes.
+        command.args([
+            "-c",
+            "diff.noprefix=false",
+            "-c",
+            "diff.mnemonicPrefix=false",
+        ]);
+        command
+    }
+
+    fn run(&self, args: &[&str], what: &str) -> Result<Vec<u8>> {
+        let output = self.git().args(args).output()?;
+
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(Error::Git(format!("Failed to get {}: {}", what, error_message)));
+        }
+
+        Ok(output.stdout)
+    }
+
+    fn run_with_input(&self, command: &mut Command, input: &str, what: &str) -> Result<String> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+
+        if !output.status.success() {
+            let error_message = String::from_utf8_lossy(&output.stderr).to_string();
+            return Err(Error::Git(format!("Failed to {}: {}", what, error_message)));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// Resolves `rev` to a commit id, or `None` if it does not exist.
+    fn resolve(&self, rev: &str) -> Option<String> {
+        let spec = format!("{}^{{commit}}", rev);
+        self.run(&["rev-parse", "--verify", "--quiet", &spec], "revision")
+            .ok()
+            .map(|out| String::from_utf8_lossy(&out).trim().to_string())
+    }
+
+    fn diff_args(&self, source: &DiffSource) -> Result<Vec<String>> {
+        let args = match source {
+            DiffSource::Staged => vec!["--staged".to_string()],
+            DiffSource::Unstaged => Vec::new(),
+            DiffSource::All => {
+                vec![
+                    self.resolve("HEAD")
+                        .unwrap_or_else(|| EMPTY_TREE.to_string()),
+                ]
+            },
+            DiffSource::Rev(rev) => {
+                let commit = self
+                    .resolve(rev)
+                    .ok_or_else(|| Error::Git(format!("Unknown revision '{}'", rev)))?;
+                let parent = self
+                    .resolve(&format!("{}~1", commit))
+                    .unwrap_or_else(|| EMPTY_TREE.to_string());
+                vec![parent, commit]
+            },
+            DiffSource::Range(range) => {
+                let (from, to) = split_range(range)?;
+                vec![format!("{}...{}", from, to)]
+            },
+        };
+        Ok(args)
+    }
+}
+
+impl GitBackend for CliBackend {
+    fn is_repository(&self) -> bool {
+        self.git()
+            .arg("rev-parse")
+            .arg("--is-inside-work-tree")
+            .output()
+            .map(|output| {
+                output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true"
+            })
+            .unwrap_or(false)
+    }
+
+    fn root(&self) -> Result<PathBuf> {
+        let output = self.run(&["rev-parse", "--show-toplevel"], "repository root")?;
+        Ok(PathBuf::from(String::from_utf8_lossy(&output).trim()))
+    }
+
+    fn diff(&self, source: &DiffSource) -> Result<Diff> {
+        let mut args = vec!["diff", "--no-ext-diff", "--no-color", "--no-textconv", "-M"];
+        let source_args = self.diff_args(source)?;
+        args.extend(source_args.iter().map(String::as_str));
+
+        let output = self.run(&args, &source.to_string())?;
+        Ok(Diff::parse_bytes(&output))
+    }
+
+    fn commits(&self, range: &str) -> Result<Vec<CommitInfo>> {
+        let spec = match split_commit_range(range)? {
+            (Some(from), to) => format!("{}..{}", from, to),
+            (None, to) => to,
+        };
+        let output = self.run(
+            &[
+                "log",
+                "--reverse",
+                "--topo-order",
+                "--format=%H%x00%an%x00%ae%x00%at%x00%B%x1e",
+                &spec,
+            ],
+            "commit log",
+        )?;
+
+        Ok(String::from_utf8_lossy(&output)
+            .split('\x1e')
+            .filter_map(parse_log_record)
+            .collect())
+    }
+
+    fn recent_commits(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        if self.resolve("HEAD").is_none() {
+            return Ok(Vec::new());
+        }
+        let limit = limit.to_string();
+        let output = self.run(
+            &[
+                "log",
+                "--no-merges",
+                "-n",
+                &limit,
+                "--format=%H%x00%an%x00%ae%x00%at%x00%B%x1e",
+                "HEAD",
+            ],
+            "commit log",
+        )?;
+
+        Ok(String::from_utf8_lossy(&output)
+            .split('\x1e')
+            .filter_map(parse_log_record)
+            .collect())
+    }
+
+    fn changed_paths(&self, id: &str) -> Result<Vec<PathBuf>> {
+        let output = self.run(
+            &[
+                "diff-tree",
+                "--no-commit-id",
+                "--name-only",
+                "-r",
+                "-z",
+                "--root",
+                id,
+            ],
+            "changed paths",
+        )?;
+        Ok(String::from_utf8_lossy(&output)
+            .split('\0')
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn user_email(&self) -> Result<Option<String>> {
+        let output = self
+            .git()
+            .args(["config", "--get", "user.email"])
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let email = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(Some(email).filter(|e| !e.is_empty()))
+    }
+
+    fn reachable_tags(&self) -> Result<Vec<String>> {
+        let output = self.run(&["tag", "--merged", "HEAD"], "tags")?;
+        Ok(String::from_utf8_lossy(&output)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+
+    fn current_branch(&self) -> Result<Option<String>> {
+        let output = self
+            .git()
+            .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(Some(branch).filter(|b| !b.is_empty()))
+    }
+
+    fn is_published(&self, id: &str) -> Result<bool> {
+        let Some(upstream) = self.resolve("@{upstream}") else {
+            return Ok(false);
+        };
+        let output = self
+            .git()
+            .args(["merge-base", "--is-ancestor", id, &upstream])
+            .output()?;
+        // Exit code 1 means "not an ancestor"; anything else is a failure,
+        // such as an unknown object.
+        match output.status.code() {
+            Some(0) => Ok(true),
+            Some(1) => Ok(false),
+            _ => Err(Error::Git(format!(
+                "Failed to check whether {} is published: {}",
+                id,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))),
+        }
+    }
+
+    fn check_reword_range(&self, range: &str) -> Result<()> {
+        let (_, to) = split_range(range)?;
+        let head = self
+            .resolve("HEAD")
+            .ok_or_else(|| Error::Git("HEAD does not point to a commit".to_string()))?;
+        if self.resolve(&to).as_ref() != Some(&head) {
+            return Err(Error::Git(format!("The range '{}' must end at HEAD", range)));
+        }
+        Ok(())
+    }
+
+    fn reword(&self, range: &str, messages: &HashMap<String, String>) -> Result<String> {
+        self.check_reword_range(range)?;
+        let head = self
+            .resolve("HEAD")
+            .ok_or_else(|| Error::Git("HEAD does not point to a commit".to_string()))?;
+
+        let mut rewritten: HashMap<String, String> = HashMap::new();
+        for commit in self.commits(range)? {
+            let raw = self.raw_commit(&commit.id)?;
+            let new_message = messages
+                .get(&commit.id)
+                .filter(|message| message.trim_end() != raw.message.trim_end());
+            // Commits before the first change keep their ids, and with them
+            // their signatures.
+            if new_message.is_none() && !raw.parents.iter().any(|p| rewritten.contains_key(p)) {
+                continue;
+            }
+            let message = new_message.unwrap_or(&raw.message);
+
+            let mut command = self.git();
+            command.arg("commit-tree").arg(&raw.tree);
+            for parent in &raw.parents {
+                command
+                    .arg("-p")
+                    .arg(rewritten.get(parent).unwrap_or(parent));
+            }
+            command
+                .arg("-F")
+                .arg("-")
+                .env("GIT_AUTHOR_NAME", &raw.author[0])
+                .env("GIT_AUTHOR_EMAIL", &raw.author[1])
+                .env("GIT_AUTHOR_DATE", &raw.author[2])
+                .env("GIT_COMMITTER_NAME", &raw.committer[0])
+                .env("GIT_COMMITTER_EMAIL", &raw.committer[1])
+                .env("GIT_COMMITTER_DATE", &raw.committer[2]);
+
+            let new_id = self.run_with_input(&mut command, message, "rewrite commit")?;
+            rewritten.insert(commit.id, new_id);
+        }
+
+        let new_head = rewritten
+            .get(&head)
+            .cloned()
+            .unwrap_or_else(|| head.clone());
+        self.run(
+            &[
+                "update-ref",
+                "-m",
+                "gitsc: reword",
+                "HEAD",
+                &new_head,
+                &head,
+            ],
+            "updated HEAD",
+        )?;
+        Ok(new_head)
+    }
+}
+
+/// The raw metadata `git commit-tree` needs to recreate a commit.
+struct RawCommit {
+    tree: String,
+    parents: Vec<String>,
+    author: [String; 3],
+    committer: [String; 3],
+    /// The message exactly as stored, trailing whitespace included.
+    message: String,
+}
+
+impl CliBackend {
+    fn raw_commit(&self, id: &str) -> Result<RawCommit> {
+        let output = self.run(
+            &[
+                "log",
+                "-1",
+                "--date=raw",
+                "--format=%T%x00%P%x00%an%x00%ae%x00%ad%x00%cn%x00%ce%x00%cd",
+                id,
+            ],
+            "commit metadata",
+        )?;
+        let output = String::from_utf8_lossy(&output);
+        let fields: Vec<&str> = output.trim_end_matches('\n').split('\0').collect();
+        let [tree, parents, an, ae, ad, cn, ce, cd] = fields[..] else {
+            return Err(Error::Git(format!("Unexpected metadata for commit {}", id)));
+        };
+        let object = self.run(&["cat-file", "commit", id], "commit message")?;
+        let message = match object.windows(2).position(|w| w == b"\n\n") {
+            Some(end) => String::from_utf8_lossy(&object[end + 2..]).into_owned(),
+            None => String::new(),
+        };
+        Ok(RawCommit {
+            tree: tree.to_string(),
+            parents: parents.split_whitespace().map(String::from).collect(),
+            author: [an.to_string(), ae.to_string(), ad.to_string()],
+            committer: [cn.to_string(), ce.to_string(), cd.to_string()],
+            message,
+        })
+    }
+}
+
+fn parse_log_record(record: &str) -> Option<CommitInfo> {
+    let mut fields = record.trim_start_matches('\n').splitn(5, '\0');
+    let id = fields.next()?.to_string();
+    if id.is_empty() {
+        return None;
+    }
+    Some(CommitInfo {
+        id,
+        author_name: fields.next()?.to_string(),
+        author_email: fields.next()?.to_string(),
+        time: fields.next()?.parse().ok()?,
+        message: fields.next()?.trim_end().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::LineKind;
+    use crate::git::testing::{TempRepo, check_reword, has_git};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    /// A repository to run the backend on, or `None` to skip the test when
+    /// no `git` binary is installed.
+    fn repo() -> Option<TempRepo> {
+        if has_git() {
+            Some(TempRepo::new())
+        } else {
+            eprintln!("Skipping: no git binary.");
+            None
+        }
+    }
+
+    /// Builds a merge where a side commit is dated before its parent, which
+    /// puts a child ahead of its parent in date order.
+    fn skewed_history(repo: &TempRepo) -> Vec<String> {
+        let root = repo.commit_at("root", 1_577_836_800);
+        repo.write("side.txt", "1");
+        let side1 = repo.commit_on("refs/heads/side", "side 1", 1_893_456_000, &[&root]);
+        repo.write("side.txt", "2");
+        let side2 = repo.commit_on("refs/heads/side", "side 2", 946_684_800, &[&side1]);
+        repo.write("main.txt", "1");
+        let main1 = repo.commit_at("main 1", 1_590_969_600);
+        repo.commit_on("HEAD", "merge", 1_609_459_200, &[&main1, &side2]);
+        vec![root, side1, side2, main1]
+    }
+
+    #[test]
+    fn lists_parents_before_children() {
+        let Some(repo) = repo() else { return };
+        let [root, side1, side2, _] = skewed_history(&repo).try_into().unwrap();
+        let ids: Vec<String> = CliBackend::new(&repo.path)
+            .commits("HEAD")
+            .unwrap()
+            .into_iter()
+            .map(|commit| commit.id)
+            .collect();
+        let position = |id: &String| ids.iter().position(|other| other == id).unwrap();
+        assert_eq!(position(&root), 0);
+        assert!(position(&side1) < position(&side2));
+        assert_eq!(ids.len(), 5);
+    }
+
+    #[test]
+    fn checks_whether_commits_are_published() {
+        let Some(repo) = repo() else { return };
+        let pushed = repo.commit("pushed");
+        repo.branch("upstream");
+        repo.config("branch.main.remote", ".");
+        repo.config("branch.main.merge", "refs/heads/upstream");
+        let local = repo.commit("local");
+
+        let git = CliBackend::new(&repo.path);
+        assert!(git.is_published(&pushed).unwrap());
+        assert!(!git.is_published(&local).unwrap());
+        assert!(git.is_published(&"0".repeat(40)).is_err());
+    }
+
+    #[test]
+    fn keeps_non_utf8_paths_and_content() {
+        let Some(repo) = repo() else { return };
+        repo.commit("init");
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        repo.write(name, b"caf\xe9\nok\n");
+        repo.add_all();
+
+        let diff = CliBackend::new(&repo.path)
+            .diff(&DiffSource::Staged)
+            .unwrap();
+        let file = &diff.files[0];
+        assert_eq!(file.path().as_os_str().as_bytes(), b"caf\xe9.txt");
+        let lines = &file.hunks[0].lines;
+        assert_eq!(lines[0].kind, LineKind::Added);
+        assert_eq!(lines[0].content, "caf\u{e9}");
+        assert_eq!(lines[1].content, "ok");
+    }
+
+    #[test]
+    fn ignores_the_users_diff_prefixes() {
+        let Some(repo) = repo() else { return };
+        // Without prefixes, the `b/` of the directory would be stripped.
+        repo.write("b/lib.rs", "one\n");
+        repo.commit("init");
+        repo.write("b/lib.rs", "two\n");
+        repo.add_all();
+        let git = CliBackend::new(&repo.path);
+
+        for key in ["diff.noprefix", "diff.mnemonicPrefix"] {
+            repo.config(key, "true");
+            let diff = git.diff(&DiffSource::Staged).unwrap();
+            assert_eq!(diff.files[0].path(), Path::new("b/lib.rs"), "{}", key);
+            repo.config(key, "false");
+        }
+    }
+
+    #[test]
+    fn rewords_only_what_changed() {
+        let Some(repo) = repo() else { return };
+        check_reword(&repo, &CliBackend::new(&repo.path));
+    }
+}
@@ -0,0 +1,755 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A parsed unified diff, as produced by `git diff`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Diff {
+    pub files: Vec<FileDiff>,
+}
+
+/// The kind of change recorded for a single file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+    /// Only the file mode changed; the content is untouched.
+    ModeChanged,
+}
+
+/// The changes made to a single file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    pub kind: ChangeKind,
+    /// The path before the change. `None` for added files.
+    pub old_path: Option<PathBuf>,
+    /// The path after the change. `None` for deleted files.
+    pub new_path: Option<PathBuf>,
+    pub old_mode: Option<String>,
+    pub new_mode: Option<String>,
+    pub binary: bool,
+    /// Similarity percentage reported for renames and copies.
+    pub similarity: Option<u8>,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A contiguous block of changes within a file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+    /// The section heading git prints after the range, usually the enclosing
+    /// function.
+    pub section: String,
+    pub lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Added,
+    Removed,
+}
+
+/// A single line of a hunk, without its `+`/`-`/` ` marker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub kind: LineKind,
+    pub content: String,
+    /// Set when git reported `\ No newline at end of file` for this line.
+    pub missing_newline: bool,
+}
+
+impl Diff {
+    /// Parses the output of `git diff` into a structured `Diff`.
+    ///
+    /// Unknown extended header lines are ignored, so the parser is tolerant of
+    /// output produced by different git versions.
+    pub fn parse(text: &str) -> Self {
+        Self::parse_bytes(text.as_bytes())
+    }
+
+    /// Parses raw `git diff` output, which is not necessarily UTF-8. Paths
+    /// keep their exact bytes, and content lines that are not valid UTF-8
+    /// are decoded as Latin-1 so that no byte is replaced.
+    pub fn parse_bytes(bytes: &[u8]) -> Self {
+        let mut files = Vec::new();
+        let mut current: Option<FileDiff> = None;
+
+        for line in lines(bytes) {
+            if let Some(rest) = line.strip_prefix(b"diff --git ") {
+                if let Some(file) = current.take() {
+                    files.push(file.finish());
+                }
+                current = Some(FileDiff::from_git_header(rest));
+                continue;
+            }
+
+            let Some(file) = current.as_mut() else {
+                continue;
+            };
+
+            if let Some(hunk) = file.hunks.last_mut() {
+                match line.first() {
+                    Some(b' ') | None => {
+                        hunk.push(LineKind::Context, line.get(1..).unwrap_or_default());
+                        continue;
+                    },
+                    Some(b'+') => {
+                        hunk.push(LineKind::Added, &line[1..]);
+                        continue;
+                    },
+                    Some(b'-') => {
+                        hunk.push(LineKind::Removed, &line[1..]);
+                        continue;
+                    },
+                    Some(b'\\') => {
+                        if let Some(last) = hunk.lines.last_mut() {
+                            last.missing_newline = true;
+                        }
+                        continue;
+                    },
+                    _ => {},
+                }
+            }
+
+            if let Some(range) = line.strip_prefix(b"@@ ") {
+                if let Some(hunk) = Hunk::from_header(&decode(range)) {
+                    file.hunks.push(hunk);
+                }
+            } else {
+                file.apply_header_line(line);
+            }
+        }
+
+        if let Some(file) = current.take() {
+            files.push(file.finish());
+        }
+
+        Self { files }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the number of lines the diff occupies when rendered.
+    pub fn line_count(&self) -> usize {
+        self.files.iter().map(FileDiff::line_count).sum()
+    }
+}
+
+impl FileDiff {
+    fn from_git_header(rest: &[u8]) -> Self {
+        let (old_path, new_path) = split_git_header_paths(rest);
+        Self {
+            kind: ChangeKind::Modified,
+            old_path,
+            new_path,
+            old_mode: None,
+            new_mode: None,
+            binary: false,
+            similarity: None,
+            hunks: Vec::new(),
+        }
+    }
+
+    fn apply_header_line(&mut self, line: &[u8]) {
+        if let Some(mode) = line.strip_prefix(b"new file mode ") {
+            self.kind = ChangeKind::Added;
+            self.new_mode = Some(decode(mode).into_owned());
+        } else if let Some(mode) = line.strip_prefix(b"deleted file mode ") {
+            self.kind = ChangeKind::Deleted;
+            self.old_mode = Some(decode(mode).into_owned());
+        } else if let Some(mode) = line.strip_prefix(b"old mode ") {
+            self.old_mode = Some(decode(mode).into_owned());
+        } else if let Some(mode) = line.strip_prefix(b"new mode ") {
+            self.new_mode = Some(decode(mode).into_owned());
+        } else if let Some(path) = line.strip_prefix(b"rename from ") {
+            self.kind = ChangeKind::Renamed;
+            self.old_path = Some(path_from_bytes(unquote(path)));
+        } else if let Some(path) = line.strip_prefix(b"rename to ") {
+            self.kind = ChangeKind::Renamed;
+            self.new_path = Some(path_from_bytes(unquote(path)));
+        } else if let Some(path) = line.strip_prefix(b"copy from ") {
+            self.kind = ChangeKind::Copied;
+            self.old_path = Some(path_from_bytes(unquote(path)));
+        } else if let Some(path) = line.strip_prefix(b"copy to ") {
+            self.kind = ChangeKind::Copied;
+            self.new_path = Some(path_from_bytes(unquote(path)));
+        } else if let Some(score) = line.strip_prefix(b"similarity index ") {
+            self.similarity = decode(score).trim_end_matches('%').parse().ok();
+        } else if let Some(path) = line.strip_prefix(b"--- ") {
+            self.old_path = parse_marker_path(path, b"a/");
+        } else if let Some(path) = line.strip_prefix(b"+++ ") {
+            self.new_path = parse_marker_path(path, b"b/");
+        } else if line.starts_with(b"Binary files ") || line == b"GIT binary patch" {
+            self.binary = true;
+        }
+    }
+
+    fn finish(mut self) -> Self {
+        match self.kind {
+            ChangeKind::Added => self.old_path = None,
+            ChangeKind::Deleted => self.new_path = None,
+            ChangeKind::Modified
+                if self.hunks.is_empty()
+                    && !self.binary
+                    && self.old_mode.is_some()
+                    && self.old_mode != self.new_mode =>
+            {
+                self.kind = ChangeKind::ModeChanged;
+            },
+            _ => {},
+        }
+        self
+    }
+
+    /// Returns the most relevant path for the file: the new path, or the old
+    /// one for deletions.
+    pub fn path(&self) -> &Path {
+        self.new_path
+            .as_deref()
+            .or(self.old_path.as_deref())
+            .unwrap_or_else(|| Path::new(""))
+    }
+
+    pub fn added_lines(&self) -> usize {
+        self.count_lines(LineKind::Added)
+    }
+
+    pub fn removed_lines(&self) -> usize {
+        self.count_lines(LineKind::Removed)
+    }
+
+    fn count_lines(&self, kind: LineKind) -> usize {
+        self.hunks
+            .iter()
+            .flat_map(|h| &h.lines)
+            .filter(|l| l.kind == kind)
+            .count()
+    }
+
+    /// Returns the number of lines the file occupies when rendered.
+    pub fn line_count(&self) -> usize {
+        self.to_string().lines().count()
+    }
+}
+
+impl Hunk {
+    fn from_header(range: &str) -> Option<Self> {
+        let (ranges, section) = range.split_once(" @@")?;
+        let (old, new) = ranges.split_once(' ')?;
+        let (old_start, old_lines) = parse_range(old.strip_prefix('-')?)?;
+        let (new_start, new_lines) = parse_range(new.strip_prefix('+')?)?;
+        Some(Self {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+            section: section.trim().to_string(),
+            lines: Vec::new(),
+        })
+    }
+
+    fn push(&mut self, kind: LineKind, content: &[u8]) {
+        self.lines.push(Line {
+            kind,
+            content: decode(content).into_owned(),
+            missing_newline: false,
+        });
+    }
+}
+
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    match range.split_once(',') {
+        Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Splits raw diff output into lines, dropping the `\r` of CRLF endings.
+fn lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes
+        .split(|&b| b == b'\n')
+        .filter(move |_| !bytes.is_empty())
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+}
+
+/// Decodes a line as UTF-8, falling back to Latin-1 so every byte maps to
+/// exactly one character.
+fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Cow::Borrowed(text),
+        Err(_) => Cow::Owned(bytes.iter().map(|&b| char::from(b)).collect()),
+    }
+}
+
+/// Builds a path from the exact bytes git printed. Outside Unix, paths must
+/// be Unicode, so invalid sequences are replaced.
+fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStringExt;
+        PathBuf::from(std::ffi::OsString::from_vec(bytes))
+    }
+    #[cfg(not(unix))]
+    {
+        PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+/// Extracts the paths from the `a/<old> b/<new>` part of a `diff --git` line.
+///
+/// Unquoted paths containing spaces are ambiguous, so this only serves as a
+/// fallback for entries without `---`/`+++` or rename headers.
+fn split_git_header_paths(rest: &[u8]) -> (Option<PathBuf>, Option<PathBuf>) {
+    if rest.starts_with(b"\"") {
+        let mut parts = split_quoted(rest).into_iter();
+        let old = parts.next().and_then(|p| strip_side(&p, b"a/"));
+        let new = parts.next().and_then(|p| strip_side(&p, b"b/"));
+        return (old, new);
+    }
+
+    // Both sides are identical for anything but renames and copies, which
+    // carry their own headers, so split the line in half.
+    if rest.len() >= 5 && (rest.len() - 1).is_multiple_of(2) {
+        let half = (rest.len() - 1) / 2;
+        let (old, new) = (&rest[..half], &rest[half + 1..]);
+        if let (Some(old), Some(new)) = (strip_side(old, b"a/"), strip_side(new, b"b/")) {
+            return (Some(old), Some(new));
+        }
+    }
+
+    match rest.windows(3).position(|window| window == b" b/") {
+        Some(at) => {
+            (strip_side(&rest[..at], b"a/"), Some(path_from_bytes(rest[at + 3..].to_vec())))
+        },
+        None => (None, None),
+    }
+}
+
+fn strip_side(path: &[u8], prefix: &[u8]) -> Option<PathBuf> {
+    path.strip_prefix(prefix)
+        .map(|path| path_from_bytes(path.to_vec()))
+}
+
+fn parse_marker_path(path: &[u8], prefix: &[u8]) -> Option<PathBuf> {
+    // Git appends a tab after paths containing spaces.
+    let path = path.strip_suffix(b"\t").unwrap_or(path);
+    if path == b"/dev/null" {
+        return None;
+    }
+    let path = unquote(path);
+    Some(path_from_bytes(path.strip_prefix(prefix).unwrap_or(&path).to_vec()))
+}
+
+/// Undoes the C-style quoting git applies to paths with special characters.
+fn unquote(path: &[u8]) -> Vec<u8> {
+    let Some(inner) = path.strip_prefix(b"\"").and_then(|p| p.strip_suffix(b"\"")) else {
+        return path.to_vec();
+    };
+
+    let mut bytes = Vec::with_capacity(inner.len());
+    let mut chars = inner.iter().copied().peekable();
+    while let Some(b) = chars.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match chars.next() {
+            Some(b'n') => bytes.push(b'\n'),
+            Some(b't') => bytes.push(b'\t'),
+            Some(b'r') => bytes.push(b'\r'),
+            Some(d @ b'0'..=b'7') => {
+                let mut value = u32::from(d - b'0');
+                for _ in 0..2 {
+                    match chars.peek() {
+                        Some(&d @ b'0'..=b'7') => {
+                            value = value * 8 + u32::from(d - b'0');
+                            chars.next();
+                        },
+                        _ => break,
+                    }
+                }
+                bytes.push(value as u8);
+            },
+            Some(other) => bytes.push(other),
+            None => bytes.push(b'\\'),
+        }
+    }
+    bytes
+}
+
+fn split_quoted(rest: &[u8]) -> Vec<Vec<u8>> {
+    let mut parts = Vec::new();
+    let mut remaining = rest.trim_ascii_start();
+    while !remaining.is_empty() {
+        if remaining.starts_with(b"\"") {
+            let mut end = 1;
+            while end < remaining.len() && remaining[end] != b'"' {
+                end += if remaining[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(remaining.len());
+            parts.push(unquote(&remaining[..end]));
+            remaining = remaining[end..].trim_ascii_start();
+        } else {
+            let end = remaining
+                .iter()
+                .position(|&b| b == b' ')
+                .unwrap_or(remaining.len());
+            parts.push(remaining[..end].to_vec());
+            remaining = remaining[end..].trim_ascii_start();
+        }
+    }
+    parts
+}
+
+impl fmt::Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for file in &self.files {
+            write!(f, "{}", file)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let old = self.old_path.as_deref().unwrap_or(self.path());
+        let new = self.new_path.as_deref().unwrap_or(self.path());
+        writeln!(f, "diff --git a/{} b/{}", old.display(), new.display())?;
+
+        match self.kind {
+            ChangeKind::Added => {
+                writeln!(f, "new file mode {}", self.new_mode.as_deref().unwrap_or("100644"))?
+            },
+            ChangeKind::Deleted => {
+                writeln!(f, "deleted file mode {}", self.old_mode.as_deref().unwrap_or("100644"))?
+            },
+            _ => {
+                if let (Some(old_mode), Some(new_mode)) = (&self.old_mode, &self.new_mode)
+                    && old_mode != new_mode
+                {
+                    writeln!(f, "old mode {}\nnew mode {}", old_mode, new_mode)?;
+                }
+            },
+        }
+
+        if matches!(self.kind, ChangeKind::Renamed | ChangeKind::Copied) {
+            let verb = if self.kind == ChangeKind::Renamed {
+                "rename"
+            } else {
+                "copy"
+            };
+            if let Some(similarity) = self.similarity {
+                writeln!(f, "similarity index {}%", similarity)?;
+            }
+            writeln!(f, "{} from {}", verb, old.display())?;
+            writeln!(f, "{} to {}", verb, new.display())?;
+        }
+
+        if self.binary {
+            return writeln!(f, "Binary files a/{} and b/{} differ", old.display(), new.display());
+        }
+
+        if !self.hunks.is_empty() {
+            match &self.old_path {
+                Some(path) => writeln!(f, "--- a/{}", path.display())?,
+                None => writeln!(f, "--- /dev/null")?,
+            }
+            match &self.new_path {
+                Some(path) => writeln!(f, "+++ b/{}", path.display())?,
+                None => writeln!(f, "+++ /dev/null")?,
+            }
+        }
+
+        for hunk in &self.hunks {
+            write!(f, "{}", hunk)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "@@ -{},{} +{},{} @@",
+            self.old_start, self.old_lines, self.new_start, self.new_lines
+        )?;
+        if self.section.is_empty() {
+            writeln!(f)?;
+        } else {
+            writeln!(f, " {}", self.section)?;
+        }
+        for line in &self.lines {
+            write!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let marker = match self.kind {
+            LineKind::Context => ' ',
+            LineKind::Added => '+',
+            LineKind::Removed => '-',
+        };
+        writeln!(f, "{}{}", marker, self.content)?;
+        if self.missing_newline {
+            writeln!(f, "\\ No newline at end of file")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(file: &FileDiff) -> &str {
+        file.path().to_str().unwrap()
+    }
+
+    #[test]
+    fn parses_modified_file_hunks() {
+        let diff = Diff::parse(
+            "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,4 @@ fn main() {
+ let a = 1;
+-let b = 2;
++let b = 3;
++let c = 4;
+ 
+",
+        );
+        let [file] = diff.files.as_slice() else {
+            panic!("expected one file: {:?}", diff);
+        };
+        assert_eq!(file.kind, ChangeKind::Modified);
+        assert_eq!(path(file), "src/lib.rs");
+        assert_eq!((file.added_lines(), file.removed_lines()), (2, 1));
+        let hunk = &file.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines, hunk.new_start, hunk.new_lines), (1, 3, 1, 4));
+        assert_eq!(hunk.section, "fn main() {");
+        assert_eq!(hunk.lines.len(), 5);
+        assert_eq!(hunk.lines[4].kind, LineKind::Context);
+    }
+
+    #[test]
+    fn parses_added_and_deleted_files() {
+        let diff = Diff::parse(
+            "diff --git a/new.txt b/new.txt
+new file mode 100755
+index 0000000..1111111
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+diff --git a/old.txt b/old.txt
+deleted file mode 100644
+index 1111111..0000000
+--- a/old.txt
++++ /dev/null
+@@ -1 +0,0 @@
+-bye
+",
+        );
+        let [added, deleted] = diff.files.as_slice() else {
+            panic!("expected two files: {:?}", diff);
+        };
+        assert_eq!(added.kind, ChangeKind::Added);
+        assert_eq!(added.old_path, None);
+        assert_eq!(added.new_mode.as_deref(), Some("100755"));
+        assert_eq!((added.hunks[0].new_start, added.hunks[0].new_lines), (1, 1));
+        assert_eq!(deleted.kind, ChangeKind::Deleted);
+        assert_eq!(deleted.new_path, None);
+        assert_eq!(path(deleted), "old.txt");
+    }
+
+    #[test]
+    fn parses_renames_with_similarity() {
+        let diff = Diff::parse(
+            "diff --git a/src/old name.rs b/src/new name.rs
+similarity index 87%
+rename from src/old name.rs
+rename to src/new name.rs
+index 1111111..2222222 100644
+--- a/src/old name.rs\t
++++ b/src/new name.rs\t
+@@ -1 +1 @@
+-a
++b
+",
+        );
+        let file = &diff.files[0];
+        assert_eq!(file.kind, ChangeKind::Renamed);
+        assert_eq!(file.similarity, Some(87));
+        assert_eq!(file.old_path.as_deref(), Some(Path::new("src/old name.rs")));
+        assert_eq!(file.new_path.as_deref(), Some(Path::new("src/new name.rs")));
+    }
+
+    #[test]
+    fn parses_pure_rename_and_copy_without_hunks() {
+        let diff = Diff::parse(
+            "diff --git a/a.txt b/dir/a.txt
+similarity index 100%
+rename from a.txt
+rename to dir/a.txt
+diff --git a/b.txt b/c.txt
+similarity index 100%
+copy from b.txt
+copy to c.txt
+",
+        );
+        assert_eq!(diff.files[0].kind, ChangeKind::Renamed);
+        assert_eq!(path(&diff.files[0]), "dir/a.txt");
+        assert_eq!(diff.files[0].old_path.as_deref(), Some(Path::new("a.txt")));
+        assert_eq!(diff.files[1].kind, ChangeKind::Copied);
+        assert_eq!(path(&diff.files[1]), "c.txt");
+    }
+
+    #[test]
+    fn parses_quoted_paths() {
+        let diff = Diff::parse(
+            "diff --git \"a/caf\\303\\251 \\\"q\\\".txt\" \"b/caf\\303\\251 \\\"q\\\".txt\"
+new file mode 100644
+index 0000000..1111111
+--- /dev/null
++++ \"b/caf\\303\\251 \\\"q\\\".txt\"
+@@ -0,0 +1 @@
++x
+diff --git \"a/tab\\there\" \"b/tab\\there\"
+deleted file mode 100644
+Binary files \"a/tab\\there\" and /dev/null differ
+",
+        );
+        assert_eq!(path(&diff.files[0]), "café \"q\".txt");
+        assert_eq!(path(&diff.files[1]), "tab\there");
+        assert!(diff.files[1].binary);
+    }
+
+    #[test]
+    fn unquote_handles_escapes() {
+        assert_eq!(unquote(b"plain"), b"plain");
+        assert_eq!(unquote(b"\"a\\nb\\\\c\""), b"a\nb\\c");
+        assert_eq!(unquote(b"\"\\303\\251\""), "é".as_bytes());
+        assert_eq!(unquote(b"\"\\377\""), b"\xff");
+        assert_eq!(unquote(b"\"trailing\\\""), b"trailing\\");
+    }
+
+    #[test]
+    fn splits_git_header_paths() {
+        let split = |rest: &str| split_git_header_paths(rest.as_bytes());
+        let some = |p: &str| Some(PathBuf::from(p));
+        assert_eq!(split("a/x.rs b/x.rs"), (some("x.rs"), some("x.rs")));
+        assert_eq!(
+            split("a/with b/space b/with b/space"),
+            (some("with b/space"), some("with b/space"))
+        );
+        assert_eq!(split("a/old.rs b/new.rs"), (some("old.rs"), some("new.rs")));
+        assert_eq!(
+            split("\"a/q \\\"x\\\"\" \"b/q \\\"x\\\"\""),
+            (some("q \"x\""), some("q \"x\""))
+        );
+        assert_eq!(split("garbage"), (None, None));
+    }
+
+    #[test]
+    fn parses_binary_files() {
+        let diff = Diff::parse(
+            "diff --git a/logo.png b/logo.png
+index 1111111..2222222 100644
+Binary files a/logo.png and b/logo.png differ
+diff --git a/icon.png b/icon.png
+new file mode 100644
+index 0000000..1111111
+GIT binary patch
+literal 3
+KcmZ?wbN>JW
+
+literal 0
+HcmV?d00001
+
+",
+        );
+        assert!(
+            diff.files
+                .iter()
+                .all(|file| file.binary && file.hunks.is_empty())
+        );
+        assert_eq!(diff.files[0].kind, ChangeKind::Modified);
+        assert_eq!(diff.files[1].kind, ChangeKind::Added);
+        assert_eq!(
+            diff.files[0].to_string().lines().last(),
+            Some("Binary files a/logo.png and b/logo.png differ")
+        );
+    }
+
+    #[test]
+    fn parses_mode_changes() {
+        let diff = Diff::parse(
+            "diff --git a/run.sh b/run.sh
+old mode 100644
+new mode 100755
+diff --git a/tool.sh b/tool.sh
+old mode 100644
+new mode 100755
+index 1111111..2222222
+--- a/tool.sh
++++ b/tool.sh
+@@ -1 +1 @@
+-echo a
++echo b
+",
+        );
+        assert_eq!(diff.files[0].kind, ChangeKind::ModeChanged);
+        assert_eq!(diff.files[0].new_mode.as_deref(), Some("100755"));
+        assert_eq!(diff.files[1].kind, ChangeKind::Modified);
+        assert!(
+            diff.files[0]
+                .to_string()
+                .contains("old mode 100644\nnew mode 100755\n")
+        );
+    }
+
+    #[test]
+    fn keeps_no_newline_markers() {
+        let text = "diff --git a/a.txt b/a.txt
+index 1111111..2222222 100644
+--- a/a.txt
++++ b/a.txt
+@@ -1 +1 @@
+-old
+\\ No newline at end of file
++new
+\\ No newline at end of file
+";
+        let diff = Diff::parse(text);
+        let lines = &diff.files[0].hunks[0].lines;
+        assert!(lines.iter().all(|line| line.missing_newline));
+        assert_eq!(lines[1].content, "new");
+        assert!(
+            diff.to_string().ends_with(
+                "-old\n\\ No newline at end of file\n+new\n\\ No newline at end of file\n"
+            )
+        );
+    }
+
+    #[test]
+    fn ignores_text_before_the_first_file() {
+        assert!(Diff::parse("").is_empty());
+        assert!(Diff::parse("warning: something\n").is_empty());
+    }
+}
@@ -0,0 +1,314 @@
+use super::{CommitInfo, Diff, DiffSource, GitBackend, split_commit_range, split_range};
+use crate::error::{Error, Result};
+use git2::{Commit, DiffFindOptions, DiffFormat, DiffOptions, Oid, Repository, Sort, Tree};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A backend that reads the repository through libgit2, without spawning
+/// `git` or consulting diff drivers from the user's config.
+pub struct LibGit2Backend {
+    path: PathBuf,
+}
+
+impl LibGit2Backend {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn open(&self) -> Result<Repository> {
+        Ok(Repository::discover(&self.path)?)
+    }
+}
+
+/// Returns the tree `HEAD` points to. An unborn branch has no tree, in which
+/// case the diff is taken against the empty tree.
+fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>> {
+    match repo.head() {
+        Ok(head) => Ok(Some(head.peel_to_tree()?)),
+        Err(_) => Ok(None),
+    }
+}
+
+fn commit_tree<'r>(repo: &'r Repository, rev: &str) -> Result<Tree<'r>> {
+    Ok(repo.revparse_single(rev)?.peel_to_tree()?)
+}
+
+impl GitBackend for LibGit2Backend {
+    fn is_repository(&self) -> bool {
+        self.open().map(|repo| !repo.is_bare()).unwrap_or(false)
+    }
+
+    fn root(&self) -> Result<PathBuf> {
+        let repo = self.open()?;
+        repo.workdir()
+            .map(|path| path.to_path_buf())
+            .ok_or_else(|| Error::Git("The repository has no working tree".to_string()))
+    }
+
+    fn diff(&self, source: &DiffSource) -> Result<Diff> {
+        let repo = self.open()?;
+        let mut options = DiffOptions::new();
+
+        let mut diff = match source {
+            DiffSource::Staged => {
+                repo.diff_tree_to_index(head_tree(&repo)?.as_ref(), None, Some(&mut options))?
+            },
+            DiffSource::Unstaged => repo.diff_index_to_workdir(None, Some(&mut options))?,
+            DiffSource::All => repo
+                .diff_tree_to_workdir_with_index(head_tree(&repo)?.as_ref(), Some(&mut options))?,
+            DiffSource::Rev(rev) => {
+                let commit = repo.revparse_single(rev)?.peel_to_commit()?;
+                let parent_tree = match commit.parent(0) {
+                    Ok(parent) => Some(parent.tree()?),
+                    Err(_) => None,
+                };
+                repo.diff_tree_to_tree(
+                    parent_tree.as_ref(),
+                    Some(&commit.tree()?),
+                    Some(&mut options),
+                )?
+            },
+            DiffSource::Range(range) => {
+                let (from, to) = split_range(range)?;
+                let from_id = repo.revparse_single(&from)?.peel_to_commit()?.id();
+                let to_id = repo.revparse_single(&to)?.peel_to_commit()?.id();
+                let base = repo.merge_base(from_id, to_id).map_err(|_| {
+                    Error::Git(format!("'{}' and '{}' have no common ancestor", from, to))
+                })?;
+                repo.diff_tree_to_tree(
+                    Some(&repo.find_commit(base)?.tree()?),
+                    Some(&commit_tree(&repo, &to)?),
+                    Some(&mut options),
+                )?
+            },
+        };
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        Ok(Diff::parse_bytes(&render_patch(&diff)?))
+    }
+
+    fn commits(&self, range: &str) -> Result<Vec<CommitInfo>> {
+        let repo = self.open()?;
+        let (from, to) = split_commit_range(range)?;
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        walk.push(repo.revparse_single(&to)?.peel_to_commit()?.id())?;
+        if let Some(from) = from {
+            walk.hide(repo.revparse_single(&from)?.peel_to_commit()?.id())?;
+        }
+
+        walk.map(|id| Ok(commit_info(&repo.find_commit(id?)?)))
+            .collect()
+    }
+
+    fn recent_commits(&self, limit: usize) -> Result<Vec<CommitInfo>> {
+        let repo = self.open()?;
+        let Ok(head) = repo.head().and_then(|h| h.peel_to_commit()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+        walk.push(head.id())?;
+
+        let mut commits = Vec::new();
+        for id in walk {
+            if commits.len() >= limit {
+                break;
+            }
+            let commit = repo.find_commit(id?)?;
+            if commit.parent_count() <= 1 {
+                commits.push(commit_info(&commit));
+            }
+        }
+        Ok(commits)
+    }
+
+    fn changed_paths(&self, id: &str) -> Result<Vec<PathBuf>> {
+        let repo = self.open()?;
+        let commit = repo.find_commit(Oid::from_str(id)?)?;
+        let parent_tree = match commit.parent(0) {
+            Ok(parent) => Some(parent.tree()?),
+            Err(_) => None,
+        };
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                delta
+                    .new_file()
+                    .path()
+                    .or_else(|| delta.old_file().path())
+                    .map(Path::to_path_buf)
+            })
+            .collect())
+    }
+
+    fn user_email(&self) -> Result<Option<String>> {
+        let repo = self.open()?;
+        Ok(repo.config()?.get_string("user.email").ok())
+    }
+
+    fn reachable_tags(&self) -> Result<Vec<String>> {
+        let repo = self.open()?;
+        let head = repo.head()?.peel_to_commit()?.id();
+        let mut tags = Vec::new();
+        for name in repo.tag_names(None)?.iter().flatten() {
+            let Ok(object) = repo.revparse_single(&format!("refs/tags/{}", name)) else {
+                continue;
+            };
+            let Ok(commit) = object.peel_to_commit() else {
+                continue;
+            };
+            if commit.id() == head || repo.graph_descendant_of(head, commit.id())? {
+                tags.push(name.to_string());
+            }
+        }
+        Ok(tags)
+    }
+
+    fn current_branch(&self) -> Result<Option<String>> {
+        let repo = self.open()?;
+        // Read HEAD as a reference so unborn branches are reported too.
+        let head = repo.find_reference("HEAD")?;
+        Ok(head
+            .symbolic_target()
+            .and_then(|target| target.strip_prefix("refs/heads/"))
+            .map(String::from))
+    }
+
+    fn is_published(&self, id: &str) -> Result<bool> {
+        let repo = self.open()?;
+        let head = repo.head()?;
+        let Some(branch_name) = head.shorthand().filter(|_| head.is_branch()) else {
+            return Ok(false);
+        };
+        let branch = repo.find_branch(branch_name, git2::BranchType::Local)?;
+        let Ok(upstream) = branch.upstream() else {
+            return Ok(false);
+        };
+        let upstream_id = upstream.get().peel_to_commit()?.id();
+        let id = Oid::from_str(id)?;
+        Ok(upstream_id == id || repo.graph_descendant_of(upstream_id, id)?)
+    }
+
+    fn check_reword_range(&self, range: &str) -> Result<()> {
+        let repo = self.open()?;
+        let (_, to) = split_range(range)?;
+        let head_id = repo.head()?.peel_to_commit()?.id();
+        if repo.revparse_single(&to)?.peel_to_commit()?.id() != head_id {
+            return Err(Error::Git(format!("The range '{}' must end at HEAD", range)));
+        }
+        Ok(())
+    }
+
+    fn reword(&self, range: &str, messages: &HashMap<String, String>) -> Result<String> {
+        self.check_reword_range(range)?;
+        let repo = self.open()?;
+        let mut head = repo.head()?;
+        let head_id = head.peel_to_commit()?.id();
+
+        let mut rewritten: HashMap<Oid, Oid> = HashMap::new();
+        for info in self.commits(range)? {
+            let commit = repo.find_commit(Oid::from_str(&info.id)?)?;
+            let original = String::from_utf8_lossy(commit.message_raw_bytes());
+            let new_message = messages
+                .get(&info.id)
+                .filter(|message| message.trim_end() != original.trim_end());
+            // Commits before the first change keep their ids, and with them
+            // their signatures.
+            if new_message.is_none() && !commit.parent_ids().any(|id| rewritten.contains_key(&id)) {
+                continue;
+            }
+            let parents = commit
+                .parent_ids()
+                .map(|id| repo.find_commit(*rewritten.get(&id).unwrap_or(&id)))
+                .collect::<std::result::Result<Vec<_>, _>>()?;
+            let message = new_message.map_or(original.as_ref(), String::as_str);
+
+            let new_id = repo.commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                message,
+                &commit.tree()?,
+                &parents.iter().collect::<Vec<_>>(),
+            )?;
+            rewritten.insert(commit.id(), new_id);
+        }
+
+        let new_head = rewritten.get(&head_id).copied().unwrap_or(head_id);
+        if head.is_branch() {
+            head.set_target(new_head, "gitsc: reword")?;
+        } else {
+            repo.set_head_detached(new_head)?;
+        }
+        Ok(new_head.to_string())
+    }
+}
+
+fn commit_info(commit: &Commit<'_>) -> CommitInfo {
+    let author = commit.author();
+    CommitInfo {
+        id: commit.id().to_string(),
+        author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+        author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+        time: author.when().seconds(),
+        message: String::from_utf8_lossy(commit.message_bytes())
+            .trim_end()
+            .to_string(),
+    }
+}
+
+/// Renders a libgit2 diff in the same patch format `git diff` prints.
+fn render_patch(diff: &git2::Diff<'_>) -> Result<Vec<u8>> {
+    let mut patch = Vec::new();
+    diff.print(DiffFormat::Patch, |_, _, line| {
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            patch.push(line.origin() as u8);
+        }
+        patch.extend_from_slice(line.content());
+        true
+    })?;
+    Ok(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::testing::{TempRepo, check_reword};
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn keeps_non_utf8_paths_and_content() {
+        let repo = TempRepo::new();
+        repo.write("readme.md", "hi\n");
+        repo.commit("init");
+        let name = std::ffi::OsStr::from_bytes(b"caf\xe9.txt");
+        repo.write(name, b"caf\xe9\n");
+        repo.write("readme.md", "hi é\n");
+        repo.add_all();
+
+        let diff = LibGit2Backend::new(&repo.path)
+            .diff(&DiffSource::Staged)
+            .unwrap();
+        let added = diff
+            .files
+            .iter()
+            .find(|file| file.path().as_os_str().as_bytes() == b"caf\xe9.txt")
+            .expect("the non-UTF-8 path is kept");
+        assert_eq!(added.hunks[0].lines[0].content, "caf\u{e9}");
+        let readme = diff
+            .files
+            .iter()
+            .find(|file| file.path() == Path::new("readme.md"));
+        assert_eq!(readme.unwrap().hunks[0].lines[1].content, "hi é");
+    }
+
+    #[test]
+    fn rewords_only_what_changed() {
+        let repo = TempRepo::new();
+        check_reword(&repo, &LibGit2Backend::new(&repo.path));
+    }
+}
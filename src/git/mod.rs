@@ -1,38 +1,202 @@
+use crate::config::GitBackendKind;
 use crate::error::{Error, Result};
-use std::process::Command;
+use log::debug;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
 
-/// Checks if the current directory is a Git repository.
-pub fn is_git_repository() -> bool {
-    Command::new("git")
-        .arg("rev-parse")
-        .arg("--is-inside-work-tree")
-        .output()
-        .map(|output| {
-            output.status.success() && String::from_utf8_lossy(&output.stdout).trim() == "true"
-        })
-        .unwrap_or(false)
+pub mod cli;
+pub mod diff;
+#[cfg(feature = "libgit2")]
+pub mod libgit2;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub use cli::CliBackend;
+pub use diff::{ChangeKind, Diff, FileDiff, Hunk, Line, LineKind};
+#[cfg(feature = "libgit2")]
+pub use libgit2::LibGit2Backend;
+
+/// Which changes to generate a commit message for.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum DiffSource {
+    /// Changes in the index, compared to `HEAD`.
+    #[default]
+    Staged,
+    /// Changes in the working tree that are not staged yet.
+    Unstaged,
+    /// Staged and unstaged changes together, compared to `HEAD`.
+    All,
+    /// The changes introduced by a single commit.
+    Rev(String),
+    /// The combined changes of every commit in `A..B`.
+    Range(String),
 }
 
-/// Gets the staged diff from the Git repository.
-///
-/// # Returns
-///
-/// * `Ok(String)` if there are staged changes.
-/// * `Err(Error::NoStagedChanges)` if there are no staged changes.
-/// * `Err(Error::Git)` if the command fails for other reasons.
-pub fn get_staged_diff() -> Result<String> {
-    let output = Command::new("git").arg("diff").arg("--staged").output()?;
+impl fmt::Display for DiffSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Staged => write!(f, "staged changes"),
+            Self::Unstaged => write!(f, "unstaged changes"),
+            Self::All => write!(f, "working tree"),
+            Self::Rev(rev) => write!(f, "commit {}", rev),
+            Self::Range(range) => write!(f, "range {}", range),
+        }
+    }
+}
+
+/// A commit read from the repository history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitInfo {
+    pub id: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author time in seconds since the Unix epoch.
+    pub time: i64,
+    /// The full commit message, including the body.
+    pub message: String,
+}
 
-    if !output.status.success() {
-        let error_message = String::from_utf8_lossy(&output.stderr).to_string();
-        return Err(Error::Git(format!("Failed to get staged diff: {}", error_message)));
+impl CommitInfo {
+    /// Returns the first line of the commit message.
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("").trim()
     }
 
-    let diff = String::from_utf8_lossy(&output.stdout).to_string();
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(7)]
+    }
+}
 
-    if diff.trim().is_empty() {
-        Err(Error::NoStagedChanges)
+/// Splits `A..B` into its endpoints. A missing side defaults to `HEAD`, like
+/// in git.
+pub fn split_range(range: &str) -> Result<(String, String)> {
+    let (from, to) = range
+        .split_once("...")
+        .or_else(|| range.split_once(".."))
+        .ok_or_else(|| Error::Git(format!("'{}' is not a range of the form A..B", range)))?;
+    let side = |s: &str| {
+        if s.is_empty() {
+            "HEAD".to_string()
+        } else {
+            s.to_string()
+        }
+    };
+    Ok((side(from), side(to)))
+}
+
+/// Splits a commit range for history walks. Unlike `split_range`, a single
+/// revision is accepted and means its whole history.
+pub fn split_commit_range(range: &str) -> Result<(Option<String>, String)> {
+    if range.contains("..") {
+        let (from, to) = split_range(range)?;
+        Ok((Some(from), to))
     } else {
-        Ok(diff)
+        Ok((None, range.to_string()))
+    }
+}
+
+/// A trait for the different ways of reading a Git repository.
+pub trait GitBackend: Send + Sync {
+    /// Checks if the current directory is a Git repository.
+    fn is_repository(&self) -> bool;
+
+    /// Returns the root directory of the working tree.
+    fn root(&self) -> Result<PathBuf>;
+
+    /// Returns the diff for the given source. The diff may be empty.
+    ///
+    /// For ranges, the diff is taken from the merge base of both ends, so it
+    /// contains exactly the changes made by the commits in the range.
+    fn diff(&self, source: &DiffSource) -> Result<Diff>;
+
+    /// Returns the commits reachable from the end of `range` but not from its
+    /// start, oldest first. A single revision lists its whole history.
+    fn commits(&self, range: &str) -> Result<Vec<CommitInfo>>;
+
+    /// Returns up to `limit` non-merge commits reachable from `HEAD`, newest
+    /// first. An unborn branch has no commits.
+    fn recent_commits(&self, limit: usize) -> Result<Vec<CommitInfo>>;
+
+    /// Returns the paths a commit changed relative to its first parent.
+    fn changed_paths(&self, id: &str) -> Result<Vec<PathBuf>>;
+
+    /// Returns the configured `user.email`, if any.
+    fn user_email(&self) -> Result<Option<String>>;
+
+    /// Returns the names of the tags whose commits are reachable from `HEAD`.
+    fn reachable_tags(&self) -> Result<Vec<String>>;
+
+    /// Returns the short name of the checked out branch, or `None` when `HEAD`
+    /// is detached.
+    fn current_branch(&self) -> Result<Option<String>>;
+
+    /// Checks whether a commit is reachable from the upstream of the current
+    /// branch, i.e. whether it has already been pushed. Returns `false` when
+    /// the branch has no upstream.
+    fn is_published(&self, id: &str) -> Result<bool>;
+
+    /// Checks that `range` is of the form `A..B` and ends at `HEAD`, as
+    /// `reword` requires.
+    fn check_reword_range(&self, range: &str) -> Result<()>;
+
+    /// Rewrites the commits in `range`, replacing the messages of those listed
+    /// in `messages` (keyed by commit id), and moves `HEAD` to the new tip.
+    ///
+    /// Trees, parents, authors, committers and dates are preserved, as are the
+    /// other messages byte for byte. Commits before the first changed message
+    /// keep their ids. The range must end at `HEAD`. Returns the id of the new
+    /// `HEAD` commit.
+    fn reword(&self, range: &str, messages: &HashMap<String, String>) -> Result<String>;
+}
+
+/// Creates the Git backend selected in the configuration.
+///
+/// `auto` prefers libgit2 when it was compiled in and falls back to the `git`
+/// binary otherwise.
+pub fn open_backend(kind: GitBackendKind) -> Result<Box<dyn GitBackend>> {
+    match kind {
+        GitBackendKind::Cli => Ok(Box::new(CliBackend::new("."))),
+        #[cfg(feature = "libgit2")]
+        GitBackendKind::Libgit2 => Ok(Box::new(LibGit2Backend::new("."))),
+        #[cfg(feature = "libgit2")]
+        GitBackendKind::Auto => {
+            let backend = LibGit2Backend::new(".");
+            if backend.is_repository() {
+                Ok(Box::new(backend))
+            } else {
+                debug!("libgit2 could not open the repository, using the git binary.");
+                Ok(Box::new(CliBackend::new(".")))
+            }
+        },
+        #[cfg(not(feature = "libgit2"))]
+        GitBackendKind::Auto => {
+            debug!("libgit2 support not compiled in, using the git binary.");
+            Ok(Box::new(CliBackend::new(".")))
+        },
+        #[cfg(not(feature = "libgit2"))]
+        GitBackendKind::Libgit2 => Err(Error::Config(
+            "git_backend 'libgit2' requires gitsc to be built with the 'libgit2' feature"
+                .to_string(),
+        )),
+    }
+}
+
+/// Gets the diff for `source` from the Git repository.
+///
+/// # Returns
+///
+/// * `Ok(Diff)` if there are changes.
+/// * `Err(Error::NoStagedChanges)` if there are no staged changes.
+/// * `Err(Error::NoChanges)` if any other source is empty.
+/// * `Err(Error::Git)` if the backend fails for other reasons.
+pub fn get_diff(backend: &dyn GitBackend, source: &DiffSource) -> Result<Diff> {
+    let diff = backend.diff(source)?;
+    debug!("Diff for {} touches {} file(s).", source, diff.files.len());
+
+    match source {
+        _ if !diff.is_empty() => Ok(diff),
+        DiffSource::Staged => Err(Error::NoStagedChanges),
+        other => Err(Error::NoChanges(other.to_string())),
     }
 }
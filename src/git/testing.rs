@@ -0,0 +1,165 @@
+//! Throwaway repositories for tests, built with libgit2 so they do not need
+//! a git binary.
+
+use git2::{IndexAddOption, Oid, Repository, RepositoryInitOptions, Signature, Time};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs, process};
+
+use super::GitBackend;
+
+/// The commit time of `commit`, in seconds since the Unix epoch.
+pub const DEFAULT_TIME: i64 = 1_704_067_200;
+
+/// A git repository in a temporary directory, removed on drop.
+pub struct TempRepo {
+    pub path: PathBuf,
+    pub repo: Repository,
+}
+
+impl TempRepo {
+    pub fn new() -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "gitsc-test-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        let repo = Repository::init_opts(&path, RepositoryInitOptions::new().initial_head("main"))
+            .unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        config.set_bool("commit.gpgsign", false).unwrap();
+        Self { path, repo }
+    }
+
+    /// Sets a value in the repository's config.
+    pub fn config(&self, key: &str, value: &str) {
+        self.repo.config().unwrap().set_str(key, value).unwrap();
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>, content: impl AsRef<[u8]>) {
+        let path = self.path.join(path);
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir).unwrap();
+        }
+        fs::write(path, content).unwrap();
+    }
+
+    /// Stages every change in the working tree, like `git add -A`.
+    pub fn add_all(&self) {
+        let mut index = self.repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.update_all(["*"], None).unwrap();
+        index.write().unwrap();
+    }
+
+    /// Stages everything and commits it, returning the new commit id.
+    pub fn commit(&self, message: &str) -> String {
+        self.commit_at(message, DEFAULT_TIME)
+    }
+
+    /// Like `commit`, with the author and committer dates set to `time`.
+    pub fn commit_at(&self, message: &str, time: i64) -> String {
+        let head = self
+            .repo
+            .refname_to_id("HEAD")
+            .ok()
+            .map(|id| id.to_string());
+        self.commit_on("HEAD", message, time, head.as_deref().as_slice())
+    }
+
+    /// Stages everything and commits it with `parents`, moving `reference`
+    /// to the new commit. Branches other than the current one are not
+    /// checked out.
+    pub fn commit_on(&self, reference: &str, message: &str, time: i64, parents: &[&str]) -> String {
+        self.add_all();
+        let tree_id = self.repo.index().unwrap().write_tree().unwrap();
+        let tree = self.repo.find_tree(tree_id).unwrap();
+        let signature = Signature::new("Test", "test@example.com", &Time::new(time, 0)).unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|id| {
+                self.repo
+                    .revparse_single(id)
+                    .unwrap()
+                    .peel_to_commit()
+                    .unwrap()
+            })
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        self.repo
+            .commit(Some(reference), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+            .to_string()
+    }
+
+    /// Creates a branch at `HEAD`.
+    pub fn branch(&self, name: &str) {
+        let head = self.repo.head().unwrap().peel_to_commit().unwrap();
+        self.repo.branch(name, &head, true).unwrap();
+    }
+}
+
+impl Drop for TempRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Whether a `git` binary is installed, for the tests of `CliBackend`.
+pub fn has_git() -> bool {
+    Command::new("git")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Checks how a backend rewords the commits of `repo`: only the history from
+/// the first changed message on is rewritten, and other messages are kept
+/// byte for byte.
+pub fn check_reword(repo: &TempRepo, git: &dyn GitBackend) {
+    let raw_message = |id: &str| {
+        let commit = repo.repo.find_commit(Oid::from_str(id).unwrap()).unwrap();
+        commit.message_raw().unwrap().to_string()
+    };
+    let base = repo.commit("base");
+    repo.write("a.txt", "1");
+    let first = repo.commit("first\n\ntrailing space  \n\n");
+    repo.write("a.txt", "2");
+    let second = repo.commit("second");
+    repo.write("a.txt", "3");
+    let third = repo.commit("third\n\nbody  \n");
+    let range = format!("{}..HEAD", base);
+
+    assert!(git.check_reword_range(&base).is_err());
+    assert!(
+        git.check_reword_range(&format!("{}..{}", base, second))
+            .is_err()
+    );
+    assert!(git.check_reword_range(&range).is_ok());
+
+    // An unchanged message rewrites nothing.
+    let unchanged = HashMap::from([(first.clone(), "first\n\ntrailing space".to_string())]);
+    assert_eq!(git.reword(&range, &unchanged).unwrap(), third);
+
+    let messages = HashMap::from([(second.clone(), "second, reworded".to_string())]);
+    let head = git.reword(&range, &messages).unwrap();
+    let ids: Vec<String> = git
+        .commits(&range)
+        .unwrap()
+        .into_iter()
+        .map(|commit| commit.id)
+        .collect();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids[0], first);
+    assert_ne!(ids[1], second);
+    assert_eq!(ids[2], head);
+    assert_eq!(raw_message(&ids[0]), "first\n\ntrailing space  \n\n");
+    assert_eq!(raw_message(&ids[1]), "second, reworded");
+    assert_eq!(raw_message(&ids[2]), "third\n\nbody  \n");
+}
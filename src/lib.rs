@@ -1,8 +1,19 @@
 pub mod ai;
 pub mod analyzer;
+pub mod changelog;
 pub mod cli;
 pub mod config;
 pub mod error;
+pub mod examples;
 pub mod formatter;
 pub mod git;
 pub mod logger;
+pub mod pipeline;
+pub mod pr;
+pub mod reword;
+pub mod squash;
+pub mod ticket;
+pub mod usage;
+pub mod version;
+
+pub use pipeline::{GeneratedCommit, Generator};
@@ -10,5 +10,6 @@ pub fn init(debug_mode: bool) {
         builder.filter_level(LevelFilter::Info);
     }
 
-    builder.target(Target::Stdout).init();
+    // Keep stdout for the output itself, so `$(gitsc)` captures only the message.
+    builder.target(Target::Stderr).init();
 }
@@ -1,190 +1,719 @@
 use clap::Parser;
-use gitsc::ai::cache::sqlite_cache::SqliteCache;
-use gitsc::ai::cache::{CacheRepository, diff_hasher};
-use gitsc::ai::providers::{GeminiProvider, OllamaProvider};
-use gitsc::ai::repository::AIProvider;
-use gitsc::cli::Cli;
-use gitsc::config::load_config;
+use commands::TokenPrinter;
+use gitsc::ai::cache::{PrunePolicy, create_cache, format_size, server};
+use gitsc::ai::providers::ollama::DEFAULT_OLLAMA_URL;
+use gitsc::ai::providers::openai::DEFAULT_OPENAI_URL;
+use gitsc::ai::providers::{OllamaProvider, create_provider};
+use gitsc::ai::repository::CommitMessage;
+use gitsc::changelog::format_date;
+use gitsc::cli::{
+    CacheArgs, CacheCommand, Cli, Commands, ConfigArgs, ConfigCommand, NextVersionArgs, UsageArgs,
+    ask, confirm,
+};
+use gitsc::config::validate::{PROVIDERS, did_you_mean};
+use gitsc::config::{
+    CacheBackendKind, Config, Severity, json_schema, load_layered, parse_value, set_key,
+    user_config_path,
+};
 use gitsc::error::Error;
 use gitsc::formatter::format_commit_message;
-use gitsc::git::{get_staged_diff, is_git_repository};
-use gitsc::{analyzer, logger};
-use log::{debug, error, info};
-use serde_json;
-use std::fs;
-use std::time::Instant;
+use gitsc::git::{GitBackend, open_backend};
+use gitsc::pipeline::{Origin, open_cache, open_usage_log};
+use gitsc::usage::{UsageLog, UsageTotals};
+use gitsc::version::{
+    Bump, latest_tags, next_version, required_bump, write_cargo_toml, write_package_json,
+};
+use gitsc::{Generator, logger};
+use log::{debug, error, info, warn};
+use serde_yaml::{Mapping, Value};
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{env, fs};
+
+mod commands;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     let cli = Cli::parse();
     logger::init(cli.debug);
 
-    let config = load_config()?;
+    if let Some(Commands::Config(args)) = &cli.command {
+        return config_cmd(&cli, args).await;
+    }
+    let layered = load_layered(&cli.config_overrides())?;
+    if layered.files().is_empty() {
+        debug!("No config file found; using the defaults. Run `gitsc config init` to create one.");
+    }
+    let config = match layered.build() {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        },
+    };
+    match &cli.command {
+        Some(Commands::Cache(args)) => return cache_cmd(&config, args).await,
+        Some(Commands::Usage(args)) => return usage_cmd(&config, args).await,
+        _ => {},
+    }
+
+    let git = open_backend(config.git_backend.unwrap_or_default())?;
 
-    if !is_git_repository() {
+    if !git.is_repository() {
         error!("Error: Not a Git repository.");
         std::process::exit(1);
     }
 
-    let diff = match get_staged_diff() {
-        Ok(d) => d,
-        Err(Error::NoStagedChanges) => {
-            info!("No staged changes found.");
-            return Ok(());
+    let cache = open_cache(&config).await?;
+    let usage_log = open_usage_log(&config).await;
+    let generator = Generator::new(&config, git.as_ref())
+        .with_cache(cache.as_deref())
+        .with_usage_log(usage_log.as_ref())
+        .with_diff_source(cli.diff_source());
+
+    let result = match &cli.command {
+        Some(Commands::Reword(args)) => {
+            commands::reword::run(&config, git.as_ref(), &generator, args).await
+        },
+        Some(Commands::SquashMsg(args)) => {
+            commands::squash::run(&config, git.as_ref(), &generator, args).await
+        },
+        Some(Commands::Pr(args)) => {
+            commands::pr::run(&config, git.as_ref(), &generator, args).await
+        },
+        Some(Commands::Changelog(args)) => {
+            commands::changelog::run(&config, git.as_ref(), &generator, args).await
+        },
+        Some(Commands::NextVersion(args)) => next_version_cmd(git.as_ref(), args),
+        Some(Commands::Cache(_) | Commands::Usage(_) | Commands::Config(_)) => {
+            unreachable!("handled before opening the repository")
+        },
+        None => generate(&config, &generator).await,
+    };
+    if let Err(e) = result {
+        error!("Error: {}", e);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// The exit code when the message was printed but comes from the heuristic
+/// fallback rather than the configured provider.
+const FALLBACK_EXIT_CODE: i32 = 2;
+
+/// Generates a commit message for the diff source selected on the command
+/// line and prints it.
+async fn generate(config: &Config, generator: &Generator<'_>) -> Result<(), Error> {
+    let result = if TokenPrinter::enabled(config) {
+        let mut printer = TokenPrinter::default();
+        let result = generator
+            .generate_streaming(&mut |token: &str| printer.print(token))
+            .await;
+        printer.finish();
+        result
+    } else {
+        generator.generate().await
+    };
+
+    match result {
+        Ok(commit) => {
+            println!("{}", commit.render());
+            // Let scripts tell a guess from a real answer.
+            if commit.origin == Origin::Fallback {
+                std::process::exit(FALLBACK_EXIT_CODE);
+            }
         },
+        Err(e @ (Error::NoStagedChanges | Error::NoChanges(_))) => info!("{}", e),
         Err(e) => {
-            error!("An error occurred: {}", e);
+            error!("Error generating commit message: {}", e);
             std::process::exit(1);
         },
+    }
+
+    Ok(())
+}
+
+/// Prints the next semantic version implied by the commits since the last
+/// release tag.
+fn next_version_cmd(git: &dyn GitBackend, args: &NextVersionArgs) -> Result<(), Error> {
+    let (latest, stable) = latest_tags(&git.reachable_tags()?);
+    let range = match &stable {
+        Some(tag) => format!("{}..HEAD", tag.name),
+        None => "HEAD".to_string(),
     };
+    let current = stable
+        .as_ref()
+        .map(|t| t.version.clone())
+        .unwrap_or_else(|| semver::Version::new(0, 0, 0));
 
-    let processed_diff = analyzer::analyze_diff(&diff, &config.smart_commit);
+    let commits = git.commits(&range)?;
+    let (bump, reasons) = required_bump(&commits);
 
-    let cache: Option<Box<dyn CacheRepository + Send + Sync>> = if config
-        .cache_enabled
-        .unwrap_or(false)
-    {
-        if let Some(cache_path_str) = &config.cache_path {
-            let expanded_cache_path = if let Some(s) = cache_path_str.to_str() {
-                if s.starts_with("~/") {
-                    let home_dir = dirs::home_dir().ok_or_else(|| {
-                        Error::Config(
-                            "Could not find home directory for cache path expansion".to_string(),
-                        )
-                    })?;
-                    home_dir.join(&s[2..])
-                } else {
-                    cache_path_str.clone()
-                }
-            } else {
-                cache_path_str.clone()
-            };
+    if bump == Bump::None {
+        println!("{}", current);
+        if !args.quiet {
+            info!(
+                "No releasable commits since {}.",
+                stable.as_ref().map_or("the start", |t| &t.name)
+            );
+        }
+        return Ok(());
+    }
 
-            if let Some(parent_dir) = expanded_cache_path.parent() {
-                fs::create_dir_all(parent_dir)?;
-            }
+    let next =
+        next_version(&current, bump, args.pre.as_deref(), latest.as_ref().map(|t| &t.version))?;
 
-            match SqliteCache::new(&expanded_cache_path).await {
-                Ok(c) => {
-                    debug!("SQLite cache initialized successfully at {:?}", expanded_cache_path);
-                    Some(Box::new(c))
-                },
-                Err(e) => {
-                    error!("Failed to initialize SQLite cache at {:?}: {}", expanded_cache_path, e);
-                    None
+    println!("{}", next);
+    if !args.quiet {
+        println!("{} bump from {}:", bump, current);
+        for reason in &reasons {
+            println!("  {:<5}  {} ({})", reason.bump, reason.summary, reason.id);
+        }
+    }
+
+    if args.write {
+        let root = git.root()?;
+        let cargo_toml = root.join("Cargo.toml");
+        let package_json = root.join("package.json");
+        if cargo_toml.exists() {
+            write_cargo_toml(&cargo_toml, &next)?;
+            info!("Updated version in {}", cargo_toml.display());
+        }
+        if package_json.exists() {
+            write_package_json(&package_json, &next)?;
+            info!("Updated version in {}", package_json.display());
+        }
+        if !cargo_toml.exists() && !package_json.exists() {
+            error!("Error: No Cargo.toml or package.json found at {}.", root.display());
+            std::process::exit(1);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates, inspects and changes the configuration. The layers are only
+/// loaded to read them, so `init` and `edit` can fix a file that does not
+/// parse.
+async fn config_cmd(cli: &Cli, args: &ConfigArgs) -> Result<(), Error> {
+    match &args.command {
+        ConfigCommand::Show { origin } => show_config(cli, *origin)?,
+        ConfigCommand::Get { key } => {
+            let layered = load_layered(&cli.config_overrides())?;
+            let Some(value) = layered.get(key) else {
+                error!("Error: `{}` is not set.", key);
+                std::process::exit(1);
+            };
+            let mut value = value.clone();
+            mask_secrets(&mut value);
+            match value {
+                Value::Mapping(_) | Value::Sequence(_) => {
+                    let yaml = serde_yaml::to_string(&value).map_err(|e| {
+                        Error::Config(format!("Failed to render the config: {}", e))
+                    })?;
+                    print!("{}", yaml);
                 },
+                value => println!("{}", render_value(&value)),
             }
-        } else {
-            error!("Cache enabled but no cache_path provided in config.");
-            None
+        },
+        ConfigCommand::Set { key, value } => set_config(key, value)?,
+        ConfigCommand::Edit => edit_config()?,
+        ConfigCommand::Schema => {
+            let schema = serde_json::to_string_pretty(&json_schema())
+                .map_err(|e| Error::Config(format!("Failed to render the schema: {}", e)))?;
+            println!("{}", schema);
+        },
+        ConfigCommand::Init { force } => init_config(*force).await?,
+    }
+    Ok(())
+}
+
+/// Prints the merged configuration, or every value with the layer it came
+/// from.
+fn show_config(cli: &Cli, origin: bool) -> Result<(), Error> {
+    let layered = load_layered(&cli.config_overrides())?;
+    if origin {
+        let rows: Vec<(&String, String, String)> = layered
+            .origins()
+            .iter()
+            .map(|(key, origin)| {
+                let value = layered.get(key).map_or(String::new(), |value| {
+                    if is_secret(key) {
+                        SECRET_MASK.to_string()
+                    } else {
+                        render_value(value)
+                    }
+                });
+                (key, value, origin.to_string())
+            })
+            .collect();
+        let key_width = rows.iter().map(|(key, _, _)| key.len()).max().unwrap_or(0);
+        let value_width = rows
+            .iter()
+            .map(|(_, value, _)| value.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (key, value, origin) in rows {
+            println!("{:<key_width$}  {:<value_width$}  {}", key, value, origin);
         }
     } else {
-        debug!("Cache not enabled in config, skipping cache initialization.");
-        None
+        let mut value = layered.value().clone();
+        mask_secrets(&mut value);
+        let yaml = serde_yaml::to_string(&value)
+            .map_err(|e| Error::Config(format!("Failed to render the config: {}", e)))?;
+        print!("{}", yaml);
+    }
+    Ok(())
+}
+
+/// Sets `key` in the user config. The previous file is put back if the
+/// result does not load.
+fn set_config(key: &str, raw: &str) -> Result<(), Error> {
+    let path = user_config_path()?;
+    let original = match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    let mut tree = match &original {
+        Some(content) => serde_yaml::from_str(content)
+            .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))?,
+        None => Value::Null,
     };
+    if tree.is_null() {
+        tree = Value::Mapping(Mapping::new());
+    }
+    set_key(&mut tree, key, parse_value(key, raw));
+    write_config(&path, &tree)?;
 
-    let diff_hash = diff_hasher::generate_diff_hash(&processed_diff);
-    let mut commit_message: Option<gitsc::ai::repository::CommitMessage> = None;
-
-    if let Some(c) = &cache {
-        match c.get(&diff_hash).await {
-            Ok(Some(cached_message_str)) => {
-                debug!("Cache hit for diff hash: {}", diff_hash);
-                match serde_json::from_str(&cached_message_str) {
-                    Ok(msg) => {
-                        commit_message = Some(msg);
-                        debug!("Commit message retrieved from cache.");
-                    },
-                    Err(e) => {
-                        error!("Failed to deserialize cached commit message: {}", e);
-                    },
-                }
-            },
-            Ok(None) => {
-                debug!("Cache miss for diff hash: {}", diff_hash);
-            },
-            Err(e) => {
-                error!("Error retrieving from Redis cache: {}", e);
+    if let Err(e) = load_layered(&[]).and_then(|layered| layered.build()) {
+        match original {
+            Some(content) => fs::write(&path, content)?,
+            None => fs::remove_file(&path)?,
+        }
+        error!("{}", e);
+        error!("Error: {} was left unchanged.", path.display());
+        std::process::exit(1);
+    }
+    info!("Set {} in {}.", key, path.display());
+    Ok(())
+}
+
+/// Opens the user config in the user's editor, creating it first if needed,
+/// and checks the result.
+fn edit_config() -> Result<(), Error> {
+    let path = user_config_path()?;
+    if !path.exists() {
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)?;
+        }
+        fs::write(&path, "# Run `gitsc config schema` for every setting.\n")?;
+    }
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // The editor may carry arguments, e.g. `code --wait`, so leave the
+    // splitting to the shell.
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        error!("Error: `{}` exited with {}.", editor, status);
+        std::process::exit(1);
+    }
+    if let Err(e) = load_layered(&[]).and_then(|layered| layered.build()) {
+        error!("{}", e);
+        error!("Run `gitsc config edit` again to fix it.");
+        std::process::exit(1);
+    }
+    info!("{} is valid.", path.display());
+    Ok(())
+}
+
+/// Walks through creating the user config: picks a provider and a model,
+/// checks the result and sends the provider a test prompt before writing it.
+async fn init_config(force: bool) -> Result<(), Error> {
+    let path = user_config_path()?;
+    if path.exists()
+        && !force
+        && !confirm(&format!("{} already exists. Replace it?", path.display()))?
+    {
+        return Ok(());
+    }
+
+    println!("Providers: {}", PROVIDERS.join(", "));
+    let provider = loop {
+        let answer = ask("Provider", "ollama")?;
+        if PROVIDERS.contains(&answer.as_str()) {
+            break answer;
+        }
+        match did_you_mean(&answer, PROVIDERS) {
+            Some(candidate) => {
+                println!("`{}` is not a provider. Did you mean `{}`?", answer, candidate)
             },
+            None => println!("`{}` is not a provider.", answer),
         }
+    };
+
+    let mut tree = Value::Mapping(Mapping::new());
+    set_key(&mut tree, "provider", provider.as_str().into());
+    match provider.as_str() {
+        "ollama" => {
+            let url = ask("Ollama URL", DEFAULT_OLLAMA_URL)?;
+            let model = match OllamaProvider::list_models(&url).await {
+                Ok(models) if !models.is_empty() => choose_model(&models)?,
+                Ok(_) => {
+                    warn!("No models are installed on {}. Install one with `ollama pull`.", url);
+                    ask("Model", "llama3")?
+                },
+                Err(e) => {
+                    warn!("Could not reach Ollama at {}: {}", url, e);
+                    ask("Model", "llama3")?
+                },
+            };
+            if url != DEFAULT_OLLAMA_URL {
+                set_key(&mut tree, "ollama_url", url.into());
+            }
+            set_key(&mut tree, "model", model.into());
+        },
+        "gemini" => {
+            let model = ask("Model", &Config::default().model)?;
+            set_key(&mut tree, "model", model.into());
+            if env::var("GEMINI_API_KEY").is_err() {
+                warn!("GEMINI_API_KEY is not set; gitsc reads the key from it.");
+            }
+        },
+        "openai" => {
+            let url = ask("API URL", DEFAULT_OPENAI_URL)?;
+            let model = ask("Model", "gpt-4o-mini")?;
+            let api_key = ask("API key (empty to read OPENAI_API_KEY)", "")?;
+            if url != DEFAULT_OPENAI_URL {
+                set_key(&mut tree, "openai_url", url.into());
+            }
+            set_key(&mut tree, "model", model.into());
+            if !api_key.is_empty() {
+                set_key(&mut tree, "api_key", api_key.into());
+            }
+        },
+        "exec" => {
+            let command = ask("Command", "")?;
+            set_key(&mut tree, "exec.command", command.into());
+        },
+        _ => {},
+    }
+    if confirm("Cache generated messages?")? {
+        set_key(&mut tree, "cache_enabled", true.into());
     }
 
-    if commit_message.is_none() {
-        let provider = match config.provider.as_str() {
-            "gemini" => GeminiProvider::new(config.model.clone())
-                .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>),
-            "ollama" => {
-                let ollama_url = config
-                    .ollama_url
-                    .clone()
-                    .ok_or_else(|| Error::Config("Ollama URL not configured".to_string()))?;
-                OllamaProvider::new(ollama_url, config.model.clone())
-                    .map(|p| Box::new(p) as Box<dyn AIProvider + Send + Sync>)
+    let config: Config = serde_yaml::from_value(tree.clone())
+        .map_err(|e| Error::Config(format!("Failed to build the config: {}", e)))?;
+    let mut failed = false;
+    for problem in config.validate() {
+        match problem.severity {
+            Severity::Error => {
+                failed = true;
+                error!("{}: {}\n  {}", problem.key, problem.message, problem.suggestion);
             },
-            _ => {
-                error!("Error: Unsupported AI provider '{}'", config.provider);
-                std::process::exit(1);
+            Severity::Warning => {
+                warn!("{}: {}\n  {}", problem.key, problem.message, problem.suggestion)
             },
-        };
+        }
+    }
+    if failed {
+        error!("Error: {} was not written.", path.display());
+        std::process::exit(1);
+    }
 
-        match provider {
-            Ok(p) => {
-                debug!("Diff size: {} bytes", processed_diff.len());
-                debug!(
-                    "First 20 lines of diff:\n{}",
-                    processed_diff
-                        .lines()
-                        .take(20)
-                        .collect::<Vec<&str>>()
-                        .join("\n")
-                );
-                debug!("Calling AI provider to analyze diff...");
-                let start_time = Instant::now();
-                match p.analyze_diff(&processed_diff).await {
-                    Ok(msg) => {
-                        let duration = start_time.elapsed();
-                        debug!("AI provider responded in {:?}", duration);
-                        if let Some(c) = &cache {
-                            match serde_json::to_string(&msg) {
-                                Ok(msg_str) => {
-                                    if let Err(e) = c.set(&diff_hash, &msg_str).await {
-                                        error!(
-                                            "Failed to set cache for diff hash {}: {}",
-                                            diff_hash, e
-                                        );
-                                    }
-                                },
-                                Err(e) => {
-                                    error!("Failed to serialize commit message for caching: {}", e);
-                                },
-                            }
-                        }
-                        commit_message = Some(msg);
-                    },
-                    Err(e) => {
-                        error!("Error generating commit message: {}", e);
-                        std::process::exit(1);
-                    },
-                }
-            },
+    if provider != "heuristic" {
+        info!("Sending {} a test prompt...", provider);
+        let result = match create_provider(&config) {
+            Ok(provider) => provider.complete("Reply with the word OK.").await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => info!("{} answered.", provider),
             Err(e) => {
-                error!("Error creating AI provider: {}", e);
-                std::process::exit(1);
+                warn!("{} did not answer: {}", provider, e);
+                if !confirm("Save the config anyway?")? {
+                    return Ok(());
+                }
             },
         }
     }
 
-    if let Some(msg) = commit_message {
-        let formatted_commit = format_commit_message(&msg, &config.commit_format);
-        if cli.debug {
-            info!("{}", formatted_commit);
-        } else {
-            println!("{}", formatted_commit);
+    write_config(&path, &tree)?;
+    info!("Wrote {}.", path.display());
+    Ok(())
+}
+
+/// Lists the installed models and reads a choice, by number or by name.
+fn choose_model(models: &[String]) -> Result<String, Error> {
+    for (index, model) in models.iter().enumerate() {
+        println!("  {}) {}", index + 1, model);
+    }
+    let answer = ask("Model", &models[0])?;
+    Ok(match answer.parse::<usize>() {
+        Ok(number) if (1..=models.len()).contains(&number) => models[number - 1].clone(),
+        _ => answer,
+    })
+}
+
+fn write_config(path: &Path, tree: &Value) -> Result<(), Error> {
+    let yaml = serde_yaml::to_string(tree)
+        .map_err(|e| Error::Config(format!("Failed to render the config: {}", e)))?;
+    if let Some(parent_dir) = path.parent() {
+        fs::create_dir_all(parent_dir)?;
+    }
+    fs::write(path, yaml)?;
+    Ok(())
+}
+
+const SECRET_MASK: &str = "********";
+
+/// Keys whose values are never printed.
+fn is_secret(key: &str) -> bool {
+    matches!(key.rsplit('.').next(), Some("api_key" | "token"))
+}
+
+fn mask_secrets(value: &mut serde_yaml::Value) {
+    if let serde_yaml::Value::Mapping(mapping) = value {
+        for (key, value) in mapping.iter_mut() {
+            if key.as_str().is_some_and(is_secret) && !value.is_mapping() {
+                *value = SECRET_MASK.into();
+            } else {
+                mask_secrets(value);
+            }
         }
-    } else {
-        error!("Failed to generate or retrieve commit message.");
-        std::process::exit(1);
     }
+}
+
+/// Renders a scalar as it would be written in YAML, and anything else inline.
+fn render_value(value: &serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(text) => text.clone(),
+        value => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
 
+/// Prints the tokens used per provider and model since `args.since`, with the
+/// estimated cost of the models that have a price.
+async fn usage_cmd(config: &Config, args: &UsageArgs) -> Result<(), Error> {
+    let log = match UsageLog::from_config(config).await {
+        Ok(Some(log)) => log,
+        Ok(None) => {
+            info!("Usage recording is disabled (usage.enabled: false).");
+            return Ok(());
+        },
+        Err(e) => {
+            error!("Failed to open the usage log: {}", e);
+            std::process::exit(1);
+        },
+    };
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+        .saturating_sub(args.since.as_secs());
+    let totals = log.totals(since).await?;
+    if totals.is_empty() {
+        info!("No usage recorded since {}.", format_date(since as i64));
+        return Ok(());
+    }
+
+    let prices = config
+        .usage
+        .as_ref()
+        .map(|usage| usage.prices.clone())
+        .unwrap_or_default();
+    let format_cost = |cost: Option<f64>| cost.map_or("n/a".to_string(), |c| format!("${:.4}", c));
+    println!("Usage since {}:", format_date(since as i64));
+    println!(
+        "{:<10} {:<28} {:>8} {:>12} {:>12} {:>10}",
+        "PROVIDER", "MODEL", "REQUESTS", "PROMPT", "COMPLETION", "COST"
+    );
+    let mut sum = UsageTotals {
+        provider: String::new(),
+        model: String::new(),
+        requests: 0,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+    };
+    let mut total_cost = None;
+    for row in &totals {
+        let cost = row.cost(&prices);
+        println!(
+            "{:<10} {:<28} {:>8} {:>12} {:>12} {:>10}",
+            row.provider,
+            row.model,
+            row.requests,
+            row.prompt_tokens,
+            row.completion_tokens,
+            format_cost(cost)
+        );
+        sum.requests += row.requests;
+        sum.prompt_tokens += row.prompt_tokens;
+        sum.completion_tokens += row.completion_tokens;
+        if let Some(cost) = cost {
+            total_cost = Some(total_cost.unwrap_or(0.0) + cost);
+        }
+    }
+    println!(
+        "{:<39} {:>8} {:>12} {:>12} {:>10}",
+        "TOTAL",
+        sum.requests,
+        sum.prompt_tokens,
+        sum.completion_tokens,
+        format_cost(total_cost)
+    );
+    if total_cost.is_some() && totals.iter().any(|row| row.cost(&prices).is_none()) {
+        println!("Models without a price in usage.prices are not included in the cost.");
+    }
     Ok(())
 }
+
+/// Inspects or cleans up the response cache.
+async fn cache_cmd(config: &Config, args: &CacheArgs) -> Result<(), Error> {
+    let cache = match create_cache(config).await {
+        Ok(cache) => cache,
+        Err(e) => {
+            error!("Failed to open the cache: {}", e);
+            std::process::exit(1);
+        },
+    };
+
+    match &args.command {
+        CacheCommand::Stats => {
+            let stats = cache.stats().await?;
+            println!("Entries:  {} ({} expired)", stats.entries, stats.expired);
+            println!("Size:     {}", format_size(stats.bytes));
+            println!("Hits:     {}", stats.hits);
+            match stats.hit_rate() {
+                Some(rate) => {
+                    println!("Misses:   {} (hit rate {:.0}%)", stats.misses, rate * 100.0)
+                },
+                None => println!("Misses:   {}", stats.misses),
+            }
+            if let (Some(oldest), Some(newest)) = (stats.oldest, stats.newest) {
+                println!("Oldest:   {}", format_date(oldest as i64));
+                println!("Newest:   {}", format_date(newest as i64));
+            }
+        },
+        CacheCommand::List => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            for entry in cache.list().await? {
+                let summary = match serde_json::from_str::<CommitMessage>(&entry.value) {
+                    Ok(msg) => format_commit_message(&msg, &config.commit_format),
+                    Err(_) => format!("<{}>", format_size(entry.value.len() as u64)),
+                };
+                println!(
+                    "{}  {:>4}{}  {}",
+                    entry.key,
+                    format_age(now.saturating_sub(entry.timestamp)),
+                    if entry.expired { "*" } else { " " },
+                    summary
+                );
+            }
+        },
+        CacheCommand::Show { key } => {
+            let entry = match cache.entry(key).await? {
+                Some(entry) => Some(entry),
+                None => {
+                    let mut matches: Vec<_> = cache
+                        .list()
+                        .await?
+                        .into_iter()
+                        .filter(|e| e.key.starts_with(key.as_str()))
+                        .collect();
+                    if matches.len() > 1 {
+                        error!("Error: '{}' matches {} cache keys.", key, matches.len());
+                        std::process::exit(1);
+                    }
+                    matches.pop()
+                },
+            };
+            let Some(entry) = entry else {
+                error!("Error: No cache entry found for '{}'.", key);
+                std::process::exit(1);
+            };
+
+            println!("Key:      {}", entry.key);
+            if let Some(created_at) = entry.created_at {
+                println!("Created:  {}", format_date(created_at as i64));
+            }
+            println!(
+                "Stored:   {}{}",
+                format_date(entry.timestamp as i64),
+                if entry.expired { " (expired)" } else { "" }
+            );
+            if let Some(provider) = &entry.metadata.provider {
+                println!(
+                    "Model:    {} {}",
+                    provider,
+                    entry.metadata.model.as_deref().unwrap_or("")
+                );
+            }
+            if !entry.metadata.candidates.is_empty() {
+                println!("Scopes:   {}", entry.metadata.candidates.join(", "));
+            }
+            println!("Hits:     {}", entry.hit_count);
+            println!();
+            match serde_json::from_str::<serde_json::Value>(&entry.value) {
+                Ok(value) => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&value).map_err(std::io::Error::from)?
+                ),
+                Err(_) => println!("{}", entry.value),
+            }
+        },
+        CacheCommand::Purge { yes } => {
+            if !yes && !confirm("Remove every cache entry?")? {
+                info!("Aborted, cache left unchanged.");
+                return Ok(());
+            }
+            let removed = cache.clear().await?;
+            info!("Removed {} cache entries.", removed);
+        },
+        CacheCommand::Prune {
+            older_than,
+            max_size,
+        } => {
+            let defaults = PrunePolicy::from_config(config)?;
+            let policy = PrunePolicy {
+                max_age: older_than.or(defaults.max_age),
+                max_bytes: max_size.or(defaults.max_bytes),
+            };
+            let removed = cache.prune(&policy).await?;
+            info!("Removed {} cache entries.", removed);
+        },
+        CacheCommand::Serve { addr, token } => {
+            let cache_config = config.cache.as_ref();
+            if cache_config.and_then(|c| c.backend) == Some(CacheBackendKind::Http) {
+                return Err(Error::Config(
+                    "The http cache backend cannot be served; use sqlite instead.".to_string(),
+                ));
+            }
+            let token = token
+                .clone()
+                .or_else(|| cache_config.and_then(|c| c.token.clone()));
+            server::serve(cache, *addr, token, PrunePolicy::from_config(config)?).await?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Formats an age in seconds with its largest unit, e.g. `5m` or `3d`.
+fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3_600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h", s / 3_600),
+        s => format!("{}d", s / 86_400),
+    }
+}
@@ -0,0 +1,643 @@
+//! The commit message pipeline: read a diff, build the prompt, ask the cache
+//! or the provider, and attach tickets. The `gitsc` binary is a thin layer
+//! over it, and other tools can embed it:
+//!
+//! ```no_run
+//! # async fn run() -> gitsc::error::Result<()> {
+//! let config = gitsc::config::load_config()?;
+//! let git = gitsc::git::open_backend(config.git_backend.unwrap_or_default())?;
+//! let commit = gitsc::Generator::new(&config, git.as_ref())
+//!     .with_diff_source(gitsc::git::DiffSource::All)
+//!     .generate()
+//!     .await?;
+//! println!("{}", commit.render());
+//! # Ok(())
+//! # }
+//! ```
+
+use log::{debug, error, warn};
+use std::time::Instant;
+
+use crate::ai::cache::{
+    CacheKey, CacheRepository, EntryMetadata, PrunePolicy, configured_backend, create_cache,
+    text_key,
+};
+use crate::ai::prompt::Prompt;
+use crate::ai::providers::{HeuristicProvider, create_provider};
+use crate::ai::repository::{AIProvider, CommitMessage, TokenSink};
+use crate::analyzer::{self, ScopeResolver};
+use crate::config::{CacheBackendKind, Config, ScopeConfig, TicketConfig};
+use crate::error::{Error, Result};
+use crate::examples;
+use crate::formatter::{format_commit_message, format_full_commit_message};
+use crate::git::{Diff, DiffSource, GitBackend, get_diff};
+use crate::ticket::{self, TicketExtractor};
+use crate::usage::{self, UsageLog};
+
+pub type Cache = Box<dyn CacheRepository + Send + Sync>;
+
+/// Where a generated message came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// A previous response for the same prompt.
+    Cache,
+    /// The configured provider.
+    Provider,
+    /// The `heuristic` provider, after the configured one failed.
+    Fallback,
+}
+
+/// A generated commit message, ready to print.
+#[derive(Debug, Clone)]
+pub struct GeneratedCommit {
+    /// The message, with ticket keys attached as configured.
+    pub message: CommitMessage,
+    /// `commit_format` with the `{ticket}` placeholder filled in.
+    pub template: String,
+    pub origin: Origin,
+}
+
+impl GeneratedCommit {
+    /// Formats the header, plus the trailers if tickets added any: they are
+    /// the only ones worth printing for a single-line message.
+    pub fn render(&self) -> String {
+        if self.message.footers.is_empty() {
+            format_commit_message(&self.message, &self.template)
+        } else {
+            format_full_commit_message(&self.message, &self.template)
+        }
+    }
+}
+
+/// Generates commit messages for a repository. Without `with_provider`, the
+/// provider selected in the configuration is used, without `with_cache`
+/// nothing is cached, and without `with_usage_log` no usage is recorded.
+pub struct Generator<'a> {
+    config: &'a Config,
+    git: &'a dyn GitBackend,
+    cache: Option<&'a (dyn CacheRepository + Send + Sync)>,
+    usage_log: Option<&'a UsageLog>,
+    provider: Option<CustomProvider>,
+    source: DiffSource,
+}
+
+/// A provider given to `with_provider`, with the name and model its messages
+/// are cached and its usage recorded under.
+struct CustomProvider {
+    name: String,
+    model: String,
+    provider: Box<dyn AIProvider + Send + Sync>,
+}
+
+impl<'a> Generator<'a> {
+    pub fn new(config: &'a Config, git: &'a dyn GitBackend) -> Self {
+        Self {
+            config,
+            git,
+            cache: None,
+            usage_log: None,
+            provider: None,
+            source: DiffSource::default(),
+        }
+    }
+
+    pub fn with_cache(mut self, cache: Option<&'a (dyn CacheRepository + Send + Sync)>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Records the tokens of every response in `usage_log`.
+    pub fn with_usage_log(mut self, usage_log: Option<&'a UsageLog>) -> Self {
+        self.usage_log = usage_log;
+        self
+    }
+
+    /// Uses `provider` instead of the configured one. `name` and `model`
+    /// identify it in the cache and the usage log, so they should change
+    /// whenever its answers would.
+    pub fn with_provider(
+        mut self,
+        name: impl Into<String>,
+        model: impl Into<String>,
+        provider: Box<dyn AIProvider + Send + Sync>,
+    ) -> Self {
+        self.provider = Some(CustomProvider {
+            name: name.into(),
+            model: model.into(),
+            provider,
+        });
+        self
+    }
+
+    /// Selects the changes `generate` describes. Defaults to the staged ones.
+    pub fn with_diff_source(mut self, source: DiffSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Describes the selected changes.
+    pub async fn generate(&self) -> Result<GeneratedCommit> {
+        self.generate_inner(None).await
+    }
+
+    /// Like `generate`, but passes the response to `on_token` as it arrives.
+    pub async fn generate_streaming(&self, on_token: TokenSink<'_>) -> Result<GeneratedCommit> {
+        self.generate_inner(Some(on_token)).await
+    }
+
+    async fn generate_inner(&self, on_token: Option<TokenSink<'_>>) -> Result<GeneratedCommit> {
+        let diff = get_diff(self.git, &self.source)?;
+        let mut prompt = self.prompt(&diff).await;
+        if let DiffSource::Range(range) = &self.source {
+            prompt = prompt.with_commits(self.git.commits(range)?);
+        }
+
+        let (message, origin) = self.message_inner(&prompt, on_token).await?;
+        let (message, template) = self.apply_tickets(message)?;
+        Ok(GeneratedCommit {
+            message,
+            template,
+            origin,
+        })
+    }
+
+    /// Builds the commit message prompt for a diff, with the scope inferred
+    /// from the repository layout as a hint and examples from the history if
+    /// enabled.
+    pub async fn prompt(&self, diff: &Diff) -> Prompt {
+        let config = self.config;
+        let mut prompt = Prompt::new(analyzer::analyze_diff(diff, &config.smart_commit))
+            .with_language(config.language.clone())
+            .with_changes(diff.clone());
+
+        if let Some(examples_config) = &config.examples {
+            match examples::sample(self.git, self.cache, examples_config, diff).await {
+                Ok(examples) => {
+                    debug!("Using {} example commit(s) from the history.", examples.len());
+                    prompt = prompt.with_examples(examples);
+                },
+                Err(e) => debug!("Skipping example commits: {}", e),
+            }
+        }
+
+        let default_scope_config = ScopeConfig::default();
+        let scope_config = config.scope.as_ref().unwrap_or(&default_scope_config);
+        if !scope_config.infer {
+            return prompt;
+        }
+
+        match self.git.root() {
+            Ok(root) => {
+                let inference = ScopeResolver::new(&root, scope_config).infer(diff);
+                debug!("Inferred scope {:?} from {:?}", inference.scope, inference.candidates);
+                prompt.with_scope(inference.scope, inference.candidates)
+            },
+            Err(e) => {
+                debug!("Skipping scope inference: {}", e);
+                prompt
+            },
+        }
+    }
+
+    /// Returns the commit message for a prompt, from the cache if possible
+    /// and from the provider otherwise.
+    pub async fn message(&self, prompt: &Prompt) -> Result<(CommitMessage, Origin)> {
+        self.message_inner(prompt, None).await
+    }
+
+    /// Like `message`, but passes the response to `on_token` as it arrives.
+    pub async fn message_streaming(
+        &self,
+        prompt: &Prompt,
+        on_token: TokenSink<'_>,
+    ) -> Result<(CommitMessage, Origin)> {
+        self.message_inner(prompt, Some(on_token)).await
+    }
+
+    async fn message_inner(
+        &self,
+        prompt: &Prompt,
+        on_token: Option<TokenSink<'_>>,
+    ) -> Result<(CommitMessage, Origin)> {
+        let config = self.config;
+        let (provider_name, model) = self.provider_id();
+        let cache_key = CacheKey::new(provider_name, model, prompt).digest();
+
+        if let Some(c) = self.cache {
+            match c.get(&cache_key).await {
+                Ok(Some(cached_message_str)) => {
+                    debug!("Cache hit for key: {}", cache_key);
+                    match serde_json::from_str(&cached_message_str) {
+                        Ok(msg) => {
+                            debug!("Commit message retrieved from cache.");
+                            return Ok((self.apply_scope_override(prompt, msg), Origin::Cache));
+                        },
+                        Err(e) => {
+                            error!("Failed to deserialize cached commit message: {}", e);
+                        },
+                    }
+                },
+                Ok(None) => {
+                    debug!("Cache miss for key: {}", cache_key);
+                },
+                Err(e) => {
+                    error!("Error retrieving from cache: {}", e);
+                },
+            }
+        }
+
+        debug!("Diff size: {} bytes", prompt.diff.len());
+        debug!(
+            "First 20 lines of diff:\n{}",
+            prompt
+                .diff
+                .lines()
+                .take(20)
+                .collect::<Vec<&str>>()
+                .join("\n")
+        );
+        debug!("Calling AI provider to analyze diff...");
+        let start_time = Instant::now();
+        // Failing to set up the provider, e.g. without an API key, is never
+        // covered by the fallback.
+        let provider = self.provider()?;
+        let provider = provider.get();
+        let result = match on_token {
+            Some(on_token) => provider.analyze_diff_streaming(prompt, on_token).await,
+            None => provider.analyze_diff(prompt).await,
+        };
+        let msg = match result {
+            Ok((msg, usage)) => {
+                let elapsed = start_time.elapsed();
+                usage::record(self.usage_log, usage, provider_name, model, "commit", elapsed).await;
+                msg
+            },
+            Err(e)
+                if provider_name != "heuristic"
+                    && config.heuristic_fallback.unwrap_or(false)
+                    && !matches!(e, Error::Config(_)) =>
+            {
+                // Not cached, so the configured provider is asked again next time.
+                warn!("{}; falling back to the heuristic provider.", e);
+                let msg = HeuristicProvider::new().describe(prompt);
+                return Ok((self.apply_scope_override(prompt, msg), Origin::Fallback));
+            },
+            Err(e) => return Err(e),
+        };
+        debug!("AI provider responded in {:?}", start_time.elapsed());
+
+        if let Some(c) = self.cache {
+            match serde_json::to_string(&msg) {
+                Ok(msg_str) => {
+                    let metadata = EntryMetadata {
+                        provider: Some(provider_name.to_string()),
+                        model: Some(model.to_string()),
+                        candidates: prompt.known_scopes.clone(),
+                    };
+                    if let Err(e) = c.set_with_metadata(&cache_key, &msg_str, &metadata).await {
+                        error!("Failed to set cache for key {}: {}", cache_key, e);
+                    }
+                },
+                Err(e) => {
+                    error!("Failed to serialize commit message for caching: {}", e);
+                },
+            }
+        }
+
+        Ok((self.apply_scope_override(prompt, msg), Origin::Provider))
+    }
+
+    /// Sends a free-form prompt, such as a pull request or release notes, and
+    /// returns the response. `kind` labels it in the cache and the usage log.
+    /// The heuristic fallback only describes commits, so it does not apply.
+    pub async fn complete(&self, kind: &str, prompt: &str) -> Result<String> {
+        self.complete_inner(kind, prompt, None).await
+    }
+
+    /// Like `complete`, but passes the response to `on_token` as it arrives.
+    pub async fn complete_streaming(
+        &self,
+        kind: &str,
+        prompt: &str,
+        on_token: TokenSink<'_>,
+    ) -> Result<String> {
+        self.complete_inner(kind, prompt, Some(on_token)).await
+    }
+
+    async fn complete_inner(
+        &self,
+        kind: &str,
+        prompt: &str,
+        on_token: Option<TokenSink<'_>>,
+    ) -> Result<String> {
+        let (provider_name, model) = self.provider_id();
+        let cache_key = text_key(kind, provider_name, model, prompt);
+
+        if let Some(c) = self.cache {
+            match c.get(&cache_key).await {
+                Ok(Some(text)) => {
+                    debug!("Cache hit for key: {}", cache_key);
+                    return Ok(text);
+                },
+                Ok(None) => debug!("Cache miss for key: {}", cache_key),
+                Err(e) => error!("Error retrieving from cache: {}", e),
+            }
+        }
+
+        debug!("Calling AI provider for the {}...", kind);
+        let start_time = Instant::now();
+        let provider = self.provider()?;
+        let provider = provider.get();
+        let completion = match on_token {
+            Some(on_token) => provider.complete_streaming(prompt, on_token).await?,
+            None => provider.complete(prompt).await?,
+        };
+        let elapsed = start_time.elapsed();
+        debug!("AI provider responded in {:?}", elapsed);
+        usage::record(self.usage_log, completion.usage, provider_name, model, kind, elapsed).await;
+
+        if let Some(c) = self.cache {
+            let metadata = EntryMetadata {
+                provider: Some(provider_name.to_string()),
+                model: Some(model.to_string()),
+                candidates: Vec::new(),
+            };
+            if let Err(e) = c
+                .set_with_metadata(&cache_key, &completion.text, &metadata)
+                .await
+            {
+                error!("Failed to set cache for key {}: {}", cache_key, e);
+            }
+        }
+
+        Ok(completion.text)
+    }
+
+    /// The provider given to `with_provider`, or the configured one.
+    fn provider(&self) -> Result<ProviderRef<'_>> {
+        match &self.provider {
+            Some(custom) => Ok(ProviderRef::Borrowed(custom.provider.as_ref())),
+            None => create_provider(self.config).map(ProviderRef::Owned),
+        }
+    }
+
+    /// The name and model of the provider `provider` returns.
+    fn provider_id(&self) -> (&str, &str) {
+        match &self.provider {
+            Some(custom) => (&custom.name, &custom.model),
+            None => (&self.config.provider, &self.config.model),
+        }
+    }
+
+    /// Replaces the model's scope with the inferred one when the
+    /// configuration asks for it.
+    fn apply_scope_override(&self, prompt: &Prompt, mut msg: CommitMessage) -> CommitMessage {
+        let override_model = self.config.scope.as_ref().is_some_and(|s| s.override_model);
+        if override_model && let Some(scope) = &prompt.scope {
+            msg.scope = Some(scope.clone());
+        }
+        msg
+    }
+
+    /// Adds the issue keys found in the current branch name to the message,
+    /// and returns it with the header template whose `{ticket}` placeholder
+    /// is filled.
+    ///
+    /// Keys are only attached to the message when a `ticket` section is
+    /// configured, but the placeholder always works with the default
+    /// patterns.
+    pub fn apply_tickets(&self, msg: CommitMessage) -> Result<(CommitMessage, String)> {
+        let config = self.config;
+        let uses_placeholder = config.commit_format.contains("{ticket}");
+        if config.ticket.is_none() && !uses_placeholder {
+            return Ok((msg, config.commit_format.clone()));
+        }
+
+        let default_ticket_config = TicketConfig::default();
+        let ticket_config = config.ticket.as_ref().unwrap_or(&default_ticket_config);
+        let tickets = match self.git.current_branch()? {
+            Some(branch) => {
+                let tickets = TicketExtractor::new(&ticket_config.patterns)?.extract(&branch);
+                debug!("Found tickets {:?} in branch '{}'", tickets, branch);
+                tickets
+            },
+            None => Vec::new(),
+        };
+
+        let msg = if config.ticket.is_some() && !uses_placeholder {
+            ticket::attach(msg, &tickets, ticket_config.placement, &ticket_config.footer_token)
+        } else {
+            msg
+        };
+        Ok((msg, ticket::fill_template(&config.commit_format, &tickets)))
+    }
+}
+
+/// A provider that is either owned by the caller or created for one call.
+enum ProviderRef<'a> {
+    Borrowed(&'a (dyn AIProvider + Send + Sync)),
+    Owned(Box<dyn AIProvider + Send + Sync>),
+}
+
+impl ProviderRef<'_> {
+    fn get(&self) -> &(dyn AIProvider + Send + Sync) {
+        match self {
+            ProviderRef::Borrowed(provider) => *provider,
+            ProviderRef::Owned(provider) => provider.as_ref(),
+        }
+    }
+}
+
+/// Opens the usage log unless recording is disabled. A log that cannot be
+/// opened is logged and skipped rather than failing generation.
+pub async fn open_usage_log(config: &Config) -> Option<UsageLog> {
+    match UsageLog::from_config(config).await {
+        Ok(log) => log,
+        Err(e) => {
+            error!("Failed to open the usage log: {}", e);
+            None
+        },
+    }
+}
+
+/// Opens the configured cache backend if caching is enabled. A cache that
+/// cannot be opened is logged and skipped rather than failing generation.
+pub async fn open_cache(config: &Config) -> Result<Option<Cache>> {
+    if !config.cache_enabled.unwrap_or(false) {
+        debug!("Cache not enabled in config, skipping cache initialization.");
+        return Ok(None);
+    }
+
+    let cache = match create_cache(config).await {
+        Ok(cache) => cache,
+        Err(e) => {
+            error!("Failed to initialize the cache: {}", e);
+            return Ok(None);
+        },
+    };
+
+    // Only a local file is cheap enough to sweep on every run. A shared cache
+    // is pruned by its server, Redis expires entries through their TTLs, and
+    // both are swept on demand by `gitsc cache prune`.
+    if configured_backend(config) != CacheBackendKind::Sqlite {
+        return Ok(Some(cache));
+    }
+
+    // Evict expired entries, and those beyond the configured limits.
+    match cache.prune(&PrunePolicy::from_config(config)?).await {
+        Ok(0) => {},
+        Ok(removed) => debug!("Pruned {} cache entries.", removed),
+        Err(e) => error!("Failed to prune the cache: {}", e),
+    }
+    Ok(Some(cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::cache::memory_cache::MemoryCache;
+    use crate::ai::repository::{Completion, Usage};
+    use crate::git::CliBackend;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every prompt with `answer`, reports 10 prompt and 2 completion
+    /// tokens, and counts the calls.
+    struct Stub {
+        calls: Arc<AtomicUsize>,
+        answer: fn() -> Result<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl AIProvider for Stub {
+        async fn complete(&self, _prompt: &str) -> Result<Completion> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Completion {
+                text: (self.answer)()?,
+                usage: Some(Usage {
+                    prompt_tokens: 10,
+                    completion_tokens: 2,
+                }),
+            })
+        }
+    }
+
+    fn stub(answer: fn() -> Result<String>) -> (Box<Stub>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let stub = Stub {
+            calls: calls.clone(),
+            answer,
+        };
+        (Box::new(stub), calls)
+    }
+
+    fn prompt() -> Prompt {
+        Prompt::new("diff --git a/src/lib.rs b/src/lib.rs\n")
+    }
+
+    #[tokio::test]
+    async fn caches_messages_under_the_provider_used() {
+        let config = Config::default();
+        let git = CliBackend::new(".");
+        let cache = MemoryCache::new(None);
+        let (provider, calls) = stub(|| Ok("feat: add a stub".to_string()));
+        let generator = Generator::new(&config, &git)
+            .with_cache(Some(&cache))
+            .with_provider("stub", "stub-1", provider);
+        let prompt = prompt();
+
+        let (msg, origin) = generator.message(&prompt).await.unwrap();
+        assert_eq!((msg.message.as_str(), origin), ("add a stub", Origin::Provider));
+        let (msg, origin) = generator.message(&prompt).await.unwrap();
+        assert_eq!((msg.message.as_str(), origin), ("add a stub", Origin::Cache));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let key = CacheKey::new("stub", "stub-1", &prompt).digest();
+        let entry = cache.entry(&key).await.unwrap().unwrap();
+        assert_eq!(entry.metadata.provider.as_deref(), Some("stub"));
+        assert_eq!(entry.metadata.model.as_deref(), Some("stub-1"));
+        let configured = CacheKey::new(&config.provider, &config.model, &prompt).digest();
+        assert_eq!(cache.get(&configured).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn records_usage_of_provider_answers() {
+        let path = std::env::temp_dir().join(format!("gitsc-usage-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = UsageLog::open(&path).await.unwrap();
+        let config = Config::default();
+        let git = CliBackend::new(".");
+        let cache = MemoryCache::new(None);
+        let (provider, _) = stub(|| Ok("feat: add a stub".to_string()));
+        let generator = Generator::new(&config, &git)
+            .with_cache(Some(&cache))
+            .with_usage_log(Some(&log))
+            .with_provider("stub", "stub-1", provider);
+
+        generator.message(&prompt()).await.unwrap();
+        // Cached answers cost nothing.
+        generator.message(&prompt()).await.unwrap();
+        let totals = log.totals(0).await.unwrap();
+        drop(log);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(totals.len(), 1);
+        let totals = &totals[0];
+        assert_eq!((totals.provider.as_str(), totals.model.as_str()), ("stub", "stub-1"));
+        assert_eq!((totals.requests, totals.prompt_tokens, totals.completion_tokens), (1, 10, 2));
+    }
+
+    #[tokio::test]
+    async fn caches_free_form_responses_by_kind() {
+        let config = Config::default();
+        let git = CliBackend::new(".");
+        let cache = MemoryCache::new(None);
+        let (provider, calls) = stub(|| Ok("Add a stub\n\nIt answers.".to_string()));
+        let generator = Generator::new(&config, &git)
+            .with_cache(Some(&cache))
+            .with_provider("stub", "stub-1", provider);
+
+        for _ in 0..2 {
+            let text = generator.complete("pr", "Write a PR").await.unwrap();
+            assert_eq!(text, "Add a stub\n\nIt answers.");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        generator.complete("changelog", "Write a PR").await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let key = text_key("pr", "stub", "stub-1", "Write a PR");
+        let entry = cache.entry(&key).await.unwrap().unwrap();
+        assert_eq!(entry.metadata.provider.as_deref(), Some("stub"));
+    }
+
+    #[tokio::test]
+    async fn falls_back_only_when_the_provider_fails_to_answer() {
+        let config = Config {
+            heuristic_fallback: Some(true),
+            ..Config::default()
+        };
+        let git = CliBackend::new(".");
+        let cache = MemoryCache::new(None);
+
+        let (provider, _) = stub(|| Err(Error::Ai("unreachable".to_string())));
+        let generator = Generator::new(&config, &git)
+            .with_cache(Some(&cache))
+            .with_provider("stub", "stub-1", provider);
+        let (_, origin) = generator.message(&prompt()).await.unwrap();
+        assert_eq!(origin, Origin::Fallback);
+        assert!(cache.list().await.unwrap().is_empty());
+
+        let (provider, _) = stub(|| Err(Error::Config("no API key".to_string())));
+        let generator = Generator::new(&config, &git).with_provider("stub", "stub-1", provider);
+        assert!(matches!(generator.message(&prompt()).await, Err(Error::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn does_not_fall_back_unless_enabled() {
+        let config = Config::default();
+        let git = CliBackend::new(".");
+        let (provider, _) = stub(|| Err(Error::Ai("unreachable".to_string())));
+        let generator = Generator::new(&config, &git).with_provider("stub", "stub-1", provider);
+        assert!(matches!(generator.message(&prompt()).await, Err(Error::Ai(_))));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs;
+use std::path::Path;
+
+/// Where GitHub looks for a pull request template, relative to the repository
+/// root.
+const TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+/// A generated pull request title and Markdown description.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PullRequest {
+    pub title: String,
+    pub description: String,
+}
+
+impl PullRequest {
+    /// Parses a model response whose first line is the title and the rest the
+    /// description.
+    ///
+    /// A `Title:` line is used wherever it is, dropping any preamble before
+    /// it, as is the line after a preamble ending in `:`. A response that
+    /// starts with a section heading or a list has no title, so it is all
+    /// description and `fallback_title` is used.
+    pub fn parse(text: &str, fallback_title: &str) -> Self {
+        let lines: Vec<&str> = text.trim().lines().collect();
+        let labelled = lines.iter().position(|line| title_label(line).is_some());
+        let (title, rest) = match labelled {
+            Some(index) => (title_label(lines[index]).unwrap_or_default(), &lines[index + 1..]),
+            None => {
+                let preamble = lines
+                    .first()
+                    .is_some_and(|line| line.trim_end().ends_with(':') && lines.len() > 1);
+                let lines = if preamble {
+                    let rest = &lines[1..];
+                    let start = rest.iter().position(|l| !l.trim().is_empty()).unwrap_or(0);
+                    &rest[start..]
+                } else {
+                    &lines[..]
+                };
+                match lines.first() {
+                    Some(first) if !starts_description(first) => (clean_title(first), &lines[1..]),
+                    _ => (String::new(), lines),
+                }
+            },
+        };
+
+        Self {
+            title: if title.is_empty() {
+                fallback_title.to_string()
+            } else {
+                title
+            },
+            description: rest.join("\n").trim().to_string(),
+        }
+    }
+
+    /// Renders the title on its own line, followed by the description.
+    pub fn render(&self) -> String {
+        format!("{}\n\n{}\n", self.title, self.description)
+    }
+}
+
+/// The title on a `Title: ...` line, if it is one.
+fn title_label(line: &str) -> Option<String> {
+    let line = line.trim().trim_start_matches(['#', '*']).trim_start();
+    let (label, title) = line.split_once(':')?;
+    label
+        .trim_end_matches('*')
+        .eq_ignore_ascii_case("title")
+        .then(|| clean_title(title))
+}
+
+/// Removes the Markdown heading marker and quotes models put around titles.
+fn clean_title(line: &str) -> String {
+    line.trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches(['"', '`', '*'])
+        .trim()
+        .to_string()
+}
+
+/// Whether a line already belongs to the description: a section heading, a
+/// list item or a table.
+fn starts_description(line: &str) -> bool {
+    let line = line.trim_start();
+    line.starts_with("##") || line.starts_with(['-', '*', '|']) || line.starts_with("1.")
+}
+
+/// Finds the repository's pull request template, if it has one.
+pub fn find_template(root: &Path) -> Option<String> {
+    TEMPLATE_PATHS
+        .iter()
+        .map(|path| root.join(path))
+        .find(|path| path.is_file())
+        .and_then(|path| fs::read_to_string(path).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_titles() {
+        let cases = [
+            (
+                "Add CSV export\n\n## Summary\nExports.",
+                "Add CSV export",
+                "## Summary\nExports.",
+            ),
+            ("# Add CSV export\n\nExports.", "Add CSV export", "Exports."),
+            ("Title: \"Add CSV export\"\n\nExports.", "Add CSV export", "Exports."),
+            ("**Title:** Add CSV export\n\nExports.", "Add CSV export", "Exports."),
+            ("Add CSV export", "Add CSV export", ""),
+        ];
+        for (text, title, description) in cases {
+            let pr = PullRequest::parse(text, "fallback");
+            assert_eq!(pr.title, title, "{}", text);
+            assert_eq!(pr.description, description, "{}", text);
+        }
+    }
+
+    #[test]
+    fn uses_the_fallback_without_a_title_line() {
+        for text in ["## Summary\nExports.", "- add export\n- add tests", ""] {
+            let pr = PullRequest::parse(text, "feat: add export");
+            assert_eq!(pr.title, "feat: add export", "{}", text);
+            assert_eq!(pr.description, text.trim(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn skips_text_around_the_title() {
+        let cases = [
+            "Sure! Here is the pull request.\n\nTitle: Add CSV export\n\n## Summary\nExports.",
+            "Here is the pull request:\n\nAdd CSV export\n\n## Summary\nExports.",
+        ];
+        for text in cases {
+            let pr = PullRequest::parse(text, "fallback");
+            assert_eq!(pr.title, "Add CSV export", "{}", text);
+            assert_eq!(pr.description, "## Summary\nExports.", "{}", text);
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use crate::git::CommitInfo;
+
+/// A commit together with the message it will be reworded to.
+#[derive(Debug, Clone)]
+pub struct RewordEntry {
+    pub commit: CommitInfo,
+    pub message: String,
+}
+
+/// Replaces the subject line of `original` with `subject`.
+///
+/// The body and trailers of the original message, such as `Signed-off-by`,
+/// are kept as they are.
+pub fn replace_subject(original: &str, subject: &str) -> String {
+    match original.split_once("\n\n") {
+        Some((_, body)) if !body.trim().is_empty() => {
+            format!("{}\n\n{}\n", subject.trim(), body.trim_end())
+        },
+        _ => format!("{}\n", subject.trim()),
+    }
+}
+
+/// Renders a before/after table of the planned rewording.
+pub fn render_table(entries: &[RewordEntry]) -> String {
+    const MAX_WIDTH: usize = 50;
+    let before: Vec<String> = entries
+        .iter()
+        .map(|e| truncate(e.commit.summary(), MAX_WIDTH))
+        .collect();
+    let width = before
+        .iter()
+        .map(|b| b.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(6);
+
+    let mut table = format!("{:<7}  {:<width$}  {}\n", "commit", "before", "after");
+    for (entry, before) in entries.iter().zip(&before) {
+        let after = entry.message.lines().next().unwrap_or("");
+        table.push_str(&format!("{:<7}  {:<width$}  {}\n", entry.commit.short_id(), before, after));
+    }
+    table
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let cut: String = text.chars().take(max - 3).collect();
+        format!("{}...", cut)
+    }
+}
@@ -0,0 +1,82 @@
+use crate::ai::repository::{CommitMessage, Footer};
+use crate::git::CommitInfo;
+
+/// Collects the footers of the squashed commits, such as `Refs` and
+/// `Co-authored-by`, in order of first appearance and without duplicates.
+pub fn collect_footers(commits: &[CommitInfo]) -> Vec<Footer> {
+    let mut footers: Vec<Footer> = Vec::new();
+    for commit in commits {
+        for footer in CommitMessage::parse(&commit.message).footers {
+            if !footers.contains(&footer) {
+                footers.push(footer);
+            }
+        }
+    }
+    footers
+}
+
+/// Combines the generated message with the footers of the original commits.
+///
+/// The squash is marked as breaking if the model said so or any original
+/// commit carried a `BREAKING CHANGE` footer.
+pub fn build_squash_message(mut generated: CommitMessage, commits: &[CommitInfo]) -> CommitMessage {
+    for footer in collect_footers(commits) {
+        if !generated.footers.contains(&footer) {
+            generated.footers.push(footer);
+        }
+    }
+    generated.breaking |= commits
+        .iter()
+        .any(|c| CommitMessage::parse(&c.message).breaking);
+    generated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(message: &str) -> CommitInfo {
+        CommitInfo {
+            id: String::new(),
+            author_name: String::new(),
+            author_email: String::new(),
+            time: 0,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn collects_footers_once_in_order() {
+        let commits = [
+            commit("fix: a\n\nRefs: #1\nCo-authored-by: A <a@example.com>"),
+            commit("fix: b\n\nRefs: #2\nCo-authored-by: A <a@example.com>"),
+            commit("fix: c\n\nRefs: #1"),
+        ];
+        assert_eq!(
+            collect_footers(&commits),
+            [
+                Footer::new("Refs", "#1"),
+                Footer::new("Co-authored-by", "A <a@example.com>"),
+                Footer::new("Refs", "#2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn carries_footers_and_breaking_changes_into_the_squash() {
+        let generated = CommitMessage::parse("feat: add export\n\nRefs: #1");
+        let commits = [
+            commit("feat: add csv\n\nRefs: #1"),
+            commit("feat!: change the format"),
+        ];
+        let msg = build_squash_message(generated, &commits);
+        assert_eq!(msg.footers, [Footer::new("Refs", "#1")]);
+        assert!(msg.breaking);
+
+        let generated = CommitMessage::parse("fix: a");
+        let commits = [commit("fix: b\n\nBREAKING CHANGE: removes the flag")];
+        let msg = build_squash_message(generated, &commits);
+        assert_eq!(msg.footers, [Footer::new("BREAKING CHANGE", "removes the flag")]);
+        assert!(msg.breaking);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::ai::repository::{CommitMessage, Footer};
+use crate::config::TicketPlacement;
+use crate::error::{Error, Result};
+use regex::Regex;
+
+/// Jira-style keys such as `PROJ-1234`, and GitHub/GitLab issue numbers such
+/// as `#88`.
+pub const DEFAULT_PATTERNS: &[&str] = &[r"\b[A-Z][A-Z0-9]+-\d+\b", r"#\d+\b"];
+
+/// Finds issue keys in branch names.
+#[derive(Debug)]
+pub struct TicketExtractor {
+    patterns: Vec<Regex>,
+}
+
+impl TicketExtractor {
+    /// Compiles the patterns. A pattern with a capture group yields the first
+    /// group instead of the whole match.
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self> {
+        let patterns = patterns
+            .iter()
+            .map(|p| {
+                Regex::new(p.as_ref()).map_err(|e| {
+                    Error::Config(format!("Invalid ticket pattern '{}': {}", p.as_ref(), e))
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// Returns the distinct keys found in `branch`, in order of appearance.
+    pub fn extract(&self, branch: &str) -> Vec<String> {
+        let mut found: Vec<(usize, String)> = Vec::new();
+        for pattern in &self.patterns {
+            for captures in pattern.captures_iter(branch) {
+                let Some(m) = captures.get(1).or_else(|| captures.get(0)) else {
+                    continue;
+                };
+                if !found.iter().any(|(_, key)| key == m.as_str()) {
+                    found.push((m.start(), m.as_str().to_string()));
+                }
+            }
+        }
+        found.sort_by_key(|(start, _)| *start);
+        found.into_iter().map(|(_, key)| key).collect()
+    }
+}
+
+impl Default for TicketExtractor {
+    fn default() -> Self {
+        Self::new(DEFAULT_PATTERNS).expect("default ticket patterns are valid")
+    }
+}
+
+/// Attaches the keys to a commit message, skipping those it already mentions.
+pub fn attach(
+    mut commit: CommitMessage,
+    tickets: &[String],
+    placement: TicketPlacement,
+    footer_token: &str,
+) -> CommitMessage {
+    let mentioned = |key: &str| {
+        commit.message.contains(key) || commit.footers.iter().any(|f| f.value.contains(key))
+    };
+    let missing: Vec<&str> = tickets
+        .iter()
+        .map(String::as_str)
+        .filter(|key| !mentioned(key))
+        .collect();
+    if missing.is_empty() {
+        return commit;
+    }
+
+    match placement {
+        TicketPlacement::Footer => {
+            commit
+                .footers
+                .push(Footer::new(footer_token, missing.join(", ")));
+        },
+        TicketPlacement::Prefix => {
+            commit.message = format!("{} {}", missing.join(" "), commit.message);
+        },
+        TicketPlacement::None => {},
+    }
+    commit
+}
+
+/// Fills the `{ticket}` placeholder of a header template. Without keys, the
+/// placeholder is removed together with the brackets or parentheses around it.
+pub fn fill_template(template: &str, tickets: &[String]) -> String {
+    if !template.contains("{ticket}") {
+        return template.to_string();
+    }
+    if !tickets.is_empty() {
+        return template.replace("{ticket}", &tickets.join(" "));
+    }
+    template
+        .replace("[{ticket}]", "")
+        .replace("({ticket})", "")
+        .replace("{ticket}", "")
+        .trim()
+        .replace("  ", " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| k.to_string()).collect()
+    }
+
+    /// The subject and footers of a commit, with footers as `Token: value`.
+    fn outline(commit: &CommitMessage) -> (&str, Vec<String>) {
+        let footers = commit
+            .footers
+            .iter()
+            .map(|f| format!("{}: {}", f.token, f.value))
+            .collect();
+        (commit.message.as_str(), footers)
+    }
+
+    #[test]
+    fn extracts_keys_from_branch_names() {
+        let cases: &[(&[&str], &str, &[&str])] = &[
+            (DEFAULT_PATTERNS, "feature/PROJ-1234-login", &["PROJ-1234"]),
+            (DEFAULT_PATTERNS, "fix/#88-crash", &["#88"]),
+            (DEFAULT_PATTERNS, "ABC-1/DEF-22-and-#3", &["ABC-1", "DEF-22", "#3"]),
+            (DEFAULT_PATTERNS, "PROJ-1-PROJ-1", &["PROJ-1"]),
+            (DEFAULT_PATTERNS, "proj-1234-lowercase", &[]),
+            (DEFAULT_PATTERNS, "main", &[]),
+            // A capture group yields only the group.
+            (&[r"issue-(\d+)"], "fix/issue-42-crash", &["42"]),
+            // Keys are ordered by position, whichever pattern found them.
+            (&[r"#(\d+)", r"[A-Z]+-\d+"], "ABC-1-#2", &["ABC-1", "2"]),
+        ];
+        for (patterns, branch, expected) in cases {
+            let extractor = TicketExtractor::new(patterns).unwrap();
+            assert_eq!(extractor.extract(branch), keys(expected), "{}", branch);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(TicketExtractor::new(&["("]), Err(Error::Config(_))));
+    }
+
+    #[test]
+    fn attaches_keys_by_placement() {
+        let commit = CommitMessage::parse("feat(auth): add login");
+        let tickets = keys(&["PROJ-1", "#2"]);
+        let cases = [
+            (TicketPlacement::Footer, "add login", &["Refs: PROJ-1, #2"][..]),
+            (TicketPlacement::Prefix, "PROJ-1 #2 add login", &[]),
+            (TicketPlacement::None, "add login", &[]),
+        ];
+        for (placement, message, footers) in cases {
+            let attached = attach(commit.clone(), &tickets, placement, "Refs");
+            assert_eq!(outline(&attached), (message, keys(footers)), "{:?}", placement);
+        }
+    }
+
+    #[test]
+    fn skips_keys_already_present() {
+        let tickets = keys(&["PROJ-1", "#2"]);
+        let cases = [
+            (
+                "feat: PROJ-1 add login",
+                TicketPlacement::Footer,
+                ("PROJ-1 add login", &["Refs: #2"][..]),
+            ),
+            (
+                "feat: add login\n\nCloses: #2",
+                TicketPlacement::Prefix,
+                ("PROJ-1 add login", &["Closes: #2"][..]),
+            ),
+            (
+                "feat: add login\n\nRefs: PROJ-1, #2",
+                TicketPlacement::Footer,
+                ("add login", &["Refs: PROJ-1, #2"][..]),
+            ),
+        ];
+        for (text, placement, (message, footers)) in cases {
+            let attached = attach(CommitMessage::parse(text), &tickets, placement, "Refs");
+            assert_eq!(outline(&attached), (message, keys(footers)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn fills_the_ticket_placeholder() {
+        let cases: &[(&str, &[&str], &str)] = &[
+            ("[{ticket}] {type}: {message}", &["PROJ-1"], "[PROJ-1] {type}: {message}"),
+            ("{type}: {message} ({ticket})", &["#1", "#2"], "{type}: {message} (#1 #2)"),
+            ("[{ticket}] {type}: {message}", &[], "{type}: {message}"),
+            ("{type}: {message} ({ticket})", &[], "{type}: {message}"),
+            ("{type}: {ticket} {message}", &[], "{type}: {message}"),
+            ("{type}: {message}", &["PROJ-1"], "{type}: {message}"),
+        ];
+        for (template, tickets, expected) in cases {
+            assert_eq!(fill_template(template, &keys(tickets)), *expected, "{}", template);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Records the tokens each provider call consumed, for `gitsc usage`.
+
+use globset::Glob;
+use log::{debug, error};
+use rusqlite::params;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio_rusqlite::Connection;
+
+use crate::ai::cache::sqlite_cache::migrate;
+use crate::ai::cache::{expand_home, now};
+use crate::ai::repository::Usage;
+use crate::config::{Config, Price};
+use crate::error::{Error, Result};
+
+/// Where usage is recorded when `usage.path` is not set.
+pub const DEFAULT_USAGE_PATH: &str = "~/.cache/gitsc/usage.db";
+
+/// Schema migrations, in order, tracked through `PRAGMA user_version` like
+/// the cache.
+const MIGRATIONS: &[&str] = &["CREATE TABLE IF NOT EXISTS usage (
+        timestamp INTEGER NOT NULL,
+        provider TEXT NOT NULL,
+        model TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        prompt_tokens INTEGER NOT NULL,
+        completion_tokens INTEGER NOT NULL,
+        duration_ms INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS usage_timestamp ON usage (timestamp);"];
+
+/// One provider call.
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub provider: String,
+    pub model: String,
+    /// What was generated: `commit`, `pr` or `changelog`.
+    pub kind: String,
+    pub usage: Usage,
+    pub duration_ms: u64,
+}
+
+/// The usage of one provider and model over a period.
+#[derive(Debug, Clone)]
+pub struct UsageTotals {
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl UsageTotals {
+    /// The estimated cost in US dollars, if the model has a price.
+    pub fn cost(&self, prices: &BTreeMap<String, Price>) -> Option<f64> {
+        price_for(prices, &self.model).map(|price| {
+            price.cost(Usage {
+                prompt_tokens: self.prompt_tokens,
+                completion_tokens: self.completion_tokens,
+            })
+        })
+    }
+}
+
+impl Price {
+    /// The cost of `usage` in US dollars.
+    pub fn cost(&self, usage: Usage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Looks up the price of a model: an exact key wins, otherwise the longest
+/// matching glob, so `gpt-4o*` is preferred to `gpt-4*` for `gpt-4o-mini`.
+pub fn price_for<'a>(prices: &'a BTreeMap<String, Price>, model: &str) -> Option<&'a Price> {
+    prices.get(model).or_else(|| {
+        prices
+            .iter()
+            .filter(|(pattern, _)| {
+                Glob::new(pattern).is_ok_and(|glob| glob.compile_matcher().is_match(model))
+            })
+            // The first of equally long patterns, in key order.
+            .rev()
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, price)| price)
+    })
+}
+
+pub struct UsageLog {
+    conn: Connection,
+}
+
+impl UsageLog {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path).await?;
+        let version = conn.call(|conn| Ok(migrate(conn, MIGRATIONS)?)).await?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::Config(format!(
+                "The usage log at {} was created by a newer version of gitsc (schema {}, expected {}).",
+                path.display(),
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        Ok(Self { conn })
+    }
+
+    /// Opens the log configured in `usage`, or returns `None` when recording
+    /// is disabled.
+    pub async fn from_config(config: &Config) -> Result<Option<Self>> {
+        let usage = config.usage.clone().unwrap_or_default();
+        if !usage.enabled {
+            return Ok(None);
+        }
+        let path = usage
+            .path
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_USAGE_PATH));
+        let path = expand_home(&path)?;
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)?;
+        }
+        Ok(Some(Self::open(&path).await?))
+    }
+
+    pub async fn record(&self, record: UsageRecord) -> Result<()> {
+        self.conn
+            .call(move |conn| {
+                conn.execute(
+                    "INSERT INTO usage (timestamp, provider, model, kind, prompt_tokens,
+                        completion_tokens, duration_ms)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        record.timestamp,
+                        record.provider,
+                        record.model,
+                        record.kind,
+                        record.usage.prompt_tokens,
+                        record.usage.completion_tokens,
+                        record.duration_ms
+                    ],
+                )?;
+                Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Sums the calls made at or after `since`, per provider and model, most
+    /// used first.
+    pub async fn totals(&self, since: u64) -> Result<Vec<UsageTotals>> {
+        let totals = self
+            .conn
+            .call(move |conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT provider, model, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens)
+                     FROM usage WHERE timestamp >= ?1
+                     GROUP BY provider, model
+                     ORDER BY SUM(prompt_tokens) + SUM(completion_tokens) DESC",
+                )?;
+                let rows = stmt.query_map(params![since], |row| {
+                    Ok(UsageTotals {
+                        provider: row.get(0)?,
+                        model: row.get(1)?,
+                        requests: row.get(2)?,
+                        prompt_tokens: row.get(3)?,
+                        completion_tokens: row.get(4)?,
+                    })
+                })?;
+                Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+            })
+            .await?;
+        Ok(totals)
+    }
+}
+
+/// Logs the tokens a response used and adds them to `log` as a call of
+/// `kind` to `model` of `provider_name`. Failing to record is not worth
+/// failing the command for, so errors are only logged.
+pub async fn record(
+    log: Option<&UsageLog>,
+    usage: Option<Usage>,
+    provider_name: &str,
+    model: &str,
+    kind: &str,
+    elapsed: Duration,
+) {
+    let Some(usage) = usage else {
+        return;
+    };
+    debug!(
+        "Token usage: {} prompt + {} completion = {} tokens",
+        usage.prompt_tokens,
+        usage.completion_tokens,
+        usage.total()
+    );
+    let Some(log) = log else {
+        return;
+    };
+    let record = UsageRecord {
+        timestamp: now(),
+        provider: provider_name.to_string(),
+        model: model.to_string(),
+        kind: kind.to_string(),
+        usage,
+        duration_ms: elapsed.as_millis() as u64,
+    };
+    if let Err(e) = log.record(record).await {
+        error!("Failed to record token usage: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prices(entries: &[(&str, f64)]) -> BTreeMap<String, Price> {
+        entries
+            .iter()
+            .map(|&(pattern, input)| (pattern.to_string(), Price { input, output: 0.0 }))
+            .collect()
+    }
+
+    #[test]
+    fn prefers_the_most_specific_price() {
+        let prices = prices(&[
+            ("gpt-4*", 1.0),
+            ("gpt-4o*", 2.0),
+            ("gpt-4o-mini", 3.0),
+            ("*", 4.0),
+        ]);
+        let input = |model| price_for(&prices, model).map(|price| price.input);
+
+        assert_eq!(input("gpt-4o-mini"), Some(3.0));
+        assert_eq!(input("gpt-4o-2024-08-06"), Some(2.0));
+        assert_eq!(input("gpt-4-turbo"), Some(1.0));
+        assert_eq!(input("gemini-2.5-flash"), Some(4.0));
+    }
+
+    #[test]
+    fn has_no_price_for_unknown_models() {
+        let prices = prices(&[("gpt-4*", 1.0), ("[", 2.0)]);
+        assert!(price_for(&prices, "llama3").is_none());
+    }
+}
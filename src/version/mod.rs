@@ -0,0 +1,270 @@
+use crate::ai::repository::CommitMessage;
+use crate::error::{Error, Result};
+use crate::git::CommitInfo;
+use semver::{Prerelease, Version};
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// How much a set of changes requires the version to move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bump {
+    None,
+    Patch,
+    Minor,
+    Major,
+}
+
+impl fmt::Display for Bump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Patch => write!(f, "patch"),
+            Self::Minor => write!(f, "minor"),
+            Self::Major => write!(f, "major"),
+        }
+    }
+}
+
+/// A commit that contributed to the version bump.
+#[derive(Debug, Clone)]
+pub struct Reason {
+    pub id: String,
+    pub summary: String,
+    pub bump: Bump,
+}
+
+/// A release tag parsed as a semantic version.
+#[derive(Debug, Clone)]
+pub struct ReleaseTag {
+    pub name: String,
+    pub version: Version,
+}
+
+impl ReleaseTag {
+    /// Parses tags such as `v1.2.3` and `1.2.3-rc.1`. Returns `None` for tags
+    /// that are not semantic versions.
+    pub fn parse(name: &str) -> Option<Self> {
+        let version = Version::parse(name.strip_prefix('v').unwrap_or(name)).ok()?;
+        Some(Self {
+            name: name.to_string(),
+            version,
+        })
+    }
+}
+
+/// Returns the highest release tag, and the highest one that is not a
+/// pre-release.
+pub fn latest_tags(tags: &[String]) -> (Option<ReleaseTag>, Option<ReleaseTag>) {
+    let mut parsed: Vec<ReleaseTag> = tags.iter().filter_map(|t| ReleaseTag::parse(t)).collect();
+    parsed.sort_by(|a, b| a.version.cmp(&b.version));
+    let stable = parsed
+        .iter()
+        .rev()
+        .find(|t| t.version.pre.is_empty())
+        .cloned();
+    (parsed.pop(), stable)
+}
+
+/// Returns the bump a Conventional Commit calls for: major for breaking
+/// changes, minor for features and patch for fixes and performance work.
+pub fn bump_for(commit: &CommitInfo) -> Bump {
+    let message = CommitMessage::parse(&commit.message);
+    if message.breaking {
+        Bump::Major
+    } else {
+        match message.commit_type.to_lowercase().as_str() {
+            "feat" => Bump::Minor,
+            "fix" | "perf" => Bump::Patch,
+            _ => Bump::None,
+        }
+    }
+}
+
+/// Returns the commits that call for a bump, and the largest bump among them.
+pub fn required_bump(commits: &[CommitInfo]) -> (Bump, Vec<Reason>) {
+    let reasons: Vec<Reason> = commits
+        .iter()
+        .map(|c| Reason {
+            id: c.short_id().to_string(),
+            summary: c.summary().to_string(),
+            bump: bump_for(c),
+        })
+        .filter(|r| r.bump != Bump::None)
+        .collect();
+    let bump = reasons.iter().map(|r| r.bump).max().unwrap_or(Bump::None);
+    (bump, reasons)
+}
+
+/// Computes the next version from the last stable release.
+///
+/// With a pre-release channel such as `rc`, the result is `<next>-rc.N`, where
+/// `N` continues from the latest pre-release of the same version and channel.
+pub fn next_version(
+    stable: &Version,
+    bump: Bump,
+    channel: Option<&str>,
+    latest: Option<&Version>,
+) -> Result<Version> {
+    let mut next = stable.clone();
+    match bump {
+        Bump::None => {},
+        Bump::Patch => next.patch += 1,
+        Bump::Minor => {
+            next.minor += 1;
+            next.patch = 0;
+        },
+        Bump::Major => {
+            next.major += 1;
+            next.minor = 0;
+            next.patch = 0;
+        },
+    }
+    next.pre = Prerelease::EMPTY;
+    next.build = semver::BuildMetadata::EMPTY;
+
+    let Some(channel) = channel else {
+        return Ok(next);
+    };
+
+    let number = latest
+        .filter(|l| (l.major, l.minor, l.patch) == (next.major, next.minor, next.patch))
+        .and_then(|l| {
+            let (name, number) = l.pre.as_str().rsplit_once('.')?;
+            (name == channel).then(|| number.parse::<u64>().ok())?
+        })
+        .map_or(1, |n| n + 1);
+
+    next.pre = Prerelease::new(&format!("{}.{}", channel, number))
+        .map_err(|e| Error::Config(format!("Invalid pre-release channel '{}': {}", channel, e)))?;
+    Ok(next)
+}
+
+/// Updates the `version` in a `Cargo.toml`, keeping its formatting.
+pub fn write_cargo_toml(path: &Path, version: &Version) -> Result<()> {
+    let content = fs::read_to_string(path)?;
+    let mut document: toml_edit::DocumentMut = content
+        .parse()
+        .map_err(|e| Error::Config(format!("Failed to parse {}: {}", path.display(), e)))?;
+
+    // Workspace roots keep the shared version in `[workspace.package]`.
+    let item = if document.contains_key("package") {
+        document["package"].get_mut("version")
+    } else {
+        document
+            .get_mut("workspace")
+            .and_then(|w| w.get_mut("package"))
+            .and_then(|p| p.get_mut("version"))
+    };
+    match item {
+        Some(item) if item.is_str() => *item = toml_edit::value(version.to_string()),
+        _ => {
+            return Err(Error::Config(format!("No version string found in {}", path.display())));
+        },
+    }
+
+    fs::write(path, document.to_string())?;
+    Ok(())
+}
+
+/// Updates the top-level `version` in a `package.json`, leaving the rest of the
+/// file untouched.
+pub fn write_package_json(path: &Path, version: &Version) -> Result<()> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str::<serde_json::Value>(&content).map_err(std::io::Error::from)?;
+
+    // Replace the value in place so key order and indentation are preserved.
+    let span = top_level_string(&content, "version")
+        .ok_or_else(|| Error::Config(format!("No version string found in {}", path.display())))?;
+    let updated = format!("{}\"{}\"{}", &content[..span.start], version, &content[span.end..]);
+    fs::write(path, updated)?;
+    Ok(())
+}
+
+/// Finds the string value of `key` in the outermost object of a JSON document
+/// and returns its span, quotes included. Keys of nested objects, such as
+/// `scripts.version`, are skipped.
+fn top_level_string(content: &str, key: &str) -> Option<Range<usize>> {
+    let bytes = content.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                let end = string_end(bytes, i)?;
+                let rest = content[end..].trim_start();
+                if depth == 1
+                    && let Some(value) = rest.strip_prefix(':')
+                    && serde_json::from_str::<String>(&content[i..end]).ok()? == key
+                {
+                    let start = content.len() - value.trim_start().len();
+                    return match bytes.get(start) {
+                        Some(b'"') => Some(start..string_end(bytes, start)?),
+                        _ => None,
+                    };
+                }
+                i = end;
+                continue;
+            },
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth -= 1,
+            _ => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+/// Returns the index just past the closing quote of the string starting at
+/// `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    const PACKAGE_JSON: &str = r#"{
+  "name": "app",
+  "scripts": { "version": "1.2.3" },
+  "dependencies": { "lib": "1.2.3" },
+  "description": "see \"version\": \"1.2.3\"",
+  "version" :  "1.2.3",
+  "files": ["version"]
+}
+"#;
+
+    #[test]
+    fn finds_the_top_level_version_only() {
+        let span = top_level_string(PACKAGE_JSON, "version").unwrap();
+        assert_eq!(&PACKAGE_JSON[span.clone()], "\"1.2.3\"");
+        assert!(PACKAGE_JSON[..span.start].ends_with("\"version\" :  "));
+        assert_eq!(top_level_string(r#"{"a": {"version": "1"}}"#, "version"), None);
+        assert_eq!(top_level_string(r#"{"version": 1}"#, "version"), None);
+    }
+
+    #[test]
+    fn writes_package_json_in_place() {
+        let path = env::temp_dir().join(format!("gitsc-package-{}.json", std::process::id()));
+        fs::write(&path, PACKAGE_JSON).unwrap();
+        write_package_json(&path, &Version::new(2, 0, 0)).unwrap();
+        let updated = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            updated,
+            PACKAGE_JSON.replace("\"version\" :  \"1.2.3\"", "\"version\" :  \"2.0.0\"")
+        );
+    }
+}